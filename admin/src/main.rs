@@ -0,0 +1,71 @@
+use blazed_demo::*;
+use clap::Parser;
+use std::{
+    io::{BufRead, stdin},
+    net::SocketAddr,
+    process::ExitCode,
+};
+
+/// Send administrative commands to a running server.
+#[derive(Parser, Debug)]
+struct Config {
+    /// Remote (loopback) TCP address of the admin control socket
+    #[arg(short, long, default_value_t = get_socket_addr(ADMIN_PORT))]
+    addr: SocketAddr,
+
+    /// Shared secret of the admin control socket [env: BLAZED_ADMIN_SECRET]
+    #[arg(short, long)]
+    secret: Option<String>,
+
+    /// Command to execute (one per line from stdin otherwise), e.g., `status`
+    #[arg(trailing_var_arg = true)]
+    command: Vec<String>,
+}
+
+/// Execute a single command line, printing its output.
+fn execute(conn: &mut ControlClient, line: &str) -> BlazedResult<bool> {
+    match conn.send(&Command::parse(line)?)? {
+        Ok(out) => {
+            if !out.is_empty() {
+                println!("{out}");
+            }
+            Ok(true)
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            Ok(false)
+        }
+    }
+}
+
+fn run(cfg: Config) -> BlazedResult<bool> {
+    let secret = cfg
+        .secret
+        .or_else(|| std::env::var(ADMIN_SECRET_VAR).ok())
+        .ok_or(format!("No secret given (--secret or {ADMIN_SECRET_VAR})."))?;
+    let mut conn = ControlClient::connect(cfg.addr, &secret)?;
+
+    if !cfg.command.is_empty() {
+        return execute(&mut conn, &cfg.command.join(" "));
+    }
+
+    // a script of commands (stopping at the first failure)
+    for line in stdin().lock().lines() {
+        let line = line?;
+        if !line.trim().is_empty() && !execute(&mut conn, &line)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn main() -> ExitCode {
+    match run(Config::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
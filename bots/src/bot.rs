@@ -0,0 +1,158 @@
+use crate::*;
+use blazed_demo::*;
+use crossbeam_channel::TryRecvError;
+use std::{
+    net::SocketAddr,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// A simulated player, as seen by the server.
+pub struct Bot {
+    conn: Connection,
+    collider: Collider,
+    movement: Movement,
+    cam: Option<CameraAttr>, // once the server has placed the bot
+    check: DesyncCheck,
+}
+
+impl Bot {
+    /// Join the server exactly like the client does.
+    pub fn join(
+        tcp_addr: SocketAddr,
+        udp_addr: SocketAddr,
+        hello: ClientHandshake,
+        stats: &mut Stats,
+    ) -> BlazedResult<Self> {
+        let start = Instant::now();
+        let conn = Connection::connect(tcp_addr, udp_addr, None, hello, Clock::default())?;
+        stats.join = Some(start.elapsed());
+
+        let Ok(NetEvent::Joined {
+            world, movement, ..
+        }) = conn.events().recv()
+        else {
+            return Err("Expected to have joined".into());
+        };
+
+        Ok(Self {
+            conn,
+            collider: world.collider(),
+            movement,
+            cam: None,
+            check: DesyncCheck::default(),
+        })
+    }
+
+    /// Play (and measure) until the time is up, the server disconnects or anything fails.
+    pub fn play(
+        mut self,
+        mut driver: Driver,
+        duration: Duration,
+        stats: &mut Stats,
+    ) -> BlazedResult {
+        let start = Instant::now();
+        let mut next_tick = start;
+        let mut keys = Keys::empty();
+
+        loop {
+            let now = Instant::now();
+            stats.played = now - start;
+            if stats.played >= duration {
+                return Ok(());
+            }
+
+            // whatever the server has said
+            self.recv_all(stats)?;
+
+            // the next step of the script
+            if let Some((next, (xrel, yrel))) = driver.next(now) {
+                self.input(&mut keys, next, xrel, yrel)?;
+            }
+
+            // predict (like the client) to compare against the server
+            if let Some(cam) = &mut self.cam
+                && (!keys.is_empty() || !cam.is_resting(&self.movement))
+            {
+                cam.input(keys, &self.collider, &self.movement);
+            }
+
+            next_tick += GAME_SPEED;
+            sleep(next_tick.saturating_duration_since(Instant::now()));
+        }
+    }
+
+    /// Handle every event received so far.
+    fn recv_all(&mut self, stats: &mut Stats) -> BlazedResult {
+        let id = self.conn.id();
+
+        loop {
+            let event = match self.conn.events().try_recv() {
+                Ok(event) => event,
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => return Err("Disconnected".into()),
+            };
+
+            match event {
+                NetEvent::Synced(sample) => stats.rtts.push(sample.rtt()),
+                NetEvent::Movement(movement) => self.movement = movement,
+                NetEvent::Added(data) if data.id == id => self.cam = Some(data.cam),
+                NetEvent::Respawned { id: obj_id, pos } if obj_id == id => {
+                    if let Some(cam) = &mut self.cam {
+                        cam.respawn(pos);
+
+                        // placed elsewhere, so nothing can be predicted from the latest snapshot
+                        self.check.reset();
+                    }
+                }
+                NetEvent::Snapshot(snapshot) => {
+                    let desync = self.check.check(snapshot, &self.collider, &self.movement);
+                    stats.snapshots = self.check.checked();
+                    stats.desyncs.extend(desync);
+                }
+                NetEvent::Updated(mut data) => {
+                    stats.updates += 1;
+
+                    // correct the prediction
+                    if let Some(cam) = &mut self.cam
+                        && data.id == id
+                    {
+                        if let Some(eye) = data.cam.eye {
+                            stats.corrections.push((cam.eye - eye).mag());
+                        }
+                        cam.patch(&mut data.cam);
+                    }
+                }
+                NetEvent::Disconnected(Some(reason)) => {
+                    return Err(format!("Disconnected by server: {reason}").into());
+                }
+                NetEvent::Disconnected(None) => return Err("Disconnected by server".into()),
+                _ => (),
+            }
+        }
+    }
+
+    /// Press and release keys (as they have changed) and turn.
+    fn input(&mut self, keys: &mut Keys, next: Keys, xrel: i32, yrel: i32) -> BlazedResult {
+        for (bits, is_pressed) in [(*keys - next, false), (next - *keys, true)] {
+            if !bits.is_empty() {
+                let input = self.conn.send(Input::key(bits, is_pressed))?;
+                self.check.sent(input);
+            }
+        }
+        *keys = next;
+
+        if (xrel, yrel) != (0, 0) {
+            let input = self.conn.send(Input::Motion(MotionOpt {
+                xrel: Some(xrel),
+                yrel: Some(yrel),
+                seq: 0,
+            }))?;
+            self.check.sent(input);
+            if let Some(cam) = &mut self.cam {
+                cam.look_at(xrel, yrel);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+mod bot;
+mod script;
+mod stats;
+mod swarm;
+
+pub use bot::*;
+pub use script::*;
+pub use stats::*;
+pub use swarm::*;
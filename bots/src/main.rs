@@ -0,0 +1,72 @@
+use blazed_bots::*;
+use blazed_demo::*;
+use clap::Parser;
+use std::{net::SocketAddr, path::PathBuf, process::ExitCode, time::Duration};
+
+// Headless players for load and soak testing a server.
+#[derive(Parser, Debug)]
+struct Config {
+    /// Remote TCP address of the server
+    #[arg(short, long, default_value_t = get_socket_addr(TCP_PORT))]
+    tcp_addr: SocketAddr,
+
+    /// Remote UDP address of the server
+    #[arg(short, long, default_value_t = get_socket_addr(UDP_PORT))]
+    udp_addr: SocketAddr,
+
+    /// Number of bots
+    #[arg(short = 'n', long, default_value_t = 8)]
+    count: usize,
+
+    /// Seconds each bot plays for (once joined)
+    #[arg(short, long, default_value_t = 10.0)]
+    duration: f32,
+
+    /// Milliseconds between each bot joining
+    #[arg(short, long, default_value_t = 0)]
+    ramp: u64,
+
+    /// Movement script every bot follows (random movement otherwise)
+    #[arg(short, long)]
+    script: Option<PathBuf>,
+
+    /// Seed of the random movement (each bot's is offset by its index)
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Name of every bot (deduplicated by the server)
+    #[arg(long, default_value = "bot")]
+    name: String,
+}
+
+fn run(cfg: Config) -> BlazedResult<bool> {
+    let swarm = Swarm {
+        tcp_addr: cfg.tcp_addr,
+        udp_addr: cfg.udp_addr,
+        count: cfg.count,
+        duration: Duration::try_from_secs_f32(cfg.duration)
+            .map_err(|_| format!("Invalid duration {}", cfg.duration))?,
+        ramp: Duration::from_millis(cfg.ramp),
+        script: cfg
+            .script
+            .map(|path| Script::parse(&std::fs::read_to_string(path)?))
+            .transpose()?,
+        seed: cfg.seed,
+        name: Name::new(&cfg.name)?,
+    };
+
+    let report = swarm.run()?;
+    print!("{report}");
+    Ok(report.is_success())
+}
+
+fn main() -> ExitCode {
+    match run(Config::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
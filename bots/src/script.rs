@@ -0,0 +1,223 @@
+use blazed_demo::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// keys a randomly moving bot picks from
+const RANDOM_KEYS: [Keys; 6] = [Keys::W, Keys::A, Keys::S, Keys::D, Keys::SPACE, Keys::CTRL];
+
+/// bounds of how long a randomly moving bot keeps doing the same thing (ms)
+const RANDOM_HOLD: [u64; 2] = [200, 1500];
+
+/// A single instruction of a movement script.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    Hold(Keys, Duration), // press exactly these keys for a while (none to wait)
+    Look(i32, i32),       // turn (as if the mouse had moved)
+}
+
+/// A movement script, one instruction per line (repeated until the bot is done), e.g.,
+///
+/// ```text
+/// # run forward, turn around, then jump back
+/// hold W+SHIFT 1.5
+/// look 60 0
+/// hold S+SPACE 0.5
+/// wait 1
+/// ```
+#[derive(Clone, Debug)]
+pub struct Script {
+    steps: Arc<[Step]>,
+}
+
+impl Script {
+    pub fn parse(s: &str) -> BlazedResult<Self> {
+        let mut steps = Vec::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let step = Self::parse_step(line).map_err(|e| format!("line {}: {e}", i + 1))?;
+            steps.push(step);
+        }
+
+        // a script that never waits would spin forever
+        if !steps
+            .iter()
+            .any(|step| matches!(step, Step::Hold(_, dur) if !dur.is_zero()))
+        {
+            return Err("Script never holds nor waits".into());
+        }
+        Ok(Self {
+            steps: steps.into(),
+        })
+    }
+
+    fn parse_step(line: &str) -> BlazedResult<Step> {
+        let args = line.split_whitespace().collect::<Vec<_>>();
+
+        match args.as_slice() {
+            ["hold", keys, secs] => Ok(Step::Hold(parse_keys(keys)?, parse_secs(secs)?)),
+            ["wait", secs] => Ok(Step::Hold(Keys::empty(), parse_secs(secs)?)),
+            ["look", xrel, yrel] => Ok(Step::Look(xrel.parse()?, yrel.parse()?)),
+            _ => Err(format!("Unknown step `{line}`").into()),
+        }
+    }
+}
+
+/// Parse keys joined by `+`, e.g., `W+SHIFT`.
+fn parse_keys(s: &str) -> BlazedResult<Keys> {
+    s.split('+').try_fold(Keys::empty(), |keys, name| {
+        Keys::from_name(&name.to_uppercase())
+            .map(|key| keys | key)
+            .ok_or_else(|| format!("Unknown key `{name}`").into())
+    })
+}
+
+fn parse_secs(s: &str) -> BlazedResult<Duration> {
+    s.parse::<f32>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f32(secs).ok())
+        .ok_or_else(|| format!("Invalid duration `{s}`").into())
+}
+
+/// What a bot does next.
+#[derive(Debug)]
+pub enum Driver {
+    Script {
+        script: Script,
+        index: usize,
+        until: Instant,
+    },
+    Random {
+        rng: Box<ChaCha8Rng>,
+        until: Instant,
+    },
+}
+
+impl Driver {
+    pub fn script(script: Script) -> Self {
+        Self::Script {
+            index: script.steps.len() - 1,
+            script,
+            until: Instant::now(),
+        }
+    }
+
+    pub fn random(seed: u64) -> Self {
+        Self::Random {
+            rng: Box::new(ChaCha8Rng::seed_from_u64(seed)),
+            until: Instant::now(),
+        }
+    }
+
+    /// The keys to hold from now on and how far to turn, once the current step is over.
+    pub fn next(&mut self, now: Instant) -> Option<(Keys, (i32, i32))> {
+        match self {
+            Self::Script {
+                script,
+                index,
+                until,
+            } => {
+                if now < *until {
+                    return None;
+                }
+
+                // turns take no time, so they apply along with the next hold
+                let mut look = (0, 0);
+                loop {
+                    *index = (*index + 1) % script.steps.len();
+                    match script.steps[*index] {
+                        Step::Look(xrel, yrel) => look = (look.0 + xrel, look.1 + yrel),
+                        Step::Hold(keys, dur) => {
+                            *until = now + dur;
+                            return Some((keys, look));
+                        }
+                    }
+                }
+            }
+            Self::Random { rng, until } => {
+                if now < *until {
+                    return None;
+                }
+                *until =
+                    now + Duration::from_millis(rng.random_range(RANDOM_HOLD[0]..RANDOM_HOLD[1]));
+
+                // mostly moving along the ground, now and then jumping or crouching
+                let mut keys = Keys::empty();
+                for (i, &key) in RANDOM_KEYS.iter().enumerate() {
+                    let odds = if i < 4 { 0.4 } else { 0.1 };
+                    keys.set(key, rng.random_bool(odds));
+                }
+                let look = (rng.random_range(-40..=40), rng.random_range(-10..=10));
+                Some((keys, look))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: f32) -> Duration {
+        Duration::from_secs_f32(secs)
+    }
+
+    #[test]
+    fn steps_are_parsed_in_order() {
+        let script = Script::parse(
+            "# run forward, turn around, then jump back\n\
+             hold W+shift 1.5\n\
+             \n\
+             look 60 -5 # to the right\n\
+             hold S+SPACE 0.5\n\
+             wait 1",
+        )
+        .unwrap();
+        assert_eq!(
+            *script.steps,
+            [
+                Step::Hold(Keys::W | Keys::SHIFT, secs(1.5)),
+                Step::Look(60, -5),
+                Step::Hold(Keys::S | Keys::SPACE, secs(0.5)),
+                Step::Hold(Keys::empty(), secs(1.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = Script::parse("wait 1\nhold W+JUMP 1").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err}");
+        assert!(err.to_string().contains("JUMP"), "{err}");
+
+        for bad in ["run 1", "hold W", "wait -1", "wait soon", "look left 0"] {
+            assert!(Script::parse(&format!("wait 1\n{bad}")).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn scripts_must_take_time() {
+        assert!(Script::parse("").is_err());
+        assert!(Script::parse("look 10 0\nhold W 0").is_err());
+        assert!(Script::parse("look 10 0\nhold W 0\nwait 0.1").is_ok());
+    }
+
+    #[test]
+    fn looks_apply_along_with_the_next_hold() {
+        let script = Script::parse("hold W 1\nlook 10 0\nlook 5 2\nwait 1").unwrap();
+        let mut driver = Driver::script(script);
+        let now = Instant::now();
+
+        assert_eq!(driver.next(now), Some((Keys::W, (0, 0))));
+        assert_eq!(driver.next(now + secs(0.5)), None);
+        assert_eq!(driver.next(now + secs(1.0)), Some((Keys::empty(), (15, 2))));
+        assert_eq!(driver.next(now + secs(2.0)), Some((Keys::W, (0, 0))));
+    }
+}
@@ -0,0 +1,113 @@
+use blazed_demo::*;
+use std::{fmt, time::Duration};
+
+/// Everything a single bot has measured.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub join: Option<Duration>, // how long the handshake took (if it succeeded)
+    pub error: Option<String>,  // why the bot failed to join or was disconnected
+    pub played: Duration,       // time spent in-game
+    pub rtts: Vec<u64>,         // round-trip times (µs)
+    pub updates: u64,           // object updates received (over UDP)
+    pub corrections: Vec<f32>,  // distance between the predicted and the server's position
+    pub snapshots: u64, // of the server's simulation of the bot checked against the prediction
+    pub desyncs: Vec<Desync>, // snapshots that didn't match the prediction
+}
+
+/// Statistics of every bot.
+#[derive(Debug)]
+pub struct Report {
+    pub bots: Vec<Stats>,
+}
+
+impl Report {
+    /// Whether every bot has joined and stayed until the end.
+    pub fn is_success(&self) -> bool {
+        self.bots
+            .iter()
+            .all(|s| s.join.is_some() && s.error.is_none())
+    }
+}
+
+/// Sorted copy of the samples.
+fn sorted<T: Copy + PartialOrd>(samples: impl Iterator<Item = T>) -> Vec<T> {
+    let mut samples = samples.collect::<Vec<_>>();
+    samples.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+    samples
+}
+
+/// The given percentile of sorted samples.
+fn percentile<T: Copy + Default>(sorted: &[T], p: usize) -> T {
+    match sorted.len() {
+        0 => T::default(),
+        n => sorted[(n - 1) * p / 100],
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let joins = sorted(self.bots.iter().filter_map(|s| s.join));
+        writeln!(
+            f,
+            "joined:      {}/{} (p50 {:?}, max {:?})",
+            joins.len(),
+            self.bots.len(),
+            percentile(&joins, 50),
+            joins.last().copied().unwrap_or_default(),
+        )?;
+
+        let rtts = sorted(self.bots.iter().flat_map(|s| s.rtts.iter().copied()));
+        let ms = |us: u64| us as f64 / 1000.0;
+        writeln!(
+            f,
+            "rtt:         p50 {:.2}ms, p99 {:.2}ms, max {:.2}ms ({} samples)",
+            ms(percentile(&rtts, 50)),
+            ms(percentile(&rtts, 99)),
+            ms(rtts.last().copied().unwrap_or_default()),
+            rtts.len(),
+        )?;
+
+        // per bot, while in-game
+        let rates = sorted(
+            self.bots
+                .iter()
+                .filter(|s| !s.played.is_zero())
+                .map(|s| s.updates as f64 / s.played.as_secs_f64()),
+        );
+        writeln!(
+            f,
+            "update rate: p50 {:.1}/s, min {:.1}/s per bot",
+            percentile(&rates, 50),
+            rates.first().copied().unwrap_or_default(),
+        )?;
+
+        let corrections = sorted(self.bots.iter().flat_map(|s| s.corrections.iter().copied()));
+        let mean = match corrections.len() {
+            0 => 0.0,
+            n => corrections.iter().sum::<f32>() / n as f32,
+        };
+        writeln!(
+            f,
+            "desync:      mean {mean:.3}, p99 {:.3}, max {:.3} ({} corrections)",
+            percentile(&corrections, 99),
+            corrections.last().copied().unwrap_or_default(),
+            corrections.len(),
+        )?;
+        writeln!(
+            f,
+            "snapshots:   {} of {} mispredicted",
+            self.bots.iter().map(|s| s.desyncs.len()).sum::<usize>(),
+            self.bots.iter().map(|s| s.snapshots).sum::<u64>(),
+        )?;
+
+        for (i, stats) in self.bots.iter().enumerate() {
+            if let Some(e) = &stats.error {
+                writeln!(f, "bot #{i}: {e}")?;
+            }
+            if let Some(desync) = stats.desyncs.first() {
+                writeln!(f, "bot #{i}: {desync}")?;
+            }
+        }
+        Ok(())
+    }
+}
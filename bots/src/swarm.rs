@@ -0,0 +1,71 @@
+use crate::*;
+use blazed_demo::*;
+use std::{
+    net::SocketAddr,
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+use ultraviolet::Vec3;
+
+/// Bots joining the same server, each on its own thread.
+#[derive(Clone, Debug)]
+pub struct Swarm {
+    pub tcp_addr: SocketAddr,
+    pub udp_addr: SocketAddr,
+    pub count: usize,
+    pub duration: Duration,     // each bot plays for (once joined)
+    pub ramp: Duration,         // between each bot joining
+    pub script: Option<Script>, // every bot follows (random movement otherwise)
+    pub seed: u64,              // of the random movement (each bot's is offset by its index)
+    pub name: Name,             // of every bot (deduplicated by the server)
+}
+
+impl Swarm {
+    /// Join and play with every bot, returning once all of them are done.
+    pub fn run(&self) -> BlazedResult<Report> {
+        let start = Instant::now();
+
+        let bots = (0..self.count)
+            .map(|i| {
+                let script = self.script.clone();
+                let join_at = start + self.ramp * i as u32;
+                let Self {
+                    tcp_addr,
+                    udp_addr,
+                    count,
+                    duration,
+                    name,
+                    ..
+                } = *self;
+                let seed = self.seed + i as u64;
+
+                spawn(move || {
+                    sleep(join_at.saturating_duration_since(Instant::now()));
+                    let driver = script.map_or_else(|| Driver::random(seed), Driver::script);
+
+                    // distinguishable (rather than random) colors
+                    let hue = i as f32 / count as f32;
+                    let hello = ClientHandshake {
+                        name,
+                        color: Vec3::new(hue, 1.0 - hue, 0.5),
+                    };
+
+                    let mut stats = Stats::default();
+                    let result = Bot::join(tcp_addr, udp_addr, hello, &mut stats)
+                        .and_then(|bot| bot.play(driver, duration, &mut stats));
+                    if let Err(e) = result {
+                        stats.error = Some(e.to_string());
+                    }
+                    stats
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Report {
+            bots: bots
+                .into_iter()
+                .map(|bot| bot.join().map_err(BlazedError::from))
+                .collect::<BlazedResult<_>>()?,
+        })
+    }
+}
@@ -0,0 +1,71 @@
+use blazed_bots::{Report, Script, Swarm};
+use server::{Config, Name, ServerHandle};
+use std::time::Duration;
+
+/// A map without obstacles (shared with the server's tests).
+const OPEN_MAP: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../server/tests/maps/open.ron");
+
+/// A server on ephemeral loopback ports, with any additional options.
+fn start_server(args: &[&str]) -> ServerHandle {
+    let mut all = vec!["server", "-t", "127.0.0.1:0", "-u", "127.0.0.1:0"];
+    all.extend(["--map", OPEN_MAP, "--seed", "0"]);
+    all.extend(args);
+    let cfg = Config::parse_from(all).expect("invalid options");
+    ServerHandle::start(cfg).expect("failed to start the server")
+}
+
+/// Play with the bots against the server, then shut it down.
+fn play(server: ServerHandle, count: usize, script: Option<Script>) -> Report {
+    let swarm = Swarm {
+        tcp_addr: server.tcp_addr(),
+        udp_addr: server.udp_addr(),
+        count,
+        duration: Duration::from_secs(2),
+        ramp: Duration::from_millis(10),
+        script,
+        seed: 0,
+        name: Name::new("bot").unwrap(),
+    };
+    let report = swarm.run().unwrap();
+    server.shutdown().unwrap();
+    report
+}
+
+#[test]
+fn random_bots_play_on_loopback() {
+    let report = play(start_server(&[]), 8, None);
+    assert!(report.is_success(), "{report}");
+    assert_eq!(report.bots.len(), 8);
+    assert!(report.bots.iter().all(|s| s.updates > 0), "{report}");
+}
+
+#[test]
+fn scripted_bots_play_on_loopback() {
+    let script = Script::parse("hold W+SHIFT 0.5\nlook 60 0\nhold S+SPACE 0.3\nwait 0.2").unwrap();
+    let report = play(start_server(&[]), 4, Some(script));
+    assert!(report.is_success(), "{report}");
+    assert!(report.bots.iter().all(|s| !s.rtts.is_empty()), "{report}");
+}
+
+#[test]
+fn bots_predict_the_server() {
+    let script =
+        Script::parse("hold W 0.4\nhold W+D 0.4\nlook 40 -5\nhold W+D 0.3\nwait 0.3").unwrap();
+    let report = play(start_server(&[]), 2, Some(script));
+    assert!(report.is_success(), "{report}");
+
+    // every snapshot matches what the bot has predicted (or is about to)
+    for stats in &report.bots {
+        assert!(stats.snapshots >= 5, "{report}");
+        assert!(stats.desyncs.is_empty(), "{report}");
+    }
+}
+
+#[test]
+fn server_keeps_up_with_a_full_game() {
+    let report = play(start_server(&["--max-players", "64"]), 64, None);
+    assert!(report.is_success(), "{report}");
+
+    // every bot keeps hearing about the others (rather than the server falling behind)
+    assert!(report.bots.iter().all(|s| s.updates > 100), "{report}");
+}
@@ -15,6 +15,7 @@ struct P_Light {
 #define P_LIGHTS_MAX 16                 // TODO - figure out if we can make this dynamic?
 
 uniform vec3 cam_pos;                   // camera's (eye) position
+uniform float ambient_strength;         // ambient light strength
 uniform int p_lights_len;               // current number of point lights
 uniform P_Light p_lights[P_LIGHTS_MAX]; // array of point lights
 
@@ -59,7 +60,7 @@ float get_attenuation(vec3 frag_to_light) {
 
 vec3 calc_ads(vec3 light_dir, vec3 frag_pos, vec3 frag_norm, vec3 light_col) {
     // ambient
-    vec3 ambient = get_ambient(0.2, light_col);
+    vec3 ambient = get_ambient(ambient_strength, light_col);
 
     // diffuse
     vec3 diffuse = get_diffuse(frag_norm, light_dir, light_col);
@@ -1,6 +1,43 @@
 use crate::*;
 use clap::Parser;
-use std::net::SocketAddr;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Parses a duration given in milliseconds.
+fn parse_millis(s: &str) -> Result<Duration> {
+    let ms = s.parse::<u64>()?;
+    Ok(Duration::from_millis(ms))
+}
+
+fn parse_name(s: &str) -> Result<Name> {
+    Name::new(s).map_err(Into::into)
+}
+
+/// Parses a hex color (e.g., `#ff8800`).
+fn parse_color(s: &str) -> Result<Vec3> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err("Colors must be hex triplets (e.g., #ff8800).".into());
+    }
+
+    let channel = |i: usize| -> Result<f32> {
+        let value = u8::from_str_radix(&hex[i..i + 2], 16)?;
+        Ok(value as f32 / 255.0)
+    };
+    Ok(Vec3::new(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Every interface (e.g., to be reached by other players) at a port.
+fn any_addr(port: u16) -> SocketAddr {
+    (Ipv4Addr::UNSPECIFIED, port).into()
+}
+
+fn parse_movement(s: &str) -> Result<Movement> {
+    Movement::parse(s).map_err(Into::into)
+}
 
 #[derive(Parser, Debug)]
 pub struct Config {
@@ -8,14 +45,34 @@ pub struct Config {
     #[arg(long, default_value_t = 120)]
     fps: Id,
 
-    /// Do not attempt to connect to server.
-    #[arg(long, default_value_t)]
+    /// Play on a server within the client (without any sockets)
+    #[arg(long, default_value_t, group = "local")]
     offline: bool,
 
+    /// Host a server within the client that others may join (playing on it too)
+    #[arg(long, default_value_t, group = "local")]
+    host: bool,
+
+    /// Local TCP IP address of the hosted server
+    #[arg(long, default_value_t = any_addr(TCP_PORT), requires = "host")]
+    host_tcp_addr: SocketAddr,
+
+    /// Local UDP IP address of the hosted server
+    #[arg(long, default_value_t = any_addr(UDP_PORT), requires = "host")]
+    host_udp_addr: SocketAddr,
+
     /// Remote TCP IP address
     #[arg(alias = "rt", long, default_value_t = get_socket_addr(TCP_PORT))]
     remote_tcp_addr: SocketAddr,
 
+    /// Player name (the server appends a number if taken)
+    #[arg(long, default_value = DEFAULT_NAME, value_parser = parse_name)]
+    name: Name,
+
+    /// Preferred player color (hex)
+    #[arg(long, default_value = "#ffffff", value_parser = parse_color)]
+    color: Vec3,
+
     /// Local UDP IP address (optional)
     #[arg(alias = "lu", long)]
     local_udp_addr: Option<SocketAddr>,
@@ -23,6 +80,34 @@ pub struct Config {
     /// Remote UDP IP address
     #[arg(alias = "ru", long, default_value_t = get_socket_addr(UDP_PORT))]
     remote_udp_addr: SocketAddr,
+
+    /// Maximum time (ms) to extrapolate remote players without updates
+    #[arg(long, default_value = "250", value_parser = parse_millis)]
+    max_extrapolation: Duration,
+
+    /// Time (ms) to blend remote players back into authoritative state
+    #[arg(long, default_value = "100", value_parser = parse_millis)]
+    extrapolation_blend: Duration,
+
+    /// Player movement mode while offline or hosting [free-fly, walking]
+    #[arg(long, default_value = "free-fly", value_parser = parse_movement)]
+    movement: Movement,
+
+    /// Map file to play while offline or hosting (a random demo world otherwise)
+    #[arg(long, requires = "local")]
+    map: Option<PathBuf>,
+
+    /// Seed of generated world geometry while offline or hosting (random otherwise)
+    #[arg(long, requires = "local")]
+    seed: Option<u64>,
+
+    /// Play back a recorded session (see the server's --record) rather than joining a server
+    #[arg(long, conflicts_with = "local")]
+    replay: Option<PathBuf>,
+
+    /// Player to follow while replaying (the camera flies freely otherwise)
+    #[arg(long, requires = "replay")]
+    follow: Option<Id>,
 }
 
 impl Config {
@@ -30,14 +115,34 @@ impl Config {
         self.fps
     }
 
-    pub const fn is_online(&self) -> bool {
-        !self.offline
+    pub const fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    pub const fn is_hosting(&self) -> bool {
+        self.host
+    }
+
+    pub const fn host_tcp_addr(&self) -> SocketAddr {
+        self.host_tcp_addr
+    }
+
+    pub const fn host_udp_addr(&self) -> SocketAddr {
+        self.host_udp_addr
     }
 
     pub const fn remote_tcp_addr(&self) -> SocketAddr {
         self.remote_tcp_addr
     }
 
+    pub const fn name(&self) -> Name {
+        self.name
+    }
+
+    pub const fn color(&self) -> Vec3 {
+        self.color
+    }
+
     pub const fn local_udp_addr(&self) -> Option<SocketAddr> {
         self.local_udp_addr
     }
@@ -45,6 +150,34 @@ impl Config {
     pub const fn remote_udp_addr(&self) -> SocketAddr {
         self.remote_udp_addr
     }
+
+    pub const fn max_extrapolation(&self) -> Duration {
+        self.max_extrapolation
+    }
+
+    pub const fn extrapolation_blend(&self) -> Duration {
+        self.extrapolation_blend
+    }
+
+    pub const fn movement(&self) -> Movement {
+        self.movement
+    }
+
+    pub fn map(&self) -> Option<&Path> {
+        self.map.as_deref()
+    }
+
+    pub const fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn replay(&self) -> Option<&Path> {
+        self.replay.as_deref()
+    }
+
+    pub const fn follow(&self) -> Option<Id> {
+        self.follow
+    }
 }
 
 impl Default for Config {
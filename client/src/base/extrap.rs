@@ -0,0 +1,157 @@
+use crate::*;
+use std::{
+    collections::HashMap,
+    f32::consts::{PI, TAU},
+    time::Instant,
+};
+use ultraviolet::{Mat4, Vec2, Vec3};
+
+/// Dead-reckoning state of a single remote entity.
+#[derive(Clone, Copy, Debug)]
+struct Reckoning {
+    pos: Vec3,      // last authoritative position
+    velocity: Vec3, // last authoritative velocity (units/sec)
+    stamp: Instant, // when the authoritative state was received
+    error: Vec3,    // offset (displayed - authoritative) being blended out
+
+    // facing isn't extrapolated: nothing tells how fast it turns, nor when it stops
+    facing: Vec2,       // last authoritative yaw and pitch (radians)
+    turned: Instant,    // when the authoritative facing was received
+    facing_error: Vec2, // offset (displayed - authoritative) being blended out
+}
+
+impl Reckoning {
+    const fn new(pos: Vec3, facing: Vec2, stamp: Instant) -> Self {
+        Self {
+            pos,
+            velocity: Vec3::new(0.0, 0.0, 0.0),
+            stamp,
+            error: Vec3::new(0.0, 0.0, 0.0),
+            facing,
+            turned: stamp,
+            facing_error: Vec2::new(0.0, 0.0),
+        }
+    }
+}
+
+/// Bounded extrapolation of remote entities between (possibly late) updates.
+#[derive(Debug)]
+pub struct Remotes {
+    inner: HashMap<Id, Reckoning>,
+    max_extrapolation: Duration,
+    blend: Duration,
+}
+
+impl Remotes {
+    pub fn new(max_extrapolation: Duration, blend: Duration) -> Self {
+        Self {
+            inner: Default::default(),
+            max_extrapolation,
+            blend,
+        }
+    }
+
+    /// Start tracking a remote entity (facing the given yaw and pitch).
+    pub fn insert(&mut self, id: Id, pos: Vec3, facing: Vec2, now: Instant) {
+        self.inner.insert(id, Reckoning::new(pos, facing, now));
+    }
+
+    /// Stop tracking a remote entity.
+    pub fn remove(&mut self, id: Id) {
+        self.inner.remove(&id);
+    }
+
+    /// Stop tracking every remote entity.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    /// Apply an authoritative update, blending from wherever the entity is currently displayed.
+    pub fn apply(&mut self, data: &UptObjOpt, now: Instant) {
+        let (Some(displayed), Some(facing), Some(r)) = (
+            self.position(data.id, now),
+            self.facing(data.id, now),
+            self.inner.get_mut(&data.id),
+        ) else {
+            return;
+        };
+
+        // (turning the short way round)
+        if data.cam.yaw.is_some() || data.cam.pitch.is_some() {
+            r.facing = Vec2::new(
+                data.cam.yaw.map_or(facing.x, |yaw| yaw.radians()),
+                data.cam.pitch.map_or(facing.y, |pitch| pitch.radians()),
+            );
+            r.turned = now;
+            r.facing_error = facing - r.facing;
+            r.facing_error.x = (r.facing_error.x + PI).rem_euclid(TAU) - PI;
+        }
+
+        if data.cam.eye.is_none() && data.cam.velocity.is_none() {
+            return;
+        }
+
+        // re-anchor at the authoritative state
+        let pos = data.cam.eye.unwrap_or(displayed);
+        if let Some(velocity) = data.cam.velocity {
+            r.velocity = velocity;
+        }
+        r.pos = pos;
+        r.stamp = now;
+
+        // whatever was off is smoothed out over the blend duration
+        r.error = displayed - pos;
+    }
+
+    /// The displayed position of an entity at the given time.
+    pub fn position(&self, id: Id, now: Instant) -> Option<Vec3> {
+        let r = self.inner.get(&id)?;
+        let elapsed = now.saturating_duration_since(r.stamp);
+
+        // extrapolate no further than allowed
+        let extrapolated = r.pos + r.velocity * elapsed.min(self.max_extrapolation).as_secs_f32();
+
+        Some(extrapolated + r.error * self.blend_weight(elapsed))
+    }
+
+    /// The displayed yaw and pitch (radians) of an entity at the given time.
+    pub fn facing(&self, id: Id, now: Instant) -> Option<Vec2> {
+        let r = self.inner.get(&id)?;
+        let elapsed = now.saturating_duration_since(r.turned);
+        Some(r.facing + r.facing_error * self.blend_weight(elapsed))
+    }
+
+    /// Move every tracked object to its displayed position, returning whether any is still in motion.
+    pub fn advance(&self, now: Instant, objects: &mut RawObjects) -> bool {
+        let mut is_moving = false;
+
+        for (&id, r) in self.inner.iter() {
+            let elapsed = now.saturating_duration_since(r.stamp);
+
+            is_moving |= (r.velocity != Vec3::zero() && elapsed < self.max_extrapolation)
+                || (r.error != Vec3::zero() && elapsed < self.blend)
+                || (r.facing_error != Vec2::zero()
+                    && now.saturating_duration_since(r.turned) < self.blend);
+
+            if let (Some(pos), Some(facing), Some(obj)) = (
+                self.position(id, now),
+                self.facing(id, now),
+                objects.get_mut(id),
+            ) {
+                obj.trans.translation = Mat4::from_translation(pos);
+                obj.trans.rotation = Mat4::from_euler_angles(0.0, facing.y, -facing.x);
+                obj.trans.model_upt();
+            }
+        }
+        is_moving
+    }
+
+    /// Remaining weight of the blended error (1.0 -> 0.0).
+    fn blend_weight(&self, elapsed: Duration) -> f32 {
+        if elapsed >= self.blend {
+            0.0
+        } else {
+            1.0 - elapsed.as_secs_f32() / self.blend.as_secs_f32()
+        }
+    }
+}
@@ -25,3 +25,30 @@ pub const fn try_from_scancode(key: Scancode) -> Option<Keys> {
     let kb = from_scancode(key);
     if kb.is_empty() { None } else { Some(kb) }
 }
+
+/// Controls of a replay's playback.
+#[derive(Clone, Copy, Debug)]
+pub enum ReplayControl {
+    Pause,   // or resume
+    Slower,  // halve the speed
+    Faster,  // double the speed
+    Back,    // scrub back a few seconds
+    Forward, // scrub forward a few seconds
+    Restart,
+    Follow,  // the next player
+    FreeFly, // stop following anyone
+}
+
+pub const fn replay_control(key: Scancode) -> Option<ReplayControl> {
+    match key {
+        Scancode::P => Some(ReplayControl::Pause),
+        Scancode::Minus => Some(ReplayControl::Slower),
+        Scancode::Equals => Some(ReplayControl::Faster),
+        Scancode::Comma => Some(ReplayControl::Back),
+        Scancode::Period => Some(ReplayControl::Forward),
+        Scancode::Home => Some(ReplayControl::Restart),
+        Scancode::Tab => Some(ReplayControl::Follow),
+        Scancode::F => Some(ReplayControl::FreeFly),
+        _ => None,
+    }
+}
@@ -1,5 +1,6 @@
 mod cfg;
 mod err;
+mod extrap;
 mod fps;
 mod keys;
 mod net;
@@ -9,6 +10,7 @@ mod util;
 
 pub use cfg::*;
 pub use err::*;
+pub use extrap::*;
 pub use fps::*;
 pub use keys::*;
 pub use net::*;
@@ -20,6 +22,7 @@ pub use blazed_demo::*;
 
 use atomic_enum::*;
 use std::{ops::Deref, sync::Arc};
+use ultraviolet::Vec3;
 
 /// A reference to a read-only locked [`RawObjects`].
 pub type ObjectsRef<'a> = &'a RwLock<RawObjects>;
@@ -90,7 +93,9 @@ pub enum ObjectAction {
     Add { data: UptObj },
     Upt { data: UptObjOpt },
     Remove { id: Id },
+    Respawn { id: Id, pos: Vec3 },
     User { data: UptObjOpt },
+    UserRespawn { pos: Vec3 },
 }
 
 /// Event wrappers related to the user.
@@ -109,7 +114,11 @@ pub enum GameEvent {
     Render(RenderAction),
     Object(ObjectAction),
     User(UserAction),
-    Fps(Id), // dynamically update FPS limit
+    Movement(Movement), // server's movement rules
+    World(World),       // replace the static world
+    Snapshot(Snapshot), // the server's simulation of the player
+    Sent(Input),        // the user's input, as numbered once sent to the server
+    Steer(bool),        // whether the user's input moves the camera (not while following a replay)
 }
 
 /// Event wrappers related to the backend.
@@ -120,4 +129,5 @@ pub enum SysEvent {
     MouseMotion(i32, i32),
     Keyboard(Keys, bool),
     AspectRatio(i32, i32),
+    Replay(ReplayControl),
 }
@@ -0,0 +1,155 @@
+use crate::*;
+use server::ServerHandle;
+use std::{
+    ffi::OsString,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+/// Where the player's server is (and how it's reached).
+#[derive(Clone, Debug)]
+pub enum Endpoint {
+    /// Over TCP and UDP (e.g., a remote server or one hosted by this client).
+    Sockets {
+        tcp_addr: SocketAddr,
+        udp_addr: SocketAddr,
+        local_udp_addr: Option<SocketAddr>,
+    },
+
+    /// Within an in-memory network (while offline).
+    Memory {
+        net: MemNet,
+        tcp_addr: SocketAddr,
+        udp_addr: SocketAddr,
+    },
+}
+
+impl Endpoint {
+    /// The remote server of the config.
+    pub const fn remote(cfg: &Config) -> Self {
+        Self::Sockets {
+            tcp_addr: cfg.remote_tcp_addr(),
+            udp_addr: cfg.remote_udp_addr(),
+            local_udp_addr: cfg.local_udp_addr(),
+        }
+    }
+
+    /// Join the server, returning once the handshake has finished (or failed).
+    pub fn connect(&self, hello: ClientHandshake, clock: Clock) -> BlazedResult<Connection> {
+        match self {
+            Self::Sockets {
+                tcp_addr,
+                udp_addr,
+                local_udp_addr,
+            } => Connection::connect(*tcp_addr, *udp_addr, *local_udp_addr, hello, clock),
+            Self::Memory {
+                net,
+                tcp_addr,
+                udp_addr,
+            } => {
+                let tcp = MemStream::connect(net, *tcp_addr)?;
+                let udp = MemUdp::bind(net, SocketAddr::new(tcp.local_addr().ip(), 0))?;
+                Connection::over(tcp, udp, *udp_addr, hello, clock)
+            }
+        }
+    }
+}
+
+/// A server running within the client (offline or hosting), shut down once dropped.
+///
+/// The player joins it like any other server, so the game behaves exactly as it does online.
+pub struct LocalServer {
+    handle: Option<ServerHandle>,
+    endpoint: Endpoint,
+}
+
+impl LocalServer {
+    /// Start whichever server the player has asked for (none while playing online).
+    pub fn start(cfg: &Config) -> Result<Option<Self>> {
+        let (handle, endpoint) = if cfg.is_offline() {
+            // nobody else can reach a server within memory (so no admin socket either)
+            let (tcp_addr, udp_addr) = (get_socket_addr(TCP_PORT), get_socket_addr(UDP_PORT));
+            let server_cfg = server_config(cfg, tcp_addr, udp_addr, &["--admin-secret", ""])?;
+
+            let net = MemNet::new(Link::default());
+            let handle =
+                ServerHandle::start_in(server_cfg, &net).map_err(|server::Error::Blazed(e)| e)?;
+            info!("[Server] Playing offline");
+
+            let endpoint = Endpoint::Memory {
+                net,
+                tcp_addr,
+                udp_addr,
+            };
+            (handle, endpoint)
+        } else if cfg.is_hosting() {
+            let server_cfg = server_config(cfg, cfg.host_tcp_addr(), cfg.host_udp_addr(), &[])?;
+            let handle = ServerHandle::start(server_cfg).map_err(|server::Error::Blazed(e)| e)?;
+            info!("[Server] Hosting @ {}", handle.tcp_addr());
+
+            // the server itself, however it's reached by others
+            let endpoint = Endpoint::Sockets {
+                tcp_addr: reachable(handle.tcp_addr()),
+                udp_addr: reachable(handle.udp_addr()),
+                local_udp_addr: cfg.local_udp_addr(),
+            };
+            (handle, endpoint)
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            handle: Some(handle),
+            endpoint,
+        }))
+    }
+
+    pub const fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+}
+
+impl Drop for LocalServer {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take()
+            && let Err(e) = handle.shutdown()
+        {
+            error!("[Server] {e}");
+        }
+    }
+}
+
+/// The server's settings, as chosen by the player.
+fn server_config(
+    cfg: &Config,
+    tcp_addr: SocketAddr,
+    udp_addr: SocketAddr,
+    extra: &[&str],
+) -> Result<server::Config> {
+    let mode = if cfg.movement().is_walking() {
+        "walking"
+    } else {
+        "free-fly"
+    };
+    let mut args = Vec::<OsString>::from(["server".into(), "--movement".into(), mode.into()]);
+    args.extend(["-t".into(), tcp_addr.to_string().into()]);
+    args.extend(["-u".into(), udp_addr.to_string().into()]);
+    if let Some(path) = cfg.map() {
+        args.extend(["--map".into(), path.into()]);
+    }
+    if let Some(seed) = cfg.seed() {
+        args.extend(["--seed".into(), seed.to_string().into()]);
+    }
+    args.extend(extra.iter().map(Into::into));
+
+    server::Config::parse_from(args).map_err(|server::Error::Blazed(e)| e.into())
+}
+
+/// An address that reaches a socket bound to it (e.g., loopback for every interface).
+fn reachable(addr: SocketAddr) -> SocketAddr {
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => Ipv4Addr::LOCALHOST.into(),
+        IpAddr::V6(ip) if ip.is_unspecified() => Ipv6Addr::LOCALHOST.into(),
+        ip => ip,
+    };
+    SocketAddr::new(ip, addr.port())
+}
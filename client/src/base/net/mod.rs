@@ -1,7 +1,7 @@
-mod tcp;
-mod udp;
+mod local;
+mod replay;
 mod util;
 
-pub use tcp::*;
-pub use udp::*;
+pub use local::*;
+pub use replay::*;
 pub use util::*;
@@ -0,0 +1,376 @@
+use crate::*;
+use crossbeam_channel::{Receiver, Sender, select, tick};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+    path::Path,
+    sync::atomic::{AtomicU16, Ordering},
+    time::{Duration, Instant},
+};
+
+/// how far a replay is scrubbed at once
+const SCRUB: Duration = Duration::from_secs(5);
+
+/// bounds of the playback speed (halving or doubling)
+const SPEEDS: [f32; 2] = [1.0 / 16.0, 16.0];
+
+/// frames between snapshots of the game (to seek from)
+const CHECKPOINT: usize = 1024;
+
+/// an identity no player has (see `PLAYER_IDS`), whose camera flies freely
+const SPECTATOR: Id = Id::MAX;
+
+/// A recorded session, loaded whole so it can be played from any point.
+pub struct Replay {
+    frames: Vec<ReplayFrame>,
+    checkpoints: Vec<(BTreeMap<Id, UptObj>, Movement)>, // players before every CHECKPOINT frames
+    state: ReplayState,                                 // the game before the next frame
+    next: usize,
+}
+
+impl Replay {
+    pub fn open(path: &Path) -> Result<Self> {
+        let reader = ReplayReader::open(path)
+            .map_err(|e| format!("Couldn't open replay '{}': {e}", path.display()))?;
+        let state = reader.start().clone();
+        let frames = reader.collect::<BlazedResult<Vec<_>>>()?;
+
+        let mut checkpoints = vec![(state.players.clone(), state.movement)];
+        let mut scratch = state.clone();
+        for (i, frame) in frames.iter().enumerate() {
+            if i > 0 && i % CHECKPOINT == 0 {
+                checkpoints.push((scratch.players.clone(), scratch.movement));
+            }
+            scratch.apply(frame);
+        }
+
+        Ok(Self {
+            frames,
+            checkpoints,
+            state,
+            next: 0,
+        })
+    }
+
+    /// Recording time of the first and last frame.
+    pub fn bounds(&self) -> [Duration; 2] {
+        let time = |frame: Option<&ReplayFrame>| frame.map(|f| f.time).unwrap_or_default();
+        [time(self.frames.first()), time(self.frames.last())]
+    }
+
+    /// Recording time of the frame to be played next (none once finished).
+    pub fn next_time(&self) -> Option<Duration> {
+        self.frames.get(self.next).map(|f| f.time)
+    }
+
+    pub const fn state(&self) -> &ReplayState {
+        &self.state
+    }
+
+    pub fn frames(&self, range: Range<usize>) -> &[ReplayFrame] {
+        &self.frames[range]
+    }
+
+    /// Play every frame up to the time, returning which were played.
+    pub fn advance(&mut self, to: Duration) -> Range<usize> {
+        let start = self.next;
+        while let Some(frame) = self.frames.get(self.next)
+            && frame.time <= to
+        {
+            self.state.apply(frame);
+            self.next += 1;
+        }
+        start..self.next
+    }
+
+    /// Jump to the time (the game as it was after every earlier frame).
+    pub fn seek(&mut self, to: Duration) {
+        let target = self.frames.partition_point(|f| f.time <= to);
+        let index = (target / CHECKPOINT).min(self.checkpoints.len() - 1);
+
+        let (players, movement) = &self.checkpoints[index];
+        self.state.players = players.clone();
+        self.state.movement = *movement;
+        self.next = index * CHECKPOINT;
+        self.advance(to);
+    }
+}
+
+/// Plays a replay in (scaled) real time, as if a server were sending it.
+struct Playback {
+    replay: Replay,
+    following: Option<Id>,
+    speed: f32,
+    is_paused: bool,
+    anchor: (Instant, Duration), // when playback was at some recording time
+    has_begun: bool,
+    cam: Camera,
+    event_sender: Arc<EventSender>,
+    render_sender: Sender<()>,
+    rate: u16, // updates played within the current second
+}
+
+impl Playback {
+    /// Current recording time.
+    fn time(&self) -> Duration {
+        let (since, time) = self.anchor;
+        if self.is_paused {
+            return time;
+        }
+        let end = self.replay.bounds()[1];
+        (time + since.elapsed().mul_f32(self.speed)).min(end)
+    }
+
+    /// Move playback to a recording time (from now on).
+    fn anchor(&mut self, time: Duration) {
+        self.anchor = (Instant::now(), time);
+    }
+
+    /// The player whose eyes the camera sees through.
+    fn id(&self) -> Id {
+        self.following.unwrap_or(SPECTATOR)
+    }
+
+    /// Movement rules of the camera (its own while flying freely).
+    fn movement(&self, movement: Movement) -> Movement {
+        if self.following.is_some() {
+            movement
+        } else {
+            Movement {
+                mode: MoveMode::FreeFly,
+                ..movement
+            }
+        }
+    }
+
+    fn handle(&mut self, event: NetEvent) -> Result {
+        let id = self.id();
+        handle_event(
+            &self.event_sender,
+            &self.render_sender,
+            id,
+            event,
+            &mut self.rate,
+        )
+        .map(drop)
+    }
+
+    /// Show the game anew (after seeking or following someone else), as when joining a server.
+    fn present(&mut self) -> Result {
+        // a player that's gone can't be followed
+        if let Some(id) = self.following
+            && !self.replay.state().players.contains_key(&id)
+        {
+            self.following = None;
+        }
+
+        // the free camera stays wherever it is
+        let attr = (self.has_begun && self.following.is_none()).then(|| *self.cam.read().attr());
+        self.has_begun = true;
+        self.event_sender
+            .push_custom_event(GameEvent::Steer(self.following.is_none()))?;
+
+        let (id, state) = (self.id(), self.replay.state());
+        let joined = NetEvent::Joined {
+            id,
+            world: state.world.clone(),
+            movement: self.movement(state.movement),
+            players: state
+                .players
+                .values()
+                .filter(|p| p.id != id)
+                .copied()
+                .collect(),
+        };
+        let followed = state.players.get(&id).copied();
+
+        self.handle(joined)?;
+        if let Some(data) = followed {
+            self.handle(NetEvent::Added(data))?;
+        }
+        if let Some(attr) = attr {
+            let data = UptObjOpt {
+                id,
+                cam: attr.into_opt(),
+                ..Default::default()
+            };
+            self.event_sender
+                .push_custom_event(GameEvent::Object(ObjectAction::User { data }))?;
+        }
+        Ok(())
+    }
+
+    /// Play every frame that's due (whatever has changed about a player as a single update).
+    fn play(&mut self) -> Result {
+        let range = self.replay.advance(self.time());
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        let mut changed = BTreeSet::new();
+        let mut events = Vec::new();
+        for frame in self.replay.frames(range) {
+            for event in &frame.events {
+                events.push(match *event {
+                    ReplayEvent::Joined(data) => NetEvent::Added(data),
+                    ReplayEvent::Left(id) => NetEvent::Removed(id),
+                    ReplayEvent::Respawned { id, pos } => NetEvent::Respawned { id, pos },
+                    ReplayEvent::Movement(movement) => NetEvent::Movement(self.movement(movement)),
+                    ReplayEvent::State(data) => {
+                        changed.insert(data.id);
+                        continue;
+                    }
+                    ReplayEvent::Input { .. } => continue, // seen through its effects
+                });
+            }
+        }
+        let players = &self.replay.state().players;
+        events.extend(
+            changed
+                .into_iter()
+                .filter_map(|id| players.get(&id))
+                .map(|player| NetEvent::Updated(player.into_opt())),
+        );
+
+        for event in events {
+            self.handle(event)?;
+        }
+
+        // the followed player has left
+        if let Some(id) = self.following
+            && !self.replay.state().players.contains_key(&id)
+        {
+            info!("[Replay] Player {id} has left (flying freely)");
+            self.present()?;
+        }
+
+        if self.replay.next_time().is_none() {
+            self.anchor(self.time());
+            self.is_paused = true;
+            info!("[Replay] Finished (scrub back and resume to watch again)");
+        }
+        Ok(())
+    }
+
+    fn control(&mut self, control: ReplayControl) -> Result {
+        let [begin, end] = self.replay.bounds();
+        let time = self.time();
+        self.anchor(time);
+
+        match control {
+            ReplayControl::Pause => {
+                self.is_paused = !self.is_paused;
+                if self.is_paused {
+                    info!("[Replay] Paused at {:?}", time - begin);
+
+                    // remote players aren't extrapolated past where they've stopped
+                    return self.present();
+                }
+                info!("[Replay] Resumed");
+            }
+            ReplayControl::Slower | ReplayControl::Faster => {
+                let [min, max] = SPEEDS;
+                self.speed = match control {
+                    ReplayControl::Slower => self.speed / 2.0,
+                    _ => self.speed * 2.0,
+                }
+                .clamp(min, max);
+                info!("[Replay] Speed x{}", self.speed);
+            }
+            ReplayControl::Back | ReplayControl::Forward | ReplayControl::Restart => {
+                let to = match control {
+                    ReplayControl::Back => time.saturating_sub(SCRUB).max(begin),
+                    ReplayControl::Forward => (time + SCRUB).min(end),
+                    _ => begin,
+                };
+                self.replay.seek(to);
+                self.anchor(to);
+                info!("[Replay] At {:?} of {:?}", to - begin, end - begin);
+                return self.present();
+            }
+            ReplayControl::Follow => {
+                let players = &self.replay.state().players;
+                let after = self.following.map_or(0, |id| id.saturating_add(1));
+                let Some(&id) = players
+                    .range(after..)
+                    .chain(players)
+                    .next()
+                    .map(|(id, _)| id)
+                else {
+                    info!("[Replay] Nobody to follow");
+                    return Ok(());
+                };
+                info!("[Replay] Following {} ({id})", players[&id].name);
+                self.following = Some(id);
+                return self.present();
+            }
+            ReplayControl::FreeFly => {
+                if self.following.take().is_some() {
+                    info!("[Replay] Flying freely");
+                    return self.present();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Play back a replay in place of a connection, driving the game through the same events.
+pub fn init_replay(
+    s: &SyncSelect,
+    event_sender: Arc<EventSender>,
+    render_sender: Sender<()>,
+    (input_receiver, control_receiver): (Receiver<Input>, Receiver<ReplayControl>),
+    (tps, cam): (Arc<AtomicU16>, Camera),
+    (replay, cfg): (Replay, Config),
+) {
+    s.spawn(move || -> Result {
+        let [begin, end] = replay.bounds();
+        info!("[Replay] Playing {:?}", end - begin);
+
+        let mut playback = Playback {
+            replay,
+            following: cfg.follow(),
+            speed: 1.0,
+            is_paused: false,
+            anchor: (Instant::now(), begin),
+            has_begun: false,
+            cam,
+            event_sender,
+            render_sender,
+            rate: 0,
+        };
+        playback.replay.seek(begin);
+        playback.present()?;
+        if let Some(id) = cfg.follow()
+            && playback.following.is_none()
+        {
+            warn!("[Replay] Player {id} isn't there to follow (flying freely)");
+        }
+
+        // updates played within the current second
+        let ticker = tick(SECOND);
+
+        loop {
+            // until the next frame is due
+            let wait = match playback.replay.next_time() {
+                Some(next) if !playback.is_paused => {
+                    next.saturating_sub(playback.time())
+                        .div_f32(playback.speed)
+                        .min(SECOND)
+                }
+                _ => SECOND,
+            };
+
+            select! {
+                recv(control_receiver) -> control => playback.control(control?)?,
+                recv(input_receiver) -> input => {
+                    input?; // nobody to send it to
+                }
+                recv(ticker) -> _ => tps.store(std::mem::take(&mut playback.rate), Ordering::Relaxed),
+                default(wait) => (),
+            }
+            playback.play()?;
+        }
+    });
+}
@@ -1,145 +1,201 @@
 use crate::*;
-use crossbeam_channel::{Receiver, Sender};
-use pfrs::*;
-use std::{net::Ipv4Addr, sync::atomic::AtomicU16, thread::sleep};
-
-/// obtain player identity and gamestates from server
-pub fn handshake(
-    tcp: &TcpClient,
-    udp: &mut UdpClient,
-    event_sender: Arc<EventSender>,
-) -> Result<Id> {
-    debug!("[TCP] [1] Sending client handshake");
-    tcp.send(&ClientHandshake::serialize())?;
-
-    let mut buf = [0; PACKET_SIZE];
-
-    debug!("[TCP] [2] Receiving server handshake");
-    let n = tcp.recv(&mut buf)?;
-    assert!(n != 0);
-
-    if buf[0] != ServerHandshake::ID {
-        return Err(format!("[TCP] [4] Found {}, expected ServerHandshake", buf[0]).into());
-    }
-    let id = ServerHandshake::deserialize(&buf[1..n]).id();
-
-    debug!("[UDP] [3] Sending client handshake");
-    udp.send(&ClientHandshake::serialize())?;
-
-    debug!("[TCP] [4] Receiving game states");
-    while let Ok(n) = tcp.recv(&mut buf) {
-        assert!(n != 0);
-
-        match buf[0] {
-            Flush::ID => break,
-            UptObj::ID => {
-                let data = UptObj::deserialize(&buf[1..n]);
+use crossbeam_channel::{Receiver, Sender, select, tick};
+use std::{
+    sync::atomic::{AtomicU16, Ordering},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// bounds of the delay between reconnects (doubling after each failure)
+const RECONNECT_DELAY: [Duration; 2] = [SECOND, Duration::from_secs(32)];
+
+/// least delay after the server has disconnected the player on purpose
+const REJECTED_DELAY: Duration = Duration::from_secs(5);
+
+/// Translate a network event into game events, returning why the session has ended (once it has).
+pub fn handle_event(
+    event_sender: &EventSender,
+    render_sender: &Sender<()>,
+    id: Id,
+    event: NetEvent,
+    rate: &mut u16,
+) -> Result<Option<Option<RejectReason>>> {
+    let action = match event {
+        NetEvent::Joined {
+            world,
+            movement,
+            players,
+            ..
+        } => {
+            // the world must be (re)built before anyone is placed in it
+            event_sender.push_custom_event(GameEvent::Movement(movement))?;
+            event_sender.push_custom_event(GameEvent::World(world))?;
+            for data in players {
                 event_sender.push_custom_event(GameEvent::Object(ObjectAction::Add { data }))?;
             }
-            _ => unreachable!(),
-        }
-    }
-    debug!("[TCP] [5] Finishing");
 
-    // initial rendering
-    event_sender.push_custom_event(GameEvent::Render(RenderAction::Flush))?;
+            // initial rendering
+            event_sender.push_custom_event(GameEvent::Render(RenderAction::Flush))?;
+            return Ok(None);
+        }
+        NetEvent::Added(data) if data.id == id => {
+            let cam = CameraAttrOpt {
+                fov: Some(data.cam.fov),
+                yaw: Some(data.cam.yaw),
+                pitch: Some(data.cam.pitch),
+                eye: Some(data.cam.eye),
+                velocity: Some(data.cam.velocity),
+                target: Some(data.cam.target),
+                up: Some(data.cam.up),
+                state: Some(data.cam.state),
+            };
+
+            let data = UptObjOpt {
+                id,
+                kind: Some(data.kind),
+                name: Some(data.name),
+                dim: Some(data.dim),
+                color: Some(data.color),
+                cam,
+                keys: data.keys,
+            };
+            ObjectAction::User { data }
+        }
+        NetEvent::Added(data) => ObjectAction::Add { data },
+        NetEvent::Updated(data) => {
+            // increment TPS
+            *rate = rate.saturating_add(1);
+
+            if data.id == id {
+                ObjectAction::User { data }
+            } else {
+                ObjectAction::Upt { data }
+            }
+        }
+        NetEvent::Removed(id) => ObjectAction::Remove { id },
+        NetEvent::Respawned { id: obj_id, pos } if obj_id == id => {
+            ObjectAction::UserRespawn { pos }
+        }
+        NetEvent::Respawned { id, pos } => ObjectAction::Respawn { id, pos },
+        NetEvent::Movement(movement) => {
+            // the server's rules have changed
+            event_sender.push_custom_event(GameEvent::Movement(movement))?;
+            return Ok(None);
+        }
+        NetEvent::Notice(text) => {
+            info!("[Server] {text}");
+            return Ok(None);
+        }
+        NetEvent::Snapshot(snapshot) => {
+            // checked against the prediction
+            event_sender.push_custom_event(GameEvent::Snapshot(snapshot))?;
+            return Ok(None);
+        }
+        NetEvent::Synced(_) => return Ok(None),
+        NetEvent::Disconnected(reason) => return Ok(Some(reason)),
+    };
 
-    Ok(id)
+    event_sender.push_custom_event(GameEvent::Object(action))?;
+    _ = render_sender.try_send(());
+    Ok(None)
 }
 
-/// send channeled user-input to UDP socket
-pub fn handle_input(s: &SyncSelect, mut udp: UdpClient, input_receiver: Receiver<Vec<u8>>) {
-    s.spawn(move || -> Result<()> {
-        udp.socket().set_write_timeout(Some(GAME_SPEED))?;
-
-        // repeatedly send user input to server
-        while let Ok(data) = input_receiver.recv() {
-            udp.send(data.as_slice())?;
-        }
-        Err(BlazedError::Infallible.into())
-    });
+/// What the game is played from.
+pub enum Source {
+    Server(Endpoint),
+    Replay(Replay),
 }
 
-/// establish client-server handshake then initialize TCP/UDP threads and input thread
+/// establish a connection, then relay its events and the user's input,
+/// returning once disconnected (and why, if the server said)
 pub fn handle_conn(
-    event_sender: Arc<EventSender>,
-    render_sender: Sender<()>,
-    input_receiver: Receiver<Vec<u8>>,
-    (tps, ping): (Arc<AtomicU16>, Arc<RwLock<Duration>>),
-    cfg: &Config,
-) -> Result<()> {
-    // establish connection
-    debug!("[TCP] Connecting");
-    let tcp = TcpClient::new(cfg.remote_tcp_addr())?;
-
-    debug!("[UDP] Connecting");
-    let local_udp_addr = cfg.local_udp_addr().unwrap_or(get_socket_addr(
-        find_open_port(Ipv4Addr::LOCALHOST, Protocol::Udp).ok_or("No available dynamic ports")?,
-    ));
-    let mut udp = UdpClient::new(local_udp_addr, cfg.remote_udp_addr())?;
-
-    // packet buffer for this client
-    let id = handshake(&tcp, &mut udp, event_sender.clone())?;
-
-    let s = SyncSelect::default();
-
-    // handle outgoing TCP packets
-    handle_tcp(
-        &s,
-        event_sender.clone(),
-        render_sender.clone(),
-        tcp,
-        ping,
-        id,
-    );
-
-    // handle outgoing UDP packets
-    handle_udp(
-        &s,
-        event_sender,
-        render_sender.clone(),
-        udp.clone(),
-        tps,
-        id,
-    );
-
-    // handle mouse and keyboard input
-    handle_input(&s, udp, input_receiver);
-
-    Ok(())
+    event_sender: &EventSender,
+    render_sender: &Sender<()>,
+    input_receiver: &Receiver<Input>,
+    (tps, clock): &(Arc<AtomicU16>, Clock),
+    (endpoint, cfg): (&Endpoint, &Config),
+) -> Result<Option<RejectReason>> {
+    let hello = ClientHandshake {
+        name: cfg.name(),
+        color: cfg.color(),
+    };
+    let mut conn = endpoint.connect(hello, clock.clone())?;
+    let (id, events) = (conn.id(), conn.events().clone());
+
+    // updates received within the current second
+    let ticker = tick(SECOND);
+    let mut rate = 0;
+
+    loop {
+        select! {
+            recv(events) -> event => {
+                let event = event?;
+                let reason = handle_event(event_sender, render_sender, id, event, &mut rate)?;
+                if let Some(reason) = reason {
+                    return Ok(reason);
+                }
+            }
+            recv(input_receiver) -> input => {
+                // checked against the server's snapshots as numbered
+                let input = conn.send(input?)?;
+                event_sender.push_custom_event(GameEvent::Sent(input))?;
+            }
+            recv(ticker) -> _ => tps.store(std::mem::take(&mut rate), Ordering::Relaxed),
+        }
+    }
 }
 
 pub fn init_conn(
     s: &SyncSelect,
     event_sender: Arc<EventSender>,
     render_sender: Sender<()>,
-    input_receiver: Receiver<Vec<u8>>,
-    stats: (Arc<AtomicU16>, Arc<RwLock<Duration>>),
-    cfg: Config,
+    input_receiver: Receiver<Input>,
+    stats: (Arc<AtomicU16>, Clock),
+    (endpoint, cfg): (Endpoint, Config),
 ) {
     s.spawn(move || -> Result {
         let event_sender = event_sender;
+        let [min_delay, max_delay] = RECONNECT_DELAY;
+        let mut delay = min_delay;
 
         loop {
+            let start = Instant::now();
+
             // initialize TCP and UDP connection
             let result = handle_conn(
-                event_sender.clone(),
-                render_sender.clone(),
-                input_receiver.clone(),
-                stats.clone(),
-                &cfg,
+                &event_sender,
+                &render_sender,
+                &input_receiver,
+                &stats,
+                (&endpoint, &cfg),
             );
 
-            // handle result
-            if let Err(e) = result {
-                error!("[init_conn] {e}");
+            // a session that lasted starts backing off anew
+            if start.elapsed() > max_delay {
+                delay = min_delay;
             }
 
+            // handle result
+            let wait = match result {
+                Ok(Some(reason)) => {
+                    warn!("[init_conn] Disconnected by server: {reason}");
+                    delay.max(REJECTED_DELAY)
+                }
+                Ok(None) => delay,
+                Err(e) => {
+                    error!("[init_conn] {e}");
+                    delay
+                }
+            };
+
             // reset game state
+            stats.0.store(0, Ordering::Relaxed);
             event_sender.push_custom_event(GameEvent::Reset)?;
 
             // reconnect timeout
-            sleep(SECOND);
+            info!("[init_conn] Reconnecting in {}s", wait.as_secs());
+            sleep(wait);
+            delay = (wait * 2).min(max_delay);
         }
     });
 }
@@ -12,6 +12,16 @@ pub enum InstanceKind {
     NormalCube,
 }
 
+impl From<MeshKind> for InstanceKind {
+    fn from(value: MeshKind) -> Self {
+        if value.contains(MeshKind::NormalCube) {
+            Self::NormalCube
+        } else {
+            Self::SimpleCube
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct ObjData {
     vao: NativeVertexArray,
@@ -362,6 +372,7 @@ struct LookupInfo {
 pub struct RawObjects {
     groups: HashMap<InstanceKind, InstancedGroup>,
     lookup: HashMap<Id, LookupInfo>,
+    ambient: Ambient,
 }
 
 impl RawObjects {
@@ -391,9 +402,14 @@ impl RawObjects {
         Ok(Self {
             groups,
             lookup: Default::default(),
+            ambient: Default::default(),
         })
     }
 
+    pub const fn ambient(&self) -> Ambient {
+        self.ambient
+    }
+
     pub fn groups(&self) -> impl Iterator<Item = &InstancedGroup> {
         self.groups.values()
     }
@@ -422,12 +438,26 @@ impl RawObjects {
         self.lookup.insert(id, LookupInfo { kind, idx });
     }
 
-    /// Get immutable reference to instance data by object id
-    pub fn get(&self, id: Id) -> Option<&InstanceData> {
-        let LookupInfo { kind, idx } = self.lookup.get(&id)?;
-        self.groups
-            .get(kind)
-            .and_then(|group| group.instances.get(*idx))
+    /// Replace every object with the static objects of a world.
+    pub fn load_world(&mut self, world: &World) {
+        self.clear();
+        self.ambient = world.ambient();
+
+        for obj in world.objects() {
+            self.new_cube(
+                obj.id,
+                obj.mesh.into(),
+                obj.trans(),
+                obj.color.data(),
+                obj.color.is_emit(),
+            );
+        }
+    }
+
+    /// Remove every instance.
+    pub fn clear(&mut self) {
+        self.groups.values_mut().for_each(|g| g.instances.clear());
+        self.lookup.clear();
     }
 
     /// Get mutable reference to instance data by object id
@@ -449,22 +479,6 @@ impl RawObjects {
         Some(removed)
     }
 
-    /// Retain only objects with a given kind (remove others)
-    /// TODO - [maybe] introduce object types ([`ObjType`]`) into each instance data
-    pub fn retain(&mut self, _kind: ObjType) {
-        unimplemented!()
-    }
-
-    /// Iterator over all instance data in opaque groups
-    pub fn opaque(&self) -> ! {
-        unimplemented!()
-    }
-
-    /// Iterator over all instance data in translucent groups
-    pub fn translucent(&self) -> ! {
-        unimplemented!()
-    }
-
     /// Iterator over lights
     pub fn lights(&self) -> impl Iterator<Item = (Vec3, Vec3)> {
         self.groups().flat_map(|g| {
@@ -484,13 +498,4 @@ impl RawObjects {
     pub fn buffers(&self) -> impl Iterator<Item = &ObjData> {
         self.groups.values().map(InstancedGroup::data)
     }
-
-    /// Total number of instances (opaque + translucent)
-    pub fn len(&self) -> usize {
-        unimplemented!()
-    }
-
-    pub fn is_empty(&self) -> bool {
-        unimplemented!()
-    }
 }
@@ -21,6 +21,7 @@ fn setup_normal_obj<'a>(
     gl: &Context,
     prog: Program,
     cam_pos: &[f32],
+    ambient: f32,
     lights: impl Iterator<Item = &'a (usize, (Vec3, Vec3))>,
     lights_len: usize,
 ) {
@@ -28,6 +29,9 @@ fn setup_normal_obj<'a>(
         // projection matrix
         gl.program_uniform_3_f32_slice(prog.native(), prog.unif_locs().cam_pos(), cam_pos);
 
+        // ambient light strength
+        gl.program_uniform_1_f32(prog.native(), prog.unif_locs().ambient(), ambient);
+
         // number of point lights
         gl.program_uniform_1_i32(
             prog.native(),
@@ -48,7 +52,8 @@ fn setup_normal_obj<'a>(
 /// RENDER EVERYTHING
 pub fn display(gl: &Context, window: &Window, cam: &RawCamera, objects: &RawObjects) {
     unsafe {
-        gl.clear_color(0.4, 0.4, 0.4, 1.0);
+        let ambient = objects.ambient();
+        gl.clear_color(ambient.sky.x, ambient.sky.y, ambient.sky.z, 1.0);
         gl.clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT);
 
         // camera attributes
@@ -73,7 +78,14 @@ pub fn display(gl: &Context, window: &Window, cam: &RawCamera, objects: &RawObje
 
             // D_Lights | P_Lights
             if let ProgramKind::Normal = prog.kind() {
-                setup_normal_obj(gl, prog, cam_pos_slice, lights.iter(), lights.len());
+                setup_normal_obj(
+                    gl,
+                    prog,
+                    cam_pos_slice,
+                    ambient.strength,
+                    lights.iter(),
+                    lights.len(),
+                );
             }
 
             // TODO - improve this (only update transformations that have changed)
@@ -129,15 +141,21 @@ pub fn display(gl: &Context, window: &Window, cam: &RawCamera, objects: &RawObje
     }
 }
 
+/// Frame rate, frame time, tick rate and the clock (shown to the user).
+type Stats = (Arc<Fps>, Arc<RwLock<Duration>>, Arc<AtomicU16>, Clock);
+
+/// Where each kind of the user's input (and replay controls) is handed on.
+type InputSenders = (
+    Sender<Wheel>,
+    Sender<MotionOpt>,
+    Sender<(Keys, bool)>,
+    Sender<ReplayControl>,
+);
+
 fn handle_sys_events(
     s: &SyncSelect,
-    (fps, ft, tps, ping): (
-        Arc<Fps>,
-        Arc<RwLock<Duration>>,
-        Arc<AtomicU16>,
-        Arc<RwLock<Duration>>,
-    ),
-    (mw_sender, mm_sender, kb_sender): (Sender<Wheel>, Sender<MotionOpt>, Sender<(Keys, bool)>),
+    (fps, ft, tps, clock): Stats,
+    (mw_sender, mm_sender, kb_sender, control_sender): InputSenders,
     (event_sender, sys_event_receiver): (Arc<EventSender>, Receiver<SysEvent>),
 ) {
     s.spawn(move || -> Result {
@@ -147,12 +165,13 @@ fn handle_sys_events(
             match event {
                 SysEvent::Quit => break,
                 SysEvent::MouseWheel(precise_y) => {
-                    _ = mw_sender.try_send(Wheel { precise_y });
+                    _ = mw_sender.try_send(Wheel { precise_y, seq: 0 });
                 }
                 SysEvent::MouseMotion(xrel, yrel) => {
                     let opt = MotionOpt {
                         xrel: (xrel != 0).then_some(xrel),
                         yrel: (yrel != 0).then_some(yrel),
+                        seq: 0,
                     };
                     _ = mm_sender.try_send(opt);
                 }
@@ -161,9 +180,9 @@ fn handle_sys_events(
                         let fps = fps.get();
                         let ft = *ft.read();
                         let tps = tps.load(Ordering::Relaxed);
-                        let ping = *ping.read();
+                        let rtt = clock.rtt();
 
-                        let msg = format!("\r{{ Fps: {fps} @ {ft:?}, Tps: {tps}, Ping {ping:?} }}");
+                        let msg = format!("\r{{ Fps: {fps} @ {ft:?}, Tps: {tps}, Rtt {rtt:?} }}");
 
                         if let Err(e) = out.write_all(msg.as_bytes()) {
                             error!("{e}")
@@ -174,6 +193,7 @@ fn handle_sys_events(
                     }
                     _ = kb_sender.try_send((kb, is_pressed));
                 }
+                SysEvent::Replay(control) => _ = control_sender.try_send(control),
                 SysEvent::AspectRatio(w, h) => event_sender
                     .push_custom_event(GameEvent::Render(RenderAction::AspectRatio { w, h }))?,
             }
@@ -185,8 +205,9 @@ fn handle_sys_events(
 /// Facilitate all user input.
 fn process_input(
     s: &SyncSelect,
+    cam: Camera,
     event_sender: Arc<EventSender>,
-    input_sender: Sender<Vec<u8>>,
+    input_sender: Sender<Input>,
     render_sender: Sender<()>,
     (mw_receiver, mm_receiver, kb_receiver): (
         Receiver<Wheel>,
@@ -205,7 +226,7 @@ fn process_input(
     fn process_mw(
         s: &SyncSelect,
         event_sender: Arc<EventSender>,
-        input_sender: Sender<Vec<u8>>,
+        input_sender: Sender<Input>,
         render_sender: Sender<()>,
         mw_receiver: Receiver<Wheel>,
     ) -> JoinHandle<Result> {
@@ -217,7 +238,7 @@ fn process_input(
                 event_sender.push_custom_event(GameEvent::User(UserAction::Wheel(wheel)))?;
 
                 advance(&render_sender, spinner);
-                _ = input_sender.try_send(wheel.serialize().to_vec());
+                _ = input_sender.try_send(Input::Wheel(wheel));
             }
         })
     }
@@ -225,7 +246,7 @@ fn process_input(
     fn process_mm(
         s: &SyncSelect,
         event_sender: Arc<EventSender>,
-        input_sender: Sender<Vec<u8>>,
+        input_sender: Sender<Input>,
         render_sender: Sender<()>,
         mm_receiver: Receiver<MotionOpt>,
     ) -> JoinHandle<Result> {
@@ -237,24 +258,27 @@ fn process_input(
                 let motion = Motion {
                     xrel: opt.xrel.unwrap_or_default(),
                     yrel: opt.yrel.unwrap_or_default(),
+                    seq: 0,
                 };
                 event_sender.push_custom_event(GameEvent::User(UserAction::Motion(motion)))?;
 
                 advance(&render_sender, spinner);
-                _ = input_sender.try_send(opt.serialize().to_vec());
+                _ = input_sender.try_send(Input::Motion(opt));
             }
         })
     }
 
     fn process_kb(
         s: &SyncSelect,
+        cam: Camera,
         event_sender: Arc<EventSender>,
-        input_sender: Sender<Vec<u8>>,
+        input_sender: Sender<Input>,
         render_sender: Sender<()>,
         kb_receiver: Receiver<(Keys, bool)>,
     ) -> JoinHandle<Result> {
         fn cont_handler(
             s: &SyncSelect,
+            cam: Camera,
             waiter: Waiter,
             keys_cont: AtomicKeys,
             event_sender: Arc<EventSender>,
@@ -267,9 +291,9 @@ fn process_input(
                     waiter.wait();
 
                     loop {
-                        // obtain current key
+                        // obtain current key (keep stepping until the player comes to rest)
                         let kb = keys_cont.get();
-                        if kb.is_empty() {
+                        if kb.is_empty() && cam.read().is_resting() {
                             break;
                         }
                         // send input to client event handler
@@ -291,7 +315,7 @@ fn process_input(
             mut keys_cont: AtomicKeys,
             kb_receiver: Receiver<(Keys, bool)>,
             event_sender: Arc<EventSender>,
-            input_sender: Sender<Vec<u8>>,
+            input_sender: Sender<Input>,
         ) -> JoinHandle<Result> {
             s.spawn(move || {
                 let mut keys_norm = Keys::default();
@@ -301,14 +325,7 @@ fn process_input(
                     let (key, is_pressed) = kb_receiver.recv()?;
 
                     // send change to server
-                    _ = input_sender.try_send(
-                        Keyboard {
-                            bits: key.bits(),
-                            is_pressed: is_pressed as u8,
-                        }
-                        .serialize()
-                        .to_vec(),
-                    );
+                    _ = input_sender.try_send(Input::key(key, is_pressed));
 
                     // facilitate continuous/non-continuous keystrokes
                     if key.is_continuous(KeyState::Player) {
@@ -346,6 +363,7 @@ fn process_input(
         let keys_cont = AtomicKeys::default();
         cont_handler(
             s,
+            cam,
             waiter,
             keys_cont.clone(),
             event_sender.clone(),
@@ -380,7 +398,14 @@ fn process_input(
     );
 
     // process keyboard input
-    process_kb(s, event_sender, input_sender, render_sender, kb_receiver);
+    process_kb(
+        s,
+        cam,
+        event_sender,
+        input_sender,
+        render_sender,
+        kb_receiver,
+    );
 }
 
 pub fn handle_rendering_uncapped(
@@ -460,50 +485,79 @@ pub fn handle_rendering_capped(
     })
 }
 
+/// Keep requesting frames while remote players are in motion.
+fn handle_reckoning(
+    s: &SyncSelect,
+    waiter: Waiter,
+    render_sender: Sender<()>,
+) -> JoinHandle<Result> {
+    s.spawn(move || {
+        let spinner = SpinSleeper::default();
+
+        loop {
+            // wait for the renderer to report motion
+            waiter.wait();
+            waiter.reset();
+
+            _ = render_sender.try_send(());
+
+            // frames don't need to outpace the game
+            spinner.sleep(GAME_SPEED);
+        }
+    })
+}
+
 pub fn render_loop(
     s: &SyncSelect,
     (event_sender, fps_sender): (Arc<EventSender>, Sender<()>),
     (sys_event_receiver, fps_receiver): (Receiver<SysEvent>, Receiver<()>),
-    (fps, ft, tps, ping, state): (
-        Arc<Fps>,
-        Arc<RwLock<Duration>>,
-        Arc<AtomicU16>,
-        Arc<RwLock<Duration>>,
-        Arc<AtomicRenderStateKind>,
-    ),
-    cfg: Config,
+    ((fps, ft, tps, clock), state): (Stats, Arc<AtomicRenderStateKind>),
+    (cam, waiter_reckon): (Camera, Waiter),
+    (source, cfg): (Source, Config),
 ) -> JoinHandle<Result> {
     let (mm_sender, mm_receiver) = bounded(1);
     let (mw_sender, mw_receiver) = bounded(1);
     let (kb_sender, kb_receiver) = bounded(1);
+    let (control_sender, control_receiver) = bounded(8);
 
     let (input_sender, input_receiver) = bounded(1);
     let (render_sender, render_receiver) = bounded(1);
 
-    // Networking
-    if cfg.is_online() {
-        // init TCP and UDP threads
-        init_conn(
+    // Networking (even when the server is within the client), or a replay in its place
+    match source {
+        Source::Server(endpoint) => init_conn(
             s,
             event_sender.clone(),
             render_sender.clone(),
             input_receiver,
-            (tps.clone(), ping.clone()),
-            cfg,
-        );
+            (tps.clone(), clock.clone()),
+            (endpoint, cfg),
+        ),
+        Source::Replay(replay) => init_replay(
+            s,
+            event_sender.clone(),
+            render_sender.clone(),
+            (input_receiver, control_receiver),
+            (tps.clone(), cam.clone()),
+            (replay, cfg),
+        ),
     }
 
+    // extrapolated frames
+    handle_reckoning(s, waiter_reckon, render_sender.clone());
+
     // handle input throughput
     handle_sys_events(
         s,
-        (fps.clone(), ft, tps, ping),
-        (mm_sender, mw_sender, kb_sender),
+        (fps.clone(), ft, tps, clock),
+        (mm_sender, mw_sender, kb_sender, control_sender),
         (event_sender.clone(), sys_event_receiver),
     );
 
     // process current input in real-time
     process_input(
         s,
+        cam,
         event_sender.clone(),
         input_sender,
         render_sender,
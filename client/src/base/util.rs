@@ -19,6 +19,7 @@ pub struct UnifLocs {
     view: Option<NativeUniformLocation>,
     proj: Option<NativeUniformLocation>,
     cam_pos: Option<NativeUniformLocation>,
+    ambient: Option<NativeUniformLocation>,
     p_lights_len: Option<NativeUniformLocation>,
     p_lights: [[Option<NativeUniformLocation>; 2]; 16],
 }
@@ -29,6 +30,7 @@ impl UnifLocs {
             let view = gl.get_uniform_location(native, "view");
             let proj = gl.get_uniform_location(native, "proj");
             let cam_pos = gl.get_uniform_location(native, "cam_pos");
+            let ambient = gl.get_uniform_location(native, "ambient_strength");
             let p_lights_len = gl.get_uniform_location(native, "p_lights_len");
 
             let p_lights_dyn = (0..16)
@@ -52,6 +54,7 @@ impl UnifLocs {
                 view,
                 proj,
                 cam_pos,
+                ambient,
                 p_lights_len,
                 p_lights,
             }
@@ -70,6 +73,10 @@ impl UnifLocs {
         self.cam_pos.as_ref()
     }
 
+    pub const fn ambient(&self) -> Option<&NativeUniformLocation> {
+        self.ambient.as_ref()
+    }
+
     pub const fn p_lights_len(&self) -> Option<&NativeUniformLocation> {
         self.p_lights_len.as_ref()
     }
@@ -229,7 +236,8 @@ pub fn init_shaders(gl: &Context) -> Result<Shaders> {
     Ok(shaders)
 }
 
-pub fn init() -> Result<(
+/// Every SDL subsystem (and the GL context) the client runs on.
+pub type Backend = (
     Sdl,
     VideoSubsystem,
     TimerSubsystem,
@@ -238,7 +246,9 @@ pub fn init() -> Result<(
     EventSubsystem,
     EventPump,
     GLContext,
-)> {
+);
+
+pub fn init() -> Result<Backend> {
     let sdl = sdl2::init()?;
     let video = sdl.video()?;
     let timer = sdl.timer()?;
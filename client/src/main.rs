@@ -5,7 +5,6 @@ mod base;
 use base::*;
 use crossbeam_channel::{Sender, bounded};
 use glow::HasContext;
-use rand::Rng;
 use sdl2::{
     EventPump, TimerSubsystem,
     event::{Event, EventSender, WindowEvent},
@@ -13,12 +12,13 @@ use sdl2::{
     video::{SwapInterval, Window},
 };
 use std::{
+    collections::HashMap,
     sync::{Arc, atomic::Ordering},
     thread::{JoinHandle, spawn},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use sync_select::*;
-use ultraviolet::Vec3;
+use ultraviolet::{Vec2, Vec3};
 
 #[cfg(debug_assertions)]
 use glow::{FILL, FRONT_AND_BACK, LINE};
@@ -51,15 +51,29 @@ fn handle_ctrlc(s: &SyncSelect, event_sender: Arc<EventSender>) -> Result {
 fn process_events(
     gl: &GL,
     window: Window,
-    timer_fps_cfg: &mut impl FnMut(Id) -> Id,
     mut ep: EventPump,
-    (cam, objects, state): (Camera, ObjectsRef, RenderState),
-    sys_event_sender: Sender<SysEvent>,
+    (cam, objects, mut world, mut remotes, state): (
+        Camera,
+        ObjectsRef,
+        Collider,
+        Remotes,
+        RenderState,
+    ),
+    (sys_event_sender, notifier_reckon): (Sender<SysEvent>, Notifier),
     ft: Arc<RwLock<Duration>>,
 ) -> Result {
     #[cfg(debug_assertions)]
     let mut polygon_mode = false;
 
+    // names of the other players
+    let mut names = HashMap::new();
+
+    // whether the user's input moves the camera
+    let mut is_steering = true;
+
+    // the server's snapshots of the player against the prediction
+    let mut check = DesyncCheck::default();
+
     // closure that handles handles the render event
     let render = |action| {
         // usually window-based events
@@ -70,7 +84,7 @@ fn process_events(
             cam.write().upt_aspect_ratio(w, h);
         }
         // start frame timer
-        let t = std::time::Instant::now();
+        let t = Instant::now();
 
         // render a frame
         display(gl, &window, &cam.read(), &objects.read());
@@ -91,45 +105,43 @@ fn process_events(
                             // // remove and deallocate all player objects
                             // objects.write().retain(gl, ObjType::Player);
                         }
-                        GameEvent::Render(action) => render(action),
+                        GameEvent::Render(action) => {
+                            // keep rendering while remote players are extrapolated/blended
+                            if remotes.advance(Instant::now(), &mut objects.write()) {
+                                notifier_reckon.notify();
+                            }
+                            render(action)
+                        }
                         GameEvent::Object(action) => {
                             match action {
-                                ObjectAction::Add { data } => objects.write().new_cube(
-                                    data.id,
-                                    InstanceKind::NormalCube,
-                                    Transformations::new(data.cam.eye, data.dim),
-                                    data.color.data(),
-                                    data.color.is_emit(),
-                                ),
+                                ObjectAction::Add { data } => {
+                                    objects.write().new_cube(
+                                        data.id,
+                                        InstanceKind::NormalCube,
+                                        Transformations::new(data.cam.eye, data.dim),
+                                        data.color.data(),
+                                        data.color.is_emit(),
+                                    );
+                                    let facing =
+                                        Vec2::new(data.cam.yaw.radians(), data.cam.pitch.radians());
+                                    remotes.insert(data.id, data.cam.eye, facing, Instant::now());
+
+                                    info!("{} is here", data.name);
+                                    names.insert(data.id, data.name);
+                                }
                                 ObjectAction::Remove { id } => {
                                     objects.write().remove(id);
+                                    remotes.remove(id);
+
+                                    if let Some(name) = names.remove(&id) {
+                                        info!("{name} has left")
+                                    }
                                 }
                                 ObjectAction::Upt { data } => {
                                     if let Some(obj) = objects.write().get_mut(data.id) {
-                                        if data.cam.is_modified() {
-                                            if let Some(pos) = data.cam.eye {
-                                                obj.trans.translation.translate(
-                                                    &(pos
-                                                        - obj
-                                                            .trans
-                                                            .translation
-                                                            .extract_translation()),
-                                                );
-                                            }
-
-                                            if let (Some(yaw), Some(pitch)) =
-                                                (data.cam.yaw, data.cam.pitch)
-                                            {
-                                                obj.trans.rotation =
-                                                    ultraviolet::Mat4::from_euler_angles(
-                                                        0.0,
-                                                        pitch.radians(),
-                                                        -yaw.radians(),
-                                                    )
-                                            }
-
-                                            // obj.cam.patch(&mut data.cam);
-                                        }
+                                        // position and facing are dead-reckoned when rendering
+                                        remotes.apply(&data, Instant::now());
+
                                         if let Some(dim) = data.dim {
                                             obj.trans.scale_upt(dim);
                                             // obj.dim = dim
@@ -139,6 +151,24 @@ fn process_events(
                                         error!("Object {} doesn't exist.", data.id)
                                     }
                                 }
+                                ObjectAction::Respawn { id, pos } => {
+                                    // snap (rather than blend) to the spawn point, facing ahead
+                                    remotes.insert(id, pos, Vec2::zero(), Instant::now());
+
+                                    if let Some(obj) = objects.write().get_mut(id) {
+                                        obj.trans.translation =
+                                            ultraviolet::Mat4::from_translation(pos);
+                                        obj.trans.rotation = ultraviolet::Mat4::identity();
+                                        obj.trans.model_upt();
+                                    }
+                                }
+                                ObjectAction::UserRespawn { pos } => {
+                                    let mut cam = cam.write();
+                                    cam.respawn(pos);
+
+                                    // placed elsewhere, so nothing can be predicted from the latest snapshot
+                                    check.reset();
+                                }
                                 ObjectAction::User { mut data } => {
                                     if data.cam.is_modified() {
                                         let mut cam = cam.write();
@@ -148,28 +178,45 @@ fn process_events(
                                 }
                             };
                         }
+                        GameEvent::Movement(movement) => cam.write().set_movement(movement),
+                        GameEvent::Sent(input) => check.sent(input),
+                        GameEvent::Snapshot(snapshot) => {
+                            let cam = cam.read();
+                            if let Some(desync) = check.check(snapshot, &world, cam.movement()) {
+                                warn!(
+                                    "[Desync] {desync} ({} of {} snapshots)",
+                                    check.desyncs(),
+                                    check.checked()
+                                );
+                            }
+                        }
+                        GameEvent::World(data) => {
+                            // everything (players included) is rebuilt on top of the new world
+                            objects.write().load_world(&data);
+                            remotes.clear();
+                            names.clear();
+                            world = data.collider();
+
+                            // until the server places the player
+                            let spawn = data.spawns().first().copied().unwrap_or_default();
+                            cam.write().respawn(spawn);
+
+                            // (a new session numbers its inputs anew)
+                            check = DesyncCheck::default();
+                        }
+                        GameEvent::Steer(is) => is_steering = is,
+                        GameEvent::User(_) if !is_steering => (),
                         GameEvent::User(action) => {
                             match action {
-                                UserAction::Keyboard(kb) => cam.write().input(kb),
-                                UserAction::Wheel(Wheel { precise_y }) => {
+                                UserAction::Keyboard(kb) => cam.write().input(kb, &world),
+                                UserAction::Wheel(Wheel { precise_y, .. }) => {
                                     cam.write().upt_fov(precise_y)
                                 }
-                                UserAction::Motion(Motion { xrel, yrel }) => {
+                                UserAction::Motion(Motion { xrel, yrel, .. }) => {
                                     cam.write().look_at(xrel, yrel)
                                 }
                             };
                         }
-                        // set a new fps target
-                        GameEvent::Fps(fps) => {
-                            // old and new rendering states
-                            let old_state = timer_fps_cfg(fps) == 0;
-                            let new_state = fps == 0;
-
-                            // only reload the rendering state if fps polygon_mode is changed
-                            if old_state != new_state {
-                                state.store(RenderStateKind::Reload, Ordering::Relaxed);
-                            }
-                        }
                     };
                 }
             }
@@ -198,6 +245,9 @@ fn process_events(
                 repeat: false,
                 ..
             } => {
+                if let Some(control) = replay_control(key) {
+                    _ = sys_event_sender.try_send(SysEvent::Replay(control));
+                }
                 if let Some(keys) = try_from_scancode(key) {
                     _ = sys_event_sender.try_send(SysEvent::Keyboard(keys, true));
 
@@ -324,99 +374,26 @@ fn main() -> Result {
     ///////////////////////////////////////////////////////////////////////////////////////////
 
     // TODO - please implement instanced-based rendering
-    let objects = {
-        let mut raw = RawObjects::new(&gl, &programs)?;
-
-        raw.new_cube(
-            0,
-            InstanceKind::NormalCube,
-            Transformations::new([0.0, -2.0, 0.0], [7.5, 0.1, 7.5]),
-            [1.0, 1.0, 1.0, 0.96],
-            false,
-        );
-
-        raw.new_cube(
-            1,
-            InstanceKind::SimpleCube,
-            Transformations::new([3.0, 0.0, -4.0], [0.5, 0.5, 0.5]),
-            [0.0, 0.0, 1.0, 1.0],
-            true,
-        );
-
-        raw.new_cube(
-            2,
-            InstanceKind::SimpleCube,
-            Transformations::new([0.0, 2.0, 8.5], [0.5, 0.5, 0.5]),
-            [0.0, 1.0, 0.0, 1.0],
-            true,
-        );
-
-        raw.new_cube(
-            3,
-            InstanceKind::SimpleCube,
-            Transformations::new([-3.0, 1.0, -4.5], [0.5, 0.5, 0.5]),
-            [1.0, 0.0, 0.0, 1.0],
-            true,
-        );
-
-        raw.new_cube(
-            4,
-            InstanceKind::SimpleCube,
-            Transformations::new([0.0, 20.0, 0.0], [0.5, 0.5, 0.5]),
-            [1.0, 1.0, 1.0, 1.0],
-            true,
-        );
-
-        // DEBUGGING - generate random cubes
-        let mut rng = rand::rng();
-        for id in 5..4096 {
-            let pos = Vec3::new(
-                if rng.random_bool(0.5) {
-                    rng.random_range(-1000.0..-3.0)
-                } else {
-                    rng.random_range(3.0..1000.0)
-                },
-                rng.random_range(-1000.0..1000.0),
-                if rng.random_bool(0.5) {
-                    rng.random_range(-1000.0..-3.0)
-                } else {
-                    rng.random_range(3.0..1000.0)
-                },
-            );
-
-            let dim = Vec3::new(
-                rng.random_range(1.0..20.0),
-                rng.random_range(1.0..20.0),
-                rng.random_range(1.0..20.0),
-            );
-
-            let color = [
-                rng.random_range(0.0..1.0),
-                rng.random_range(0.0..1.0),
-                rng.random_range(0.0..1.0),
-                rng.random_range(0.1..1.0),
-            ];
-
-            raw.new_cube(
-                id as Id,
-                InstanceKind::SimpleCube,
-                Transformations::new(pos, dim),
-                color,
-                false,
-            );
-        }
-        Objects::new(raw)
-    };
+    let objects = Objects::new(RawObjects::new(&gl, &programs)?);
 
-    // the user's camera
+    // the user's camera (until the server tells it the movement rules)
     let cam = Camera::new(window.size());
+    cam.write().set_movement(cfg.movement());
 
     // frame-time variable
     let ft: Arc<RwLock<Duration>> = Default::default();
 
+    // estimation of the server's clock (and the real round-trip time)
+    let clock = Clock::default();
+
     // determinant of the status of threads
     let state: RenderState = Arc::new(AtomicRenderStateKind::new(RenderStateKind::Pass));
 
+    // dead-reckoning of remote players
+    let remotes = Remotes::new(cfg.max_extrapolation(), cfg.extrapolation_blend());
+    let waiter_reckon = Waiter::default();
+    let notifier_reckon = waiter_reckon.notifier();
+
     // short-circuiting local thread manager
     let s = SyncSelect::default();
 
@@ -427,19 +404,25 @@ fn main() -> Result {
     // handle SIGINT
     handle_ctrlc(&s, event_sender.clone())?;
 
+    // offline or hosting, the game is served from within the client (joined like any other server)
+    let local = LocalServer::start(&cfg)?;
+    let source = match (&local, cfg.replay()) {
+        (_, Some(path)) => Source::Replay(Replay::open(path)?),
+        (Some(local), None) => Source::Server(local.endpoint().clone()),
+        (None, None) => Source::Server(Endpoint::remote(&cfg)),
+    };
+
     // input & network handling
     let _state = render_loop(
         &s,
         (event_sender.clone(), fps_sender_1),
         (sys_event_receiver, fps_receiver_2),
         (
-            fps.clone(),
-            ft.clone(),
-            Default::default(),
-            Default::default(),
+            (fps.clone(), ft.clone(), Default::default(), clock),
             state.clone(),
         ),
-        cfg,
+        (cam.clone(), waiter_reckon),
+        (source, cfg),
     );
 
     // post short-circuitry handler
@@ -449,17 +432,17 @@ fn main() -> Result {
     if let Err(e) = process_events(
         &gl,
         window,
-        &mut timer_fps_cfg,
         ep,
-        (cam, &objects, state),
-        sys_event_sender,
+        (cam, &objects, Default::default(), remotes, state),
+        (sys_event_sender, notifier_reckon),
         ft,
     ) {
         error!("{e}")
     }
 
-    // clean everything up
+    // clean everything up (telling anyone on a hosted server why)
     clean_up(&gl, programs, objects.read().buffers());
+    drop(local);
 
     Ok(())
 }
@@ -0,0 +1,197 @@
+use crate::*;
+use std::{
+    fmt::Write,
+    thread::{Thread, sleep},
+    time::{Duration, Instant},
+};
+
+/// seconds left at which a shutdown is announced
+const COUNTDOWN: [u64; 8] = [60, 30, 10, 5, 4, 3, 2, 1];
+
+/// Executes administrative commands against the running server.
+#[derive(Clone)]
+pub struct Admin {
+    cfg: Arc<Config>, // as started
+    clients_tcp: TcpClients,
+    clients_udp: UdpClients,
+    game: Arc<Game>,
+    remote: Remote,
+    main: Thread, // unparked to stop the server
+    started: Instant,
+}
+
+impl Admin {
+    pub fn new(
+        cfg: Config,
+        clients_tcp: TcpClients,
+        clients_udp: UdpClients,
+        game: Arc<Game>,
+        remote: Remote,
+        main: Thread,
+    ) -> Self {
+        Self {
+            cfg: Arc::new(cfg),
+            clients_tcp,
+            clients_udp,
+            game,
+            remote,
+            main,
+            started: Instant::now(),
+        }
+    }
+
+    /// Parse and execute a command line.
+    pub fn run(&self, line: &str) -> Result<String> {
+        self.execute(Command::parse(line)?)
+    }
+
+    /// Execute a command, returning its (human-readable) output.
+    pub fn execute(&self, cmd: Command) -> Result<String> {
+        info!("[Admin] {cmd}");
+
+        match cmd {
+            Command::Help => Ok(Command::USAGE.to_string()),
+            Command::List => Ok(self.list()),
+            Command::Status => Ok(self.status()),
+            Command::Kick(id) => {
+                if !self.clients_tcp.read().contains_key(&id) {
+                    return Err(format!("No player {id}.").into());
+                }
+                self.remote.close(id, RejectReason::Kicked)?;
+                Ok(format!("Kicked {id}."))
+            }
+            Command::Ban(ip) => {
+                let is_new = self.game.ban(ip);
+
+                // whoever is already playing from there
+                let kicked = self
+                    .clients_tcp
+                    .read()
+                    .iter()
+                    .filter(|(_, addr)| addr.ip() == ip)
+                    .map(|(&id, _)| self.remote.close(id, RejectReason::Banned))
+                    .collect::<Result<Vec<_>>>()?
+                    .len();
+
+                if !is_new {
+                    return Ok(format!("{ip} is already banned."));
+                }
+                Ok(format!("Banned {ip} ({kicked} player(s) disconnected)."))
+            }
+            Command::Say(msg) => {
+                self.say(&msg)?;
+                Ok(format!("[Server] {msg}"))
+            }
+            Command::Tps(tps) => {
+                self.game.set_tps(tps);
+                Ok(format!("TPS set to {tps}."))
+            }
+            Command::Teleport(id, pos) => {
+                if !self.clients_tcp.read().contains_key(&id) {
+                    return Err(format!("No player {id}.").into());
+                }
+                self.remote.teleport(id, pos)?;
+                Ok(format!("Teleported {id} to {pos:?}."))
+            }
+            Command::Reload => self.reload(),
+            Command::Shutdown(delay) => {
+                self.shutdown(delay)?;
+                Ok(format!("Shutting down in {}s.", delay.as_secs()))
+            }
+        }
+    }
+
+    fn list(&self) -> String {
+        let clients = self.clients_udp.read();
+        let mut clients = clients.iter().collect::<Vec<_>>();
+        clients.sort_unstable_by_key(|(_, c)| c.id);
+
+        let mut out = format!("{} player(s)", clients.len());
+        for (addr, c) in clients {
+            let pos = c.cam.eye;
+            _ = write!(
+                out,
+                "\n{:>5}  {:<16}  {addr:<21}  ({:.1}, {:.1}, {:.1})",
+                c.id, c.name, pos.x, pos.y, pos.z
+            );
+        }
+        out
+    }
+
+    fn status(&self) -> String {
+        let uptime = self.started.elapsed().as_secs();
+        format!(
+            "players: {}/{}\ntps: {}\nuptime: {}h {:02}m {:02}s\nworld: {} objects\nbans: {}",
+            self.clients_udp.read().len(),
+            self.game.rules().max_players,
+            self.game.tps(),
+            uptime / 3600,
+            uptime / 60 % 60,
+            uptime % 60,
+            self.game.world().len(),
+            self.game.bans(),
+        )
+    }
+
+    /// Apply the (reloaded) config file to the running game.
+    fn reload(&self) -> Result<String> {
+        let path = self
+            .cfg
+            .path()
+            .ok_or("No config file to reload.")?
+            .display();
+        let new = self.cfg.reload()?;
+
+        let mut out = format!("Reloaded {path}.");
+        let fixed = self.cfg.fixed_changes(&new);
+        if !fixed.is_empty() {
+            _ = write!(out, "\nIgnored until restart: {}", fixed.join(", "));
+        }
+
+        let rules = new.rules();
+        let old = self.game.rules();
+        self.game.set_rules(rules);
+
+        // clients predict their own movement
+        if rules.movement != old.movement {
+            self.remote.broadcast(rules.movement.serialize().to_vec())?;
+        }
+        Ok(out)
+    }
+
+    /// Send a message to every player.
+    fn say(&self, msg: &str) -> Result {
+        let notice = Notice {
+            text: Text::new(msg)?,
+        };
+        self.remote.broadcast(notice.serialize().to_vec())
+    }
+
+    /// Stop the server once the countdown (announced to every player) is over.
+    fn shutdown(&self, delay: Duration) -> Result {
+        if self.game.is_closed() {
+            return Err("Already shutting down.".into());
+        }
+
+        // nobody joins in the meantime
+        self.game.close();
+
+        let admin = self.clone();
+        let end = Instant::now() + delay;
+        spawn(move || {
+            // the whole delay, then the remaining seconds along the way
+            let total = delay.as_secs();
+            let marks = COUNTDOWN.into_iter().filter(|&secs| secs < total);
+
+            for secs in [total].into_iter().chain(marks).filter(|&secs| secs > 0) {
+                let at = end - Duration::from_secs(secs);
+                sleep(at.saturating_duration_since(Instant::now()));
+                _ = admin.say(&format!("Server shutting down in {secs}s"));
+            }
+
+            sleep(end.saturating_duration_since(Instant::now()));
+            admin.main.unpark();
+        });
+        Ok(())
+    }
+}
@@ -1,36 +1,381 @@
 use crate::*;
 use clap::Parser;
-use std::time::Duration;
+use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-/// Calculates the duration of a single game tick.
-fn parse_tps(s: &str) -> Result<Duration> {
-    let tps = s.parse::<u64>()?;
+/// default size (MiB) of replay files
+const DEFAULT_FILE_SIZE: f32 = 64.0;
 
+/// default number of replay files kept
+const DEFAULT_MAX_FILES: usize = 16;
+
+/// furthest (secs) interactions may be judged back in time
+const MAX_REWIND: f32 = 5.0;
+
+fn check_tps(tps: u16) -> Result<u16> {
     if tps == 0 {
         return Err("TPS must be greater than zero.".into());
-    } else if tps > 1024 {
-        return Err("TPS must be less than or equal to 1024.".into());
+    } else if tps > MAX_TPS {
+        return Err(format!("TPS must be less than or equal to {MAX_TPS}.").into());
+    }
+    Ok(tps)
+}
+
+fn check_max_players(max: usize) -> Result<usize> {
+    if max == 0 {
+        return Err("Max players must be greater than zero.".into());
+    } else if max > PLAYER_IDS.len() {
+        return Err(format!(
+            "Max players must be less than or equal to {}.",
+            PLAYER_IDS.len()
+        )
+        .into());
+    }
+    Ok(max)
+}
+
+fn check_admin_addr(addr: SocketAddr) -> Result<SocketAddr> {
+    if !addr.ip().is_loopback() {
+        return Err("The admin socket must be bound to a loopback address.".into());
+    }
+    Ok(addr)
+}
+
+fn check_timeout(secs: f32) -> Result<Duration> {
+    if !(secs.is_finite() && secs > 0.0) {
+        return Err("Timeouts must be greater than zero.".into());
+    }
+    Ok(Duration::from_secs_f32(secs))
+}
+
+fn check_max_rewind(secs: f32) -> Result<Duration> {
+    if !(0.0..=MAX_REWIND).contains(&secs) {
+        return Err(format!("Max rewind must be within 0 and {MAX_REWIND} seconds.").into());
+    }
+    Ok(Duration::from_secs_f32(secs))
+}
+
+fn check_file_size(mib: f32) -> Result<u64> {
+    if !(mib.is_finite() && mib > 0.0) {
+        return Err("Replay files must be larger than zero.".into());
+    }
+    Ok((mib * (1 << 20) as f32) as u64)
+}
+
+fn check_max_files(max: usize) -> Result<usize> {
+    if max == 0 {
+        return Err("At least one replay file must be kept.".into());
+    }
+    Ok(max)
+}
+
+fn parse_tps(s: &str) -> Result<u16> {
+    check_tps(s.parse()?)
+}
+
+fn parse_max_players(s: &str) -> Result<usize> {
+    check_max_players(s.parse()?)
+}
+
+fn parse_admin_addr(s: &str) -> Result<SocketAddr> {
+    check_admin_addr(s.parse::<SocketAddr>().map_err(|e| e.to_string())?)
+}
+
+fn parse_max_rewind(s: &str) -> Result<Duration> {
+    check_max_rewind(s.parse::<f32>().map_err(|e| e.to_string())?)
+}
+
+fn parse_spawn_policy(s: &str) -> Result<SpawnPolicy> {
+    SpawnPolicy::parse(s).map_err(Into::into)
+}
+
+fn parse_movement(s: &str) -> Result<Movement> {
+    Movement::parse(s).map_err(Into::into)
+}
+
+// command-line options (overriding the config file)
+#[derive(Parser, Clone, Debug)]
+struct Args {
+    /// Config file (TOML) of any settings not given as options
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Local TCP IP address [default: 127.0.0.1:54269]
+    #[arg(short, long)]
+    tcp_addr: Option<SocketAddr>,
+
+    /// Local UDP IP address [default: 127.0.0.1:54277]
+    #[arg(short, long)]
+    udp_addr: Option<SocketAddr>,
+
+    /// Server ticks/sec [default: 128]
+    #[arg(long, value_parser = parse_tps)]
+    tps: Option<u16>,
+
+    /// Player movement mode [free-fly, walking] [default: free-fly]
+    #[arg(long, value_parser = parse_movement)]
+    movement: Option<Movement>,
+
+    /// Map file to load (a random demo world otherwise)
+    #[arg(long)]
+    map: Option<PathBuf>,
+
+    /// Seed of generated world geometry (random otherwise)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// How spawn points are chosen [random, round-robin, farthest] [default: random]
+    #[arg(long, value_parser = parse_spawn_policy)]
+    spawn_policy: Option<SpawnPolicy>,
+
+    /// Maximum number of players at once [default: 64]
+    #[arg(long, value_parser = parse_max_players)]
+    max_players: Option<usize>,
+
+    /// Furthest (secs) interactions are judged back in time, from the player's view of the game [default: 0.5]
+    #[arg(long, value_name = "SECS", value_parser = parse_max_rewind)]
+    max_rewind: Option<Duration>,
+
+    /// Local (loopback) TCP address of the admin control socket [default: 127.0.0.1:54283]
+    #[arg(long, value_parser = parse_admin_addr)]
+    admin_addr: Option<SocketAddr>,
+
+    /// Shared secret of the admin control socket, which is disabled without one
+    /// [env: BLAZED_ADMIN_SECRET]
+    #[arg(long)]
+    admin_secret: Option<String>,
+
+    /// Directory to record every tick of the game to (replay files), not recorded otherwise
+    #[arg(long, value_name = "DIR")]
+    record: Option<PathBuf>,
+
+    /// Largest size (MiB) of a replay file before another is started [default: 64]
+    #[arg(long, value_name = "SIZE")]
+    record_file_size: Option<f32>,
+
+    /// Most replay files kept in the directory (the oldest are deleted) [default: 16]
+    #[arg(long, value_name = "MAX")]
+    record_max_files: Option<usize>,
+}
+
+/// Movement parameters of the config file (on top of the mode's defaults).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MovementFile {
+    mode: Option<String>,
+    speed: Option<f32>,
+    fov_min: Option<f32>,
+    fov_max: Option<f32>,
+    gravity: Option<f32>,
+    jump_speed: Option<f32>,
+    terminal_speed: Option<f32>,
+    crouch_speed: Option<f32>,
+    air_control: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LimitsFile {
+    packets_per_sec: Option<u32>,
+    max_rewind: Option<f32>, // secs
+}
+
+/// Timeouts (secs) of the config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TimeoutsFile {
+    handshake: Option<f32>,
+    idle: Option<f32>,
+}
+
+/// Recording settings of the config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RecordFile {
+    dir: Option<PathBuf>,
+    file_size: Option<f32>, // MiB
+    max_files: Option<usize>,
+}
+
+/// The config file (every setting is optional).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    tcp_addr: Option<SocketAddr>,
+    udp_addr: Option<SocketAddr>,
+    tps: Option<u16>,
+    max_players: Option<usize>,
+    map: Option<PathBuf>,
+    seed: Option<u64>,
+    spawn_policy: Option<String>,
+    admin_addr: Option<SocketAddr>,
+    admin_secret: Option<String>,
+    movement: MovementFile,
+    limits: LimitsFile,
+    timeouts: TimeoutsFile,
+    record: RecordFile,
+}
+
+impl ConfigFile {
+    fn load(path: &Path) -> Result<Self> {
+        let s = std::fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read config '{}': {e}", path.display()))?;
+        toml::from_str(&s).map_err(|e| format!("Invalid config '{}': {e}", path.display()).into())
     }
-    let delay = Duration::from_secs_f32(1.0 / tps as f32);
-    Ok(delay)
 }
 
-#[derive(Parser, Debug)]
+/// Server settings (options > config file > defaults).
+#[derive(Debug)]
 pub struct Config {
-    /// Local TCP IP address
-    #[arg(short, long, default_value_t = get_socket_addr(TCP_PORT))]
+    args: Args, // kept for reloading
     tcp_addr: SocketAddr,
-
-    /// Local UDP IP address
-    #[arg(short, long, default_value_t = get_socket_addr(UDP_PORT))]
     udp_addr: SocketAddr,
-
-    /// Server ticks/sec
-    #[arg(long, default_value = "128", value_parser = parse_tps)]
-    tps: Duration,
+    map: Option<PathBuf>,
+    seed: Option<u64>,
+    spawn_policy: SpawnPolicy,
+    admin_addr: SocketAddr,
+    admin_secret: Option<String>,
+    recording: Option<Recording>,
+    rules: Rules,
 }
 
 impl Config {
+    /// Parse the options and load the config file (if any).
+    pub fn load() -> Result<Self> {
+        Self::merge(Args::parse())
+    }
+
+    /// Parse the given options (e.g., within a test) and load the config file (if any).
+    pub fn parse_from<T: Into<std::ffi::OsString> + Clone>(
+        args: impl IntoIterator<Item = T>,
+    ) -> Result<Self> {
+        let args = Args::try_parse_from(args).map_err(|e| e.to_string())?;
+        Self::merge(args)
+    }
+
+    /// Load the config file again (the options still take precedence).
+    pub fn reload(&self) -> Result<Self> {
+        Self::merge(self.args.clone())
+    }
+
+    fn merge(args: Args) -> Result<Self> {
+        let file = match &args.config {
+            Some(path) => ConfigFile::load(path)?,
+            None => Default::default(),
+        };
+        let defaults = Rules::default();
+
+        // the mode's defaults, adjusted by any parameters of the file
+        let mut movement = match (args.movement, &file.movement.mode) {
+            (Some(movement), _) => movement,
+            (None, Some(mode)) => Movement::parse(mode)?,
+            (None, None) => Default::default(),
+        };
+        let m = &file.movement;
+        let params = [
+            (&mut movement.speed, m.speed),
+            (&mut movement.fov_min, m.fov_min),
+            (&mut movement.fov_max, m.fov_max),
+            (&mut movement.gravity, m.gravity),
+            (&mut movement.jump_speed, m.jump_speed),
+            (&mut movement.terminal_speed, m.terminal_speed),
+            (&mut movement.crouch_speed, m.crouch_speed),
+            (&mut movement.air_control, m.air_control),
+        ];
+        for (param, value) in params {
+            *param = value.unwrap_or(*param);
+        }
+        movement.validate()?;
+
+        let rules = Rules {
+            tps: check_tps(args.tps.or(file.tps).unwrap_or(defaults.tps))?,
+            max_players: check_max_players(
+                args.max_players
+                    .or(file.max_players)
+                    .unwrap_or(defaults.max_players),
+            )?,
+            movement,
+            packets_per_sec: file
+                .limits
+                .packets_per_sec
+                .unwrap_or(defaults.packets_per_sec)
+                .max(1),
+            handshake_timeout: file
+                .timeouts
+                .handshake
+                .map_or(Ok(defaults.handshake_timeout), check_timeout)?,
+            idle_timeout: file
+                .timeouts
+                .idle
+                .map_or(Ok(defaults.idle_timeout), check_timeout)?,
+            max_rewind: match args.max_rewind {
+                Some(max) => max,
+                None => file
+                    .limits
+                    .max_rewind
+                    .map_or(Ok(defaults.max_rewind), check_max_rewind)?,
+            },
+        };
+
+        let spawn_policy = match (args.spawn_policy, &file.spawn_policy) {
+            (Some(policy), _) => policy,
+            (None, Some(policy)) => SpawnPolicy::parse(policy)?,
+            (None, None) => Default::default(),
+        };
+
+        let recording = match args.record.clone().or(file.record.dir) {
+            Some(dir) => Some(Recording {
+                dir,
+                max_file_size: check_file_size(
+                    args.record_file_size
+                        .or(file.record.file_size)
+                        .unwrap_or(DEFAULT_FILE_SIZE),
+                )?,
+                max_files: check_max_files(
+                    args.record_max_files
+                        .or(file.record.max_files)
+                        .unwrap_or(DEFAULT_MAX_FILES),
+                )?,
+            }),
+            None => None,
+        };
+
+        Ok(Self {
+            tcp_addr: args
+                .tcp_addr
+                .or(file.tcp_addr)
+                .unwrap_or(get_socket_addr(TCP_PORT)),
+            udp_addr: args
+                .udp_addr
+                .or(file.udp_addr)
+                .unwrap_or(get_socket_addr(UDP_PORT)),
+            map: args.map.clone().or(file.map),
+            seed: args.seed.or(file.seed),
+            spawn_policy,
+            admin_addr: check_admin_addr(
+                args.admin_addr
+                    .or(file.admin_addr)
+                    .unwrap_or(get_socket_addr(ADMIN_PORT)),
+            )?,
+            admin_secret: args
+                .admin_secret
+                .clone()
+                .or(file.admin_secret)
+                .or_else(|| std::env::var(ADMIN_SECRET_VAR).ok())
+                .filter(|s| !s.is_empty()),
+            recording,
+            rules,
+            args,
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.args.config.as_deref()
+    }
+
     pub const fn tcp_addr(&self) -> SocketAddr {
         self.tcp_addr
     }
@@ -39,13 +384,236 @@ impl Config {
         self.udp_addr
     }
 
-    pub const fn tps(&self) -> Duration {
-        self.tps
+    pub fn map(&self) -> Option<&Path> {
+        self.map.as_deref()
+    }
+
+    pub const fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub const fn spawn_policy(&self) -> SpawnPolicy {
+        self.spawn_policy
+    }
+
+    pub const fn admin_addr(&self) -> SocketAddr {
+        self.admin_addr
+    }
+
+    pub fn admin_secret(&self) -> Option<&str> {
+        self.admin_secret.as_deref()
+    }
+
+    /// Where the game is recorded (if at all).
+    pub const fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    /// Settings that can be changed while running.
+    pub const fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// Settings that differ from another config but only take effect on restart.
+    pub fn fixed_changes(&self, other: &Self) -> Vec<&'static str> {
+        [
+            ("tcp_addr", self.tcp_addr != other.tcp_addr),
+            ("udp_addr", self.udp_addr != other.udp_addr),
+            ("map", self.map != other.map),
+            ("seed", self.seed != other.seed),
+            ("spawn_policy", self.spawn_policy != other.spawn_policy),
+            ("admin_addr", self.admin_addr != other.admin_addr),
+            ("admin_secret", self.admin_secret != other.admin_secret),
+            ("record", self.recording != other.recording),
+        ]
+        .into_iter()
+        .filter_map(|(name, is_changed)| is_changed.then_some(name))
+        .collect()
     }
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self::parse()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A config file of a test, removed once dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(toml: &str) -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let n = COUNT.fetch_add(1, Ordering::Relaxed);
+            let path = std::env::temp_dir().join(format!("blazed-{}-{n}.toml", std::process::id()));
+            let file = Self(path);
+            file.write(toml);
+            file
+        }
+
+        fn write(&self, toml: &str) {
+            std::fs::write(&self.0, toml).unwrap();
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// Whether a config is as expected.
+    type Check = fn(&Config) -> bool;
+
+    /// The config of a file, with any options.
+    fn config(file: &TempFile, args: &[&str]) -> Result<Config> {
+        let path = file.0.to_str().unwrap();
+        Config::parse_from(["server", "--config", path].iter().chain(args))
+    }
+
+    #[test]
+    fn files_merge_over_defaults() {
+        let cases: [(&str, Check); 10] = [
+            ("", |c| {
+                c.rules() == Rules::default() && c.recording().is_none()
+            }),
+            ("tps = 64", |c| c.rules().tps == 64),
+            ("max_players = 4", |c| c.rules().max_players == 4),
+            ("seed = 7", |c| c.seed() == Some(7)),
+            ("spawn_policy = \"farthest\"", |c| {
+                c.spawn_policy() == SpawnPolicy::Farthest
+            }),
+            ("[movement]\nmode = \"walking\"\ngravity = 20.0", |c| {
+                c.rules().movement
+                    == Movement {
+                        gravity: 20.0,
+                        ..Movement::walking()
+                    }
+            }),
+            ("[limits]\npackets_per_sec = 0\nmax_rewind = 1.0", |c| {
+                (c.rules().packets_per_sec, c.rules().max_rewind) == (1, Duration::from_secs(1))
+            }),
+            ("[timeouts]\nidle = 2.5", |c| {
+                c.rules().idle_timeout == Duration::from_millis(2500)
+                    && c.rules().handshake_timeout == Rules::default().handshake_timeout
+            }),
+            ("[record]\ndir = \"replays\"\nmax_files = 3", |c| {
+                c.recording().is_some_and(|r| {
+                    r.dir == Path::new("replays")
+                        && r.max_files == 3
+                        && r.max_file_size == DEFAULT_FILE_SIZE as u64 * (1 << 20)
+                })
+            }),
+            ("admin_addr = \"127.0.0.1:1\"\nadmin_secret = \"s\"", |c| {
+                c.admin_addr() == "127.0.0.1:1".parse().unwrap() && c.admin_secret() == Some("s")
+            }),
+        ];
+
+        for (toml, is_expected) in cases {
+            let cfg = config(&TempFile::new(toml), &[]).unwrap();
+            assert!(is_expected(&cfg), "{toml:?}: {cfg:?}");
+        }
+    }
+
+    #[test]
+    fn invalid_files_are_refused() {
+        let cases = [
+            ("tps = ", "Invalid config"),
+            ("unknown = 1", "unknown field"),
+            ("[movement]\nwings = 2", "unknown field"),
+            ("tps = 0", "TPS must be greater than zero"),
+            ("max_players = 0", "Max players must be greater than zero"),
+            ("spawn_policy = \"nearest\"", "Unknown spawn policy"),
+            ("admin_addr = \"10.0.0.1:1\"", "loopback"),
+            (
+                "[timeouts]\nidle = 0.0",
+                "Timeouts must be greater than zero",
+            ),
+            ("[limits]\nmax_rewind = 9.0", "Max rewind must be within"),
+            (
+                "[record]\ndir = \"r\"\nmax_files = 0",
+                "At least one replay file",
+            ),
+        ];
+
+        for (toml, error) in cases {
+            let e = config(&TempFile::new(toml), &[]).unwrap_err().to_string();
+            assert!(e.contains(error), "{toml:?}: {e}");
+        }
+        let e = Config::parse_from(["server", "--config", "/nonexistent.toml"]).unwrap_err();
+        assert!(e.to_string().contains("Couldn't read config"), "{e}");
+    }
+
+    #[test]
+    fn options_take_precedence_over_files() {
+        let cases: [(&str, &[&str], Check); 8] = [
+            ("tps = 64", &["--tps", "32"], |c| c.rules().tps == 32),
+            ("max_players = 4", &["--max-players", "8"], |c| {
+                c.rules().max_players == 8
+            }),
+            ("seed = 7", &["--seed", "9"], |c| c.seed() == Some(9)),
+            ("tcp_addr = \"127.0.0.1:1\"", &["-t", "127.0.0.1:2"], |c| {
+                c.tcp_addr().port() == 2
+            }),
+            (
+                "spawn_policy = \"farthest\"",
+                &["--spawn-policy", "round-robin"],
+                |c| c.spawn_policy() == SpawnPolicy::RoundRobin,
+            ),
+            // the option's mode, still adjusted by the file's parameters
+            (
+                "[movement]\nmode = \"walking\"\nspeed = 3.0",
+                &["--movement", "free-fly"],
+                |c| {
+                    c.rules().movement.mode == Movement::free_fly().mode
+                        && c.rules().movement.speed == 3.0
+                },
+            ),
+            (
+                "[limits]\nmax_rewind = 1.0",
+                &["--max-rewind", "0.25"],
+                |c| c.rules().max_rewind == Duration::from_millis(250),
+            ),
+            (
+                "[record]\ndir = \"a\"\nmax_files = 3",
+                &["--record", "b"],
+                |c| {
+                    c.recording()
+                        .is_some_and(|r| r.dir == Path::new("b") && r.max_files == 3)
+                },
+            ),
+        ];
+
+        for (toml, args, is_expected) in cases {
+            let cfg = config(&TempFile::new(toml), args).unwrap();
+            assert!(is_expected(&cfg), "{toml:?} {args:?}: {cfg:?}");
+        }
+    }
+
+    #[test]
+    fn reloading_only_changes_the_rules() {
+        // (rules take effect at once, the rest is only reported)
+        let cases: [(&str, &[&str]); 8] = [
+            ("tps = 64", &[]),
+            ("[movement]\nmode = \"walking\"", &[]),
+            ("[timeouts]\nidle = 3.0", &[]),
+            ("seed = 7", &["seed"]),
+            ("map = \"other.ron\"", &["map"]),
+            ("udp_addr = \"127.0.0.1:1\"", &["udp_addr"]),
+            ("admin_secret = \"s\"", &["admin_secret"]),
+            ("[record]\ndir = \"r\"", &["record"]),
+        ];
+
+        for (toml, fixed) in cases {
+            let file = TempFile::new("");
+            let cfg = config(&file, &["--max-players", "8"]).unwrap();
+            file.write(toml);
+            let new = cfg.reload().unwrap();
+
+            assert_eq!(cfg.fixed_changes(&new), fixed, "{toml:?}");
+            assert_eq!(new.rules() != cfg.rules(), fixed.is_empty(), "{toml:?}");
+
+            // the options still take precedence
+            assert_eq!(new.rules().max_players, 8);
+        }
     }
 }
@@ -0,0 +1,21 @@
+use crate::*;
+use std::io::{BufRead, stdin};
+
+/// Read commands from stdin (one per line), printing their output.
+pub fn init_console(admin: Admin) -> JoinHandle<Result> {
+    // not a thread of the server (stdin may be closed from the start)
+    spawn(move || {
+        for line in stdin().lock().lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match admin.run(&line) {
+                Ok(out) => println!("{out}"),
+                Err(e) => println!("error: {e}"),
+            }
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,86 @@
+use crate::*;
+use std::{
+    io::{BufRead, BufReader, ErrorKind},
+    net::{TcpListener, TcpStream},
+    thread::sleep,
+    time::Duration,
+};
+
+/// how long a connection may take to authenticate
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// how often the listener looks for connections (and whether the game is stopping)
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Compare secrets without leaking (through timing) how much of them matched.
+fn is_secret(given: &str, secret: &str) -> bool {
+    given.len() == secret.len()
+        && given
+            .bytes()
+            .zip(secret.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Serve a single admin connection until it closes.
+fn handle_control(stream: TcpStream, secret: &str, admin: &Admin) -> Result {
+    let mut writer = stream.try_clone()?;
+    let mut lines = BufReader::new(stream.try_clone()?).lines();
+
+    // the first line must be the secret
+    stream.set_read_timeout(Some(AUTH_TIMEOUT))?;
+    let auth = lines.next().transpose()?.unwrap_or_default();
+    if !auth
+        .strip_prefix("auth ")
+        .is_some_and(|s| is_secret(s, secret))
+    {
+        write_reply(&mut writer, &Err("invalid secret".to_string()))?;
+        return Err("Invalid admin secret.".into());
+    }
+    write_reply(&mut writer, &Ok(String::new()))?;
+    stream.set_read_timeout(None)?;
+
+    for line in lines {
+        let reply = admin.run(&line?).map_err(|e| e.to_string());
+        write_reply(&mut writer, &reply)?;
+    }
+    Ok(())
+}
+
+/// Accept admin connections on a (loopback) socket, authenticated with a shared secret,
+/// until the game stops.
+pub fn init_control(
+    listener: TcpListener,
+    secret: String,
+    admin: Admin,
+    game: Arc<Game>,
+) -> Result<JoinHandle<Result>> {
+    let secret = Arc::new(secret);
+
+    // so the game stopping is noticed between connections
+    listener.set_nonblocking(true)?;
+
+    // not a thread of the server (like the console)
+    Ok(spawn(move || {
+        while !game.is_stopping() {
+            let stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    sleep(ACCEPT_INTERVAL);
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            stream.set_nonblocking(false)?;
+            let addr = stream.peer_addr()?;
+            let (secret, admin) = (secret.clone(), admin.clone());
+
+            spawn(move || {
+                if let Err(e) = handle_control(stream, &secret, &admin) {
+                    warn!("[Admin] {addr}: {e}");
+                }
+            });
+        }
+        Ok(())
+    }))
+}
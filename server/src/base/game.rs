@@ -0,0 +1,222 @@
+use crate::*;
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+use ultraviolet::Vec3;
+
+/// Settings of the running game (these can be reloaded without dropping anyone).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rules {
+    pub tps: u16,
+    pub max_players: usize,
+    pub movement: Movement,
+    pub packets_per_sec: u32,        // maximum input packets per player
+    pub handshake_timeout: Duration, // how long joining players may take
+    pub idle_timeout: Duration,      // how long players may stay silent
+    pub max_rewind: Duration,        // how far back interactions are judged from (see `Lag`)
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            tps: 128,
+            max_players: 64,
+            movement: Default::default(),
+            packets_per_sec: 2048,
+            handshake_timeout: Duration::from_secs(5),
+            idle_timeout: Duration::from_secs(10),
+            max_rewind: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Rules and state of the game (shared by every server thread).
+#[derive(Debug)]
+pub struct Game {
+    world: World,
+    collider: Arc<Collider>,
+    rules: RwLock<Rules>,
+    spawner: Mutex<Spawner>,
+    kill_depth: f32, // of the spawner (players below it have fallen out of the world)
+    ids: Mutex<IdPool>,
+    history: RwLock<History>,
+    rtts: RwLock<HashMap<Id, ClockSync>>, // of every player (timed by their clock syncs)
+    banned: RwLock<HashSet<IpAddr>>,
+    is_closed: AtomicBool,   // no longer admitting players
+    is_stopping: AtomicBool, // every thread is to finish
+}
+
+impl Game {
+    /// how long the identity of a leaving player stays retired
+    const ID_GRACE: Duration = Duration::from_secs(60);
+
+    pub fn new(world: World, policy: SpawnPolicy, rules: Rules) -> Self {
+        let collider = Arc::new(world.collider());
+        let spawner = Spawner::new(&world, collider.clone(), policy);
+        let kill_depth = spawner.kill_depth();
+
+        Self {
+            world,
+            collider,
+            rules: RwLock::new(rules),
+            spawner: Mutex::new(spawner),
+            kill_depth,
+            ids: Mutex::new(IdPool::new(PLAYER_IDS, Self::ID_GRACE)),
+            history: Default::default(),
+            rtts: Default::default(),
+            banned: Default::default(),
+            is_closed: Default::default(),
+            is_stopping: Default::default(),
+        }
+    }
+
+    /// Static geometry (sent to every joining player).
+    pub const fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// What players collide with.
+    pub fn collider(&self) -> &Collider {
+        &self.collider
+    }
+
+    pub fn rules(&self) -> Rules {
+        *self.rules.read()
+    }
+
+    pub fn set_rules(&self, rules: Rules) {
+        *self.rules.write() = rules
+    }
+
+    pub fn movement(&self) -> Movement {
+        self.rules.read().movement
+    }
+
+    /// Server ticks/sec.
+    pub fn tps(&self) -> u16 {
+        self.rules.read().tps
+    }
+
+    pub fn set_tps(&self, tps: u16) {
+        self.rules.write().tps = tps.clamp(1, MAX_TPS)
+    }
+
+    /// Duration of a single server tick.
+    pub fn tick(&self) -> Duration {
+        Duration::from_secs_f32(1.0 / self.tps() as f32)
+    }
+
+    /// Refuse every (future) player from the address.
+    pub fn ban(&self, ip: IpAddr) -> bool {
+        self.banned.write().insert(ip)
+    }
+
+    pub fn bans(&self) -> usize {
+        self.banned.read().len()
+    }
+
+    /// Refuse every (future) player (e.g., shutting down).
+    pub fn close(&self) {
+        self.is_closed.store(true, Ordering::Release)
+    }
+
+    /// Close and let every thread finish.
+    pub fn stop(&self) {
+        self.close();
+        self.is_stopping.store(true, Ordering::Release)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.is_closed.load(Ordering::Acquire)
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        self.is_stopping.load(Ordering::Acquire)
+    }
+
+    /// Admit a player from the address, given how many are already playing.
+    pub fn join(&self, ip: IpAddr, players: usize) -> Result<Id, RejectReason> {
+        if self.is_closed() {
+            return Err(RejectReason::ShuttingDown);
+        }
+        if self.banned.read().contains(&ip) {
+            return Err(RejectReason::Banned);
+        }
+        if players >= self.rules.read().max_players {
+            return Err(RejectReason::ServerFull);
+        }
+        self.ids.lock().alloc().ok_or(RejectReason::ServerFull)
+    }
+
+    /// The player has left (their identity is retired for a while).
+    pub fn leave(&self, id: Id) {
+        self.rtts.write().remove(&id);
+        self.ids.lock().release(id)
+    }
+
+    /// The player never made it in (their identity is immediately reusable).
+    pub fn abort_join(&self, id: Id) {
+        self.ids.lock().cancel(id)
+    }
+
+    /// Choose where a player (re)spawns, given the positions of every other player.
+    pub fn spawn(&self, others: &[Vec3]) -> Vec3 {
+        self.spawner.lock().pick(others)
+    }
+
+    /// Remember where every player is as of the tick (to rewind to later).
+    pub fn remember<'a>(&self, tick: u64, players: impl IntoIterator<Item = &'a UptObj>) {
+        let max_rewind = self.rules.read().max_rewind;
+        self.history
+            .write()
+            .push(tick, Instant::now(), players, max_rewind);
+    }
+
+    /// A round trip to the player has been timed (see [`ClockSample::echoed`]).
+    pub fn measure(&self, id: Id, sample: ClockSample) {
+        self.rtts.write().entry(id).or_default().add(sample)
+    }
+
+    /// Filtered round-trip time to the player (once timed).
+    pub fn rtt(&self, id: Id) -> Option<Duration> {
+        self.rtts.read().get(&id).map(ClockSync::rtt)
+    }
+
+    /// How far behind the server the player sees everyone else.
+    ///
+    /// Clients extrapolate remote players from their latest update (by as long as it's been since
+    /// it arrived) rather than showing them behind it, so they see the game as it was a round trip
+    /// ago and nothing is added for interpolation.
+    pub fn lag(&self, id: Id) -> Lag {
+        Lag {
+            rtt: self.rtt(id).unwrap_or_default(),
+            interp: Duration::ZERO,
+        }
+    }
+
+    /// The players as the player sees them now (e.g., to judge their shot).
+    pub fn rewind_for(&self, id: Id) -> Rewind<'_> {
+        self.rewind(self.lag(id))
+    }
+
+    /// The players as someone lagging behind sees them now (e.g., to judge their shot).
+    pub fn rewind(&self, lag: Lag) -> Rewind<'_> {
+        self.rewind_to(lag.view_time(Instant::now()))
+    }
+
+    /// The players as they were at the time (but no further back than the rules allow).
+    pub fn rewind_to(&self, time: Instant) -> Rewind<'_> {
+        let max_rewind = self.rules.read().max_rewind;
+        self.history
+            .read()
+            .rewind(time, Instant::now(), max_rewind, &self.collider)
+    }
+
+    /// The player has fallen out of the world.
+    pub fn is_out_of_bounds(&self, pos: Vec3) -> bool {
+        pos.y < self.kill_depth
+    }
+}
@@ -0,0 +1,181 @@
+use crate::*;
+use std::{
+    net::{SocketAddr, TcpListener},
+    thread::{Thread, current},
+};
+
+/// Unparks a thread once dropped, however the owning thread ends (panics included).
+struct Unpark(Thread);
+
+impl Drop for Unpark {
+    fn drop(&mut self) {
+        self.0.unpark();
+    }
+}
+
+/// A running server (e.g., within a test), stopped by [`ServerHandle::shutdown`].
+pub struct ServerHandle {
+    tcp_addr: SocketAddr, // as bound (e.g., an ephemeral port)
+    udp_addr: SocketAddr,
+    clients_udp: UdpClients,
+    game: Arc<Game>,
+    remote: Remote,
+    admin: Admin,
+    worker: JoinHandle<Result>,
+    control: Option<JoinHandle<Result>>, // accepting admin connections (if given a secret)
+}
+
+impl ServerHandle {
+    /// Load the world and start serving in the background, unparking the calling thread
+    /// once the server should stop (a shutdown has finished counting down or the event loop has ended).
+    pub fn start(cfg: Config) -> Result<Self> {
+        Self::start_with::<Sockets>(cfg, |tcp_addr, udp_addr| {
+            let listener = mio::net::TcpListener::bind(tcp_addr)?;
+            Ok((listener, MioUdp::bind(udp_addr)?))
+        })
+    }
+
+    /// Start serving within an in-memory network (at the configured addresses), as [`ServerHandle::start`] does.
+    pub fn start_in(cfg: Config, net: &MemNet) -> Result<Self> {
+        Self::start_with::<Memory>(cfg, |tcp_addr, udp_addr| {
+            let listener = MemListener::bind(net, tcp_addr)?;
+            Ok((listener, MemUdp::bind(net, udp_addr)?))
+        })
+    }
+
+    /// Start serving over whatever sockets are bound to the configured addresses.
+    fn start_with<T: Transport>(
+        cfg: Config,
+        bind: impl FnOnce(SocketAddr, SocketAddr) -> Result<(T::Listener, T::Udp)>,
+    ) -> Result<Self> {
+        let main = current();
+
+        // map of player streams (by identity)
+        let clients_tcp: TcpClients = Default::default();
+
+        // map of player data
+        let clients_udp: UdpClients = Default::default();
+
+        // static world geometry (sent to every joining player)
+        let seed = cfg.seed().unwrap_or_else(random_seed);
+        let world = match cfg.map() {
+            Some(path) => World::load(path, seed)?,
+            None => World::demo(seed),
+        };
+        info!("[World] Loaded {} objects (seed: {seed})", world.len());
+
+        // rules and static state of the game
+        let game = Arc::new(Game::new(world, cfg.spawn_policy(), cfg.rules()));
+
+        // every tick of the game (if asked to)
+        let recorder = cfg
+            .recording()
+            .map(|recording| Recorder::start(recording.clone(), &game))
+            .transpose()?;
+
+        // init TCP and UDP servers
+        let (listener, udp) = bind(cfg.tcp_addr(), cfg.udp_addr())?;
+        let server = Server::<T>::new(
+            listener,
+            udp,
+            clients_tcp.clone(),
+            clients_udp.clone(),
+            game.clone(),
+            recorder,
+        )?;
+        let (tcp_addr, udp_addr) = server.local_addrs()?;
+        let remote = server.remote();
+
+        // administrative commands
+        let control = cfg
+            .admin_secret()
+            .map(|secret| (cfg.admin_addr(), secret.to_string()));
+        let admin = Admin::new(
+            cfg,
+            clients_tcp,
+            clients_udp.clone(),
+            game.clone(),
+            remote.clone(),
+            main.clone(),
+        );
+
+        // from scripts (only with a secret)
+        let control = control
+            .map(|(addr, secret)| {
+                let listener = TcpListener::bind(addr)?;
+                info!("[Admin] Binded @ {addr:?}");
+                init_control(listener, secret, admin.clone(), game.clone())
+            })
+            .transpose()?;
+
+        // every socket, the game and its updates
+        let worker = spawn(move || {
+            let _unpark = Unpark(main);
+            server.run()
+        });
+
+        Ok(Self {
+            tcp_addr,
+            udp_addr,
+            clients_udp,
+            game,
+            remote,
+            admin,
+            worker,
+            control,
+        })
+    }
+
+    pub const fn tcp_addr(&self) -> SocketAddr {
+        self.tcp_addr
+    }
+
+    pub const fn udp_addr(&self) -> SocketAddr {
+        self.udp_addr
+    }
+
+    pub const fn admin(&self) -> &Admin {
+        &self.admin
+    }
+
+    pub const fn game(&self) -> &Arc<Game> {
+        &self.game
+    }
+
+    /// Every player, as the server sees them.
+    pub fn players(&self) -> Vec<UptObj> {
+        self.clients_udp.read().values().copied().collect()
+    }
+
+    /// Whether the event loop has ended (e.g., it has failed).
+    pub fn is_finished(&self) -> bool {
+        self.worker.is_finished()
+    }
+
+    /// Stop the event loop (telling players why), returning once it has.
+    pub fn shutdown(self) -> Result {
+        self.game.stop();
+        if let Err(e) = self.remote.wake() {
+            error!("[Server] {e}");
+        }
+
+        let result = self
+            .worker
+            .join()
+            .map_err(|panic| format!("The event loop panicked: {}", BlazedError::from(panic)))?;
+
+        // (which notices the game has stopped within an interval)
+        if let Some(control) = self.control {
+            match control.join() {
+                Ok(Ok(())) => (),
+                Ok(Err(e)) => error!("[Admin] {e}"),
+                Err(panic) => error!(
+                    "[Admin] The listener panicked: {}",
+                    BlazedError::from(panic)
+                ),
+            }
+        }
+        info!("[Server] Stopped");
+        result
+    }
+}
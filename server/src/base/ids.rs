@@ -0,0 +1,123 @@
+use crate::*;
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+/// Allocates identities from a fixed range, recycling released ones once they've gone stale.
+#[derive(Debug)]
+pub struct IdPool {
+    range: Range<Id>,
+    next: Id,                          // lowest never allocated identity
+    released: VecDeque<(Id, Instant)>, // when each becomes reusable (earliest first)
+    grace: Duration,
+}
+
+impl IdPool {
+    pub const fn new(range: Range<Id>, grace: Duration) -> Self {
+        Self {
+            next: range.start,
+            range,
+            released: VecDeque::new(),
+            grace,
+        }
+    }
+
+    /// Allocate an identity, preferring fresh ones (`None` if exhausted).
+    pub fn alloc(&mut self) -> Option<Id> {
+        if self.next < self.range.end {
+            self.next += 1;
+            return Some(self.next - 1);
+        }
+
+        // late packets of a recently released identity must not reach its new owner
+        let &(id, ready) = self.released.front()?;
+        if Instant::now() < ready {
+            return None;
+        }
+        self.released.pop_front();
+        Some(id)
+    }
+
+    /// Return an identity that has been in use.
+    pub fn release(&mut self, id: Id) {
+        debug_assert!(self.range.contains(&id));
+        self.released.push_back((id, Instant::now() + self.grace));
+    }
+
+    /// Return an identity that was never seen by anyone (reusable immediately).
+    pub fn cancel(&mut self, id: Id) {
+        debug_assert!(self.range.contains(&id));
+        self.released.push_front((id, Instant::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// long enough to never pass within a test
+    const FOREVER: Duration = Duration::from_secs(3600);
+
+    /// A pool of the first few player identities, every one of them allocated.
+    fn exhausted(count: Id, grace: Duration) -> (IdPool, Vec<Id>) {
+        let mut pool = IdPool::new(PLAYER_IDS.start..PLAYER_IDS.start + count, grace);
+        let ids = (0..count).map_while(|_| pool.alloc()).collect::<Vec<_>>();
+        assert_eq!(ids.len(), count as usize);
+        assert_eq!(pool.alloc(), None);
+        (pool, ids)
+    }
+
+    #[test]
+    fn released_ids_are_recycled_after_the_grace_period() {
+        let (mut pool, ids) = exhausted(2, FOREVER);
+        pool.release(ids[0]);
+        assert_eq!(pool.alloc(), None);
+
+        let (mut pool, ids) = exhausted(2, Duration::ZERO);
+        pool.release(ids[1]);
+        pool.release(ids[0]);
+        assert_eq!(pool.alloc(), Some(ids[1]));
+        assert_eq!(pool.alloc(), Some(ids[0]));
+        assert_eq!(pool.alloc(), None);
+    }
+
+    #[test]
+    fn fresh_ids_come_before_recycled_ones() {
+        let mut pool = IdPool::new(PLAYER_IDS.start..PLAYER_IDS.start + 3, Duration::ZERO);
+        let first = pool.alloc().unwrap();
+        pool.release(first);
+
+        assert_eq!(pool.alloc(), Some(first + 1));
+        assert_eq!(pool.alloc(), Some(first + 2));
+        assert_eq!(pool.alloc(), Some(first));
+    }
+
+    #[test]
+    fn aborted_joins_return_their_id() {
+        // as `Game::abort_join` does, ahead of any released identity
+        let (mut pool, ids) = exhausted(3, FOREVER);
+        pool.release(ids[0]);
+        pool.cancel(ids[2]);
+        pool.cancel(ids[1]);
+        assert_eq!(pool.alloc(), Some(ids[1]));
+        assert_eq!(pool.alloc(), Some(ids[2]));
+        assert_eq!(pool.alloc(), None);
+    }
+
+    #[test]
+    fn player_ids_stay_clear_of_the_world() {
+        let mut pool = IdPool::new(PLAYER_IDS, Duration::ZERO);
+        let ids = (0..=PLAYER_IDS.len())
+            .map_while(|_| pool.alloc())
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), PLAYER_IDS.len());
+        assert!(ids.iter().all(|id| PLAYER_IDS.contains(id)));
+        assert!(!ids.iter().any(|id| WORLD_IDS.contains(id)));
+
+        // recycled ones included
+        pool.release(ids[0]);
+        assert!(pool.alloc().is_some_and(|id| PLAYER_IDS.contains(&id)));
+    }
+}
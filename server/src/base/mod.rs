@@ -1,9 +1,25 @@
+mod admin;
 mod cfg;
+mod console;
+mod control;
 mod err;
+mod game;
+mod handle;
+mod ids;
 mod net;
+mod record;
+mod rewind;
 
+pub use admin::*;
 pub use cfg::*;
+pub use console::*;
+pub use control::*;
 pub use err::*;
+pub use game::*;
+pub use handle::*;
+pub use ids::*;
 pub use net::*;
+pub use record::*;
+pub use rewind::*;
 
 pub use blazed_demo::*;
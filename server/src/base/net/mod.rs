@@ -1,5 +1,9 @@
+mod peer;
+mod server;
 mod tcp;
+mod transport;
 mod udp;
 
-pub use tcp::*;
-pub use udp::*;
+pub use peer::*;
+pub use server::*;
+pub use transport::*;
@@ -0,0 +1,160 @@
+use crate::*;
+use std::{collections::VecDeque, io::ErrorKind, net::SocketAddr, time::Instant};
+
+/// most packets queued for a player before it's considered stalled
+pub const OUTBOX_SIZE: usize = 1024;
+
+/// How far along a stream is.
+#[derive(Clone, Debug)]
+pub enum Stage {
+    Hello, // awaiting the client's handshake
+    Udp {
+        id: Id,
+        hello: ClientHandshake,
+        packet: Packet, // expected (verbatim) over UDP as well
+    },
+    Playing {
+        id: Id,
+        addr_udp: SocketAddr,
+    },
+    Closing, // flushing the reason for disconnecting
+}
+
+/// A (non-blocking) stream with its own queue of outbound frames.
+#[derive(Debug)]
+pub struct Peer<S> {
+    stream: S,
+    addr: SocketAddr,
+    pub stage: Stage,
+    pub deadline: Instant, // of the current stage (or of silence)
+    inbox: Vec<u8>,        // received bytes not yet framed
+    outbox: VecDeque<Packet>,
+    written: usize,  // of the first frame
+    reserved: usize, // leading frames exempt from the limit (e.g., the world)
+    is_shut: bool,   // nothing more is written
+}
+
+impl<S: TcpConn> Peer<S> {
+    pub const fn new(stream: S, addr: SocketAddr, deadline: Instant) -> Self {
+        Self {
+            stream,
+            addr,
+            stage: Stage::Hello,
+            deadline,
+            inbox: Vec::new(),
+            outbox: VecDeque::new(),
+            written: 0,
+            reserved: 0,
+            is_shut: false,
+        }
+    }
+
+    pub const fn stream(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    pub const fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The player's identity (once admitted).
+    pub const fn id(&self) -> Option<Id> {
+        match self.stage {
+            Stage::Udp { id, .. } | Stage::Playing { id, .. } => Some(id),
+            Stage::Hello | Stage::Closing => None,
+        }
+    }
+
+    /// Read whatever has arrived, returning every complete packet (`None` once closed).
+    pub fn recv(&mut self) -> std::io::Result<Option<Vec<Packet>>> {
+        let mut buf = [0; 4096];
+        let mut is_closed = false;
+
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    is_closed = true;
+                    break;
+                }
+                Ok(n) => self.inbox.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut packets = Vec::new();
+        let mut start = 0;
+        while let Some(prefix) = self.inbox.get(start..start + size_of::<u16>()) {
+            let len = u16::from_le_bytes([prefix[0], prefix[1]]) as usize;
+            if len > PACKET_SIZE {
+                return Err(ErrorKind::InvalidData.into());
+            }
+
+            let end = start + size_of::<u16>() + len;
+            let Some(packet) = self.inbox.get(start + size_of::<u16>()..end) else {
+                break;
+            };
+            if !packet.is_empty() {
+                packets.push(packet.to_vec());
+            }
+            start = end;
+        }
+        self.inbox.drain(..start);
+
+        Ok((!is_closed).then_some(packets))
+    }
+
+    /// Queue a frame, unless the player is too far behind (`false`).
+    pub fn send(&mut self, frame: Packet) -> bool {
+        if self.outbox.len() - self.reserved >= OUTBOX_SIZE {
+            return false;
+        }
+        self.outbox.push_back(frame);
+        true
+    }
+
+    /// Queue a frame of the handshake (exempt from the limit).
+    pub fn push(&mut self, frame: Packet) {
+        debug_assert_eq!(self.outbox.len(), self.reserved);
+        self.outbox.push_back(frame);
+        self.reserved += 1;
+    }
+
+    /// Queue the last frame (regardless of the limit), closing the stream once written.
+    pub fn close(&mut self, frame: Packet) {
+        self.stage = Stage::Closing;
+        self.outbox.push_back(frame);
+    }
+
+    pub const fn is_closing(&self) -> bool {
+        matches!(self.stage, Stage::Closing)
+    }
+
+    /// Write as much as possible, returning whether everything has been written.
+    pub fn flush(&mut self) -> std::io::Result<bool> {
+        while let Some(frame) = self.outbox.front() {
+            match self.stream.write(&frame[self.written..]) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.written += n;
+                    if self.written == frame.len() {
+                        self.outbox.pop_front();
+                        self.written = 0;
+                        self.reserved = self.reserved.saturating_sub(1);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        // the reason has been written
+        if self.is_closing() && !self.is_shut {
+            self.is_shut = true;
+            _ = self.stream.shutdown(std::net::Shutdown::Write);
+        }
+        Ok(true)
+    }
+}
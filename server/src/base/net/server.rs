@@ -0,0 +1,299 @@
+use crate::*;
+use crossbeam_channel::{Receiver, Sender, unbounded};
+use mio::{Events, Poll, Token, Waker};
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+use ultraviolet::Vec3;
+
+pub(super) const LISTENER: Token = Token(0);
+pub(super) const UDP: Token = Token(1);
+const WAKER: Token = Token(2);
+
+/// first token of player streams
+pub(super) const PEERS: usize = 3;
+
+/// how long players get to be sent everything on shutdown
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Work handed to the event loop by other threads.
+#[derive(Debug)]
+enum Task {
+    Broadcast(Packet),
+    Close(Id, RejectReason),
+    Teleport(Id, Vec3),
+}
+
+/// Hands work to the event loop (from any thread).
+#[derive(Clone, Debug)]
+pub struct Remote {
+    sender: Sender<Task>,
+    waker: Arc<Waker>,
+}
+
+impl Remote {
+    /// Send a packet to every player (over TCP).
+    pub fn broadcast(&self, packet: Packet) -> Result {
+        self.sender.send(Task::Broadcast(packet))?;
+        self.wake()
+    }
+
+    /// Tell a player why, then disconnect them.
+    pub fn close(&self, id: Id, reason: RejectReason) -> Result {
+        self.sender.send(Task::Close(id, reason))?;
+        self.wake()
+    }
+
+    /// Move a player (as if they had respawned there).
+    pub fn teleport(&self, id: Id, pos: Vec3) -> Result {
+        self.sender.send(Task::Teleport(id, pos))?;
+        self.wake()
+    }
+
+    /// Have the event loop look at the game again (e.g., players were moved or it's stopping).
+    pub fn wake(&self) -> Result {
+        self.waker.wake().map_err(Into::into)
+    }
+}
+
+/// Every socket of the server, multiplexed by a single (readiness-based) event loop
+/// that also simulates the game and distributes its updates.
+pub struct Server<T: Transport = Sockets> {
+    pub(super) poll: Poll,
+    pub(super) listener: Option<T::Listener>, // gone once stopping
+    pub(super) udp: T::Udp,
+    pub(super) remote: Remote,
+    pub(super) ready: Ready,
+    receiver: Receiver<Task>,
+
+    pub(super) peers: HashMap<Token, Peer<T::Stream>>,
+    pub(super) tokens: HashMap<Id, Token>, // of every player
+    pub(super) next_token: usize,
+
+    pub(super) clients_tcp: TcpClients,
+    pub(super) clients_udp: UdpClients,
+    pub(super) updates: HashMap<SocketAddr, UptObjOpt>,
+    pub(super) game: Arc<Game>,
+
+    // input packets received from each player within the current second
+    pub(super) window: Instant,
+    pub(super) counts: HashMap<SocketAddr, u32>,
+
+    pub(super) next_step: Option<Instant>, // while anyone is moving
+    pub(super) tick: u64,                  // simulation steps so far
+    pub(super) hash: u64,                  // of the game as of the current tick (see `tick_hash`)
+    pub(super) latest_inputs: HashMap<SocketAddr, (u64, u32)>, // tick and number of each player's latest input (0 for a respawn)
+    pub(super) is_modified: bool, // updates are waiting to be distributed
+    pub(super) recorder: Option<Recorder>,
+    last_dist: Instant,
+    stop_deadline: Option<Instant>,
+}
+
+impl<T: Transport> Server<T> {
+    /// Serve over bound sockets (recording the game, if given a recorder).
+    pub fn new(
+        mut listener: T::Listener,
+        mut udp: T::Udp,
+        clients_tcp: TcpClients,
+        clients_udp: UdpClients,
+        game: Arc<Game>,
+        recorder: Option<Recorder>,
+    ) -> Result<Self> {
+        let poll = Poll::new()?;
+        let (sender, receiver) = unbounded();
+        let waker = Arc::new(Waker::new(poll.registry(), WAKER)?);
+        let ready = Ready::new(waker.clone());
+
+        listener.register(poll.registry(), LISTENER, &ready)?;
+        info!("[TCP] Binded @ {:?}", listener.local_addr()?);
+
+        udp.register(poll.registry(), UDP, &ready)?;
+        info!("[UDP] Binded @ {:?}", udp.local_addr()?);
+
+        Ok(Self {
+            poll,
+            listener: Some(listener),
+            udp,
+            remote: Remote { sender, waker },
+            ready,
+            receiver,
+            peers: HashMap::new(),
+            tokens: HashMap::new(),
+            next_token: PEERS,
+            clients_tcp,
+            clients_udp,
+            updates: HashMap::new(),
+            game,
+            window: Instant::now(),
+            counts: HashMap::new(),
+            next_step: None,
+            tick: 0,
+            hash: tick_hash([]),
+            latest_inputs: HashMap::new(),
+            is_modified: false,
+            recorder,
+            last_dist: Instant::now(),
+            stop_deadline: None,
+        })
+    }
+
+    pub fn remote(&self) -> Remote {
+        self.remote.clone()
+    }
+
+    /// The bound TCP and UDP addresses (e.g., once given ephemeral ports).
+    pub fn local_addrs(&self) -> Result<(SocketAddr, SocketAddr)> {
+        let listener = self.listener.as_ref().ok_or("Server has stopped")?;
+        Ok((listener.local_addr()?, self.udp.local_addr()?))
+    }
+
+    /// Serve until the game stops (and everyone has been told).
+    pub fn run(mut self) -> Result {
+        let mut events = Events::with_capacity(1024);
+
+        loop {
+            let timeout = self
+                .next_deadline()
+                .map(|at| at.saturating_duration_since(Instant::now()));
+            match self.poll.poll(&mut events, timeout) {
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                result => result?,
+            }
+
+            for event in &events {
+                if event.token() == WAKER {
+                    self.wake()?;
+                    for token in self.ready.take() {
+                        self.on_ready(token, true, false)?;
+                    }
+                    continue;
+                }
+
+                let is_readable = event.is_readable() || event.is_read_closed() || event.is_error();
+                self.on_ready(event.token(), is_readable, event.is_writable())?;
+            }
+
+            // the game and its (timed) consequences
+            let now = Instant::now();
+            while self.next_step.is_some_and(|at| at <= now) {
+                self.step()?;
+            }
+            if self.is_modified && self.last_dist + self.game.tick() <= now {
+                self.last_dist = now;
+                self.distribute();
+            }
+            self.expire(now)?;
+            self.flush_all()?;
+
+            if let Some(deadline) = self.stop_deadline
+                && (self.peers.is_empty() || deadline <= now)
+            {
+                // whatever has happened since the last tick (e.g., everyone leaving)
+                if self.next_step.is_some() {
+                    self.step()?;
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    /// Handle a socket that can be read from or written to.
+    fn on_ready(&mut self, token: Token, is_readable: bool, is_writable: bool) -> Result {
+        match token {
+            LISTENER => self.accept(),
+            UDP => self.recv_udp(),
+            token => {
+                if is_readable {
+                    self.recv_tcp(token)?;
+                }
+                if is_writable {
+                    self.flush(token)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Record something that has happened since the last tick (if recording), which is part of the next one.
+    pub(super) fn record(&mut self, event: ReplayEvent) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(event);
+        }
+
+        // so it's hashed and remembered (even if nobody moves)
+        self.resume();
+    }
+
+    /// Hash and remember the game as of the tick that has just been simulated, recording it (if recording).
+    pub(super) fn end_tick(&mut self) {
+        let clients_udp = self.clients_udp.clone();
+        let players = clients_udp.read();
+        self.hash = tick_hash(players.values());
+        self.game.remember(self.tick, players.values());
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.end_tick(self.tick, self.hash, players.values());
+        }
+    }
+
+    /// The earliest of every timer.
+    fn next_deadline(&self) -> Option<Instant> {
+        let dist = self.is_modified.then(|| self.last_dist + self.game.tick());
+        let peers = self.peers.values().map(|peer| peer.deadline).min();
+
+        [self.next_step, dist, peers, self.stop_deadline]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Handle work from other threads.
+    fn wake(&mut self) -> Result {
+        while let Ok(task) = self.receiver.try_recv() {
+            match task {
+                Task::Broadcast(packet) => self.broadcast(&packet)?,
+                Task::Close(id, reason) => {
+                    if let Some(&token) = self.tokens.get(&id) {
+                        self.close(token, reason)?;
+                    }
+                }
+                Task::Teleport(id, pos) => {
+                    let addr = self
+                        .clients_udp
+                        .read()
+                        .iter()
+                        .find_map(|(&addr, c)| (c.id == id).then_some(addr));
+                    if let Some(addr) = addr {
+                        self.respawn(addr, pos)?;
+                    }
+                }
+            }
+        }
+
+        // e.g., players have been moved or the rules have changed
+        self.resume();
+
+        if self.game.is_stopping() && self.stop_deadline.is_none() {
+            self.stop()?;
+        }
+        Ok(())
+    }
+
+    /// Stop admitting anyone and tell everyone why.
+    fn stop(&mut self) -> Result {
+        info!("[Server] Shutting down");
+        if let Some(mut listener) = self.listener.take() {
+            listener.deregister(self.poll.registry())?;
+        }
+
+        let tokens = self.peers.keys().copied().collect::<Vec<_>>();
+        for token in tokens {
+            self.close(token, RejectReason::ShuttingDown)?;
+        }
+        self.stop_deadline = Some(Instant::now() + FLUSH_TIMEOUT);
+        Ok(())
+    }
+}
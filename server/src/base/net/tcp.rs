@@ -1,205 +1,357 @@
 use crate::*;
-use crossbeam_channel::{Receiver, Sender};
-use std::time::Instant;
+use mio::Token;
+use std::{
+    io::ErrorKind,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
 use ultraviolet::Vec3;
 
-fn handshake(
-    tcp: TcpClient,
-    clients_udp: UdpClients,
-    receiver_addr: &Receiver<SocketAddr>,
-    id: Id,
-) -> Result<SocketAddr> {
-    let mut buf = [0; 1];
-
-    // receive initial client handshake packet
-    debug!("[TCP] [1] Receiving client handshake");
-    tcp.recv(&mut buf)?;
-    if buf[0] != ClientHandshake::ID {
-        return Err(format!("Found {}, expected ClientHandshake.", buf[0]).into());
+/// how long a disconnected player's stream lingers to be sent the reason
+const LINGER: Duration = SECOND;
+
+impl<T: Transport> Server<T> {
+    /// Accept every pending stream.
+    pub(super) fn accept(&mut self) -> Result {
+        loop {
+            let Some(listener) = &self.listener else {
+                return Ok(());
+            };
+
+            let (mut stream, addr) = match listener.accept() {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => {
+                    // e.g., out of file descriptors (retried on the next connection)
+                    error!("[handle_incoming] {e:?}");
+                    return Ok(());
+                }
+            };
+            info!("{addr} attempting to join");
+
+            let token = Token(self.next_token);
+            self.next_token += 1;
+            stream.register(self.poll.registry(), token, &self.ready)?;
+
+            // don't let anyone take forever to join
+            let deadline = Instant::now() + self.game.rules().handshake_timeout;
+            self.peers.insert(token, Peer::new(stream, addr, deadline));
+        }
     }
 
-    // reply with server handshake
-    debug!("[TCP] [2] Sending server handshake");
-    tcp.send(&ServerHandshake::new(id).serialize())?;
+    /// Read from a stream, handling every complete packet.
+    pub(super) fn recv_tcp(&mut self, token: Token) -> Result {
+        let Some(peer) = self.peers.get_mut(&token) else {
+            return Ok(());
+        };
 
-    // receive UDP address from UDP thread [handle_incoming]
-    debug!("[TCP] [3] Waiting for UDP address");
-    let addr = receiver_addr.recv()?;
+        match peer.recv() {
+            Ok(Some(packets)) => {
+                for packet in packets {
+                    self.on_packet(token, packet)?;
+                }
+                Ok(())
+            }
+            Ok(None) => self.drop_peer(token),
+            Err(e) => {
+                if e.kind() != ErrorKind::ConnectionReset {
+                    warn!("{} {e}", peer.addr());
+                }
+                self.drop_peer(token)
+            }
+        }
+    }
+
+    fn on_packet(&mut self, token: Token, packet: Packet) -> Result {
+        let Some(peer) = self.peers.get_mut(&token) else {
+            return Ok(());
+        };
+
+        match peer.stage {
+            Stage::Hello => self.hello(token, packet),
+            Stage::Playing { .. } => {
+                // clients keep syncing their clocks (silence means they're gone)
+                peer.deadline = Instant::now() + self.game.rules().idle_timeout;
 
-    // send game states to client
-    debug!("[TCP] [6] Sending game states");
-    for &data in clients_udp.read().values() {
-        tcp.send(&data.serialize())?
+                if packet[0] == TimeSync::ID {
+                    let Some(bytes) = payload(&packet[1..], TimeSync::UNPADDED_SIZE) else {
+                        error!("[TCP] {} sent a malformed TimeSync", peer.addr());
+                        return self.drop_peer(token);
+                    };
+                    let mut sync = TimeSync::deserialize(bytes);
+                    sync.server_recv = timestamp();
+
+                    // the round trip of the last reply (to judge the player's interactions by)
+                    if let Stage::Playing { id, .. } = peer.stage
+                        && let Some(sample) = ClockSample::echoed(sync, sync.server_recv)
+                    {
+                        self.game.measure(id, sample);
+                    }
+
+                    // reply with both of the server's timestamps
+                    sync.server_send = timestamp();
+                    if !peer.send(frame(&sync.serialize())?) {
+                        self.stall(token)?;
+                    }
+                }
+                Ok(())
+            }
+            Stage::Udp { .. } | Stage::Closing => Ok(()),
+        }
     }
 
-    // end the handshake
-    debug!("[TCP] [7] Finishing");
-    tcp.send(&Flush::serialize())?;
+    /// Admit the client (or turn it away).
+    fn hello(&mut self, token: Token, packet: Packet) -> Result {
+        debug!("[TCP] [1] Receiving client handshake");
+        if packet[0] != ClientHandshake::ID {
+            error!(
+                "[handle_incoming] Found {}, expected ClientHandshake.",
+                packet[0]
+            );
+            return self.drop_peer(token);
+        }
+        let Some(bytes) = payload(&packet[1..], ClientHandshake::UNPADDED_SIZE) else {
+            error!("[handle_incoming] Found a malformed ClientHandshake.");
+            return self.drop_peer(token);
+        };
+        let hello = ClientHandshake::deserialize(bytes);
 
-    Ok(addr)
-}
+        // players that are joining count too
+        let players = self.peers.values().filter(|p| p.id().is_some()).count();
+        let peer = self.peers.get_mut(&token).unwrap();
+
+        match self.game.join(peer.addr().ip(), players) {
+            Ok(id) => {
+                debug!("[TCP] [2] Sending server handshake");
+                peer.push(frame(&ServerHandshake::new(id).serialize())?);
+                peer.stage = Stage::Udp { id, hello, packet };
+                peer.deadline = Instant::now() + self.game.rules().handshake_timeout;
+
+                debug!("[TCP] [3] Waiting for UDP address");
+                Ok(())
+            }
+            Err(reason) => {
+                debug!("[TCP] [2] Rejecting client");
+                error!("[handle_incoming] Rejected {}: {reason}.", peer.addr());
+                self.close(token, reason)
+            }
+        }
+    }
+
+    /// The client's UDP handshake has arrived, so send it everything and let it play.
+    pub(super) fn admit(&mut self, token: Token, addr_udp: SocketAddr) -> Result {
+        let game = self.game.clone();
+        let world = game.world();
+        let peer = self.peers.get_mut(&token).unwrap();
+        let (addr_tcp, Stage::Udp { id, hello, .. }) = (peer.addr(), peer.stage.clone()) else {
+            return Ok(());
+        };
+
+        // send the static world to client
+        debug!("[TCP] [6] Sending world ({} objects)", world.len());
+        peer.push(frame(&world.ambient().serialize())?);
+        for &pos in world.spawns() {
+            peer.push(frame(&SpawnPoint { pos }.serialize())?);
+        }
+        for obj in world.authored() {
+            peer.push(frame(&obj.serialize())?);
+        }
+
+        // generated objects are rebuilt by the client itself
+        if let Some(generator) = world.generator() {
+            peer.push(frame(&generator.serialize())?);
+        }
+
+        // send game states to client
+        debug!("[TCP] [7] Sending game states");
+        peer.push(frame(&game.movement().serialize())?);
+        for data in self.clients_udp.read().values() {
+            peer.push(frame(&data.serialize())?);
+        }
 
-fn _handle_alive(tcp: TcpClient) -> Result<()> {
-    let mut buf = [0; 1];
-    let spin = SpinSleeper::default();
+        // end the handshake
+        debug!("[TCP] [8] Finishing");
+        peer.push(frame(&Flush::serialize())?);
+        peer.stage = Stage::Playing { id, addr_udp };
+        peer.deadline = Instant::now() + game.rules().idle_timeout;
 
-    loop {
-        let t = Instant::now();
+        // the requested name (if valid), distinct from everyone else's
+        let name = Name::new(hello.name.as_str())
+            .unwrap_or_else(|e| {
+                warn!("{addr_tcp} requested an invalid name: {e}");
+                Name::new(DEFAULT_NAME).unwrap()
+            })
+            .dedupe(id, |name| {
+                self.clients_udp.read().values().any(|c| c.name == *name)
+            });
 
-        // ping client
-        tcp.send(&Ping::serialize())?;
+        // any (opaque) color
+        let color = hello.color.clamped(Vec3::zero(), Vec3::one());
 
-        // wait for response
-        tcp.recv(&mut buf)?;
+        info!("{name} ({addr_tcp}) has joined as {id}");
 
-        // enforce minimum ping
-        let elapsed = t.elapsed();
-        if elapsed < PING_MINIMUM {
-            let diff = PING_MINIMUM - elapsed;
-            spin.sleep(diff);
+        // place the player clear of everyone else
+        let others = self
+            .clients_udp
+            .read()
+            .values()
+            .map(|c| c.cam.eye)
+            .collect::<Vec<_>>();
+        let pos = game.spawn(&others);
+
+        // contruct client's initial object data
+        let data = UptObj {
+            id,
+            kind: ObjType::Player,
+            name,
+            dim: Vec3::new(1.0, 1.0, 1.0),
+            color: Color::new([color.x, color.y, color.z, 1.0], false),
+            cam: CameraAttr::new(pos),
+            ..Default::default()
+        };
+
+        // initialize object update structure
+        self.updates.insert(
+            addr_udp,
+            UptObjOpt {
+                id,
+                ..Default::default()
+            },
+        );
+
+        // add the player to each table
+        self.tokens.insert(id, token);
+        self.clients_tcp.write().insert(id, addr_tcp);
+        self.clients_udp.write().insert(addr_udp, data);
+        self.latest_inputs.insert(addr_udp, (self.tick, 0));
+        self.record(ReplayEvent::Joined(data));
+
+        // player joined
+        self.broadcast(&data.serialize())
+    }
+
+    /// Remove a player from the game (their stream is dealt with separately).
+    fn leave(&mut self, id: Id, addr_udp: SocketAddr, addr_tcp: SocketAddr) -> Result {
+        if let Some(user) = self.clients_udp.write().remove(&addr_udp) {
+            info!("{} ({addr_tcp}) has left", user.name);
         }
+        self.clients_tcp.write().remove(&id);
+        self.tokens.remove(&id);
+        self.updates.remove(&addr_udp);
+        self.counts.remove(&addr_udp);
+        self.latest_inputs.remove(&addr_udp);
+        self.game.leave(id);
+        self.record(ReplayEvent::Left(id));
+
+        self.broadcast(&RemObj { id }.serialize())
     }
-}
 
-fn handle_alive(
-    tcp: TcpClient,
-    [addr_tcp, addr_udp]: [SocketAddr; 2],
-    clients_tcp: TcpClients,
-    clients_udp: UdpClients,
-    sender: Sender<Packet>,
-) -> JoinHandle<Result> {
-    spawn(move || {
-        if let Err(Error::Blazed(BlazedError::Io(e))) = _handle_alive(tcp) {
-            if let std::io::ErrorKind::ConnectionReset = e.kind() {
-                info!("{addr_tcp} has left")
-            } else {
-                warn!("{e}")
+    /// Forget a stream (and its player, if any) at once.
+    pub(super) fn drop_peer(&mut self, token: Token) -> Result {
+        let Some(mut peer) = self.peers.remove(&token) else {
+            return Ok(());
+        };
+        _ = peer.stream().deregister(self.poll.registry());
+
+        match peer.stage {
+            Stage::Playing { id, addr_udp } => self.leave(id, addr_udp, peer.addr()),
+            Stage::Udp { id, .. } => {
+                self.game.abort_join(id);
+                Ok(())
             }
+            Stage::Hello | Stage::Closing => Ok(()),
         }
+    }
 
-        if let Some(user) = clients_udp.write().remove(&addr_udp) {
-            let id = user.id;
+    /// Tell the client why, then close its stream (once everything queued has been written).
+    pub(super) fn close(&mut self, token: Token, reason: RejectReason) -> Result {
+        let Some(peer) = self.peers.get_mut(&token) else {
+            return Ok(());
+        };
+        if peer.is_closing() {
+            return Ok(());
+        }
 
-            // remove client before send packet to TCP channel
-            clients_tcp.write().remove(&id);
+        let stage = peer.stage.clone();
+        peer.close(frame(&Rejected { reason }.serialize())?);
+        peer.deadline = Instant::now() + LINGER;
 
-            // send packet to TCP channel
-            sender.send(RemObj { id }.serialize().to_vec())?
+        match stage {
+            Stage::Playing { id, addr_udp } => {
+                let addr_tcp = peer.addr();
+                self.leave(id, addr_udp, addr_tcp)
+            }
+            Stage::Udp { id, .. } => {
+                self.game.abort_join(id);
+                Ok(())
+            }
+            Stage::Hello | Stage::Closing => Ok(()),
+        }
+    }
+
+    /// Disconnect a player that has fallen too far behind.
+    fn stall(&mut self, token: Token) -> Result {
+        if let Some(peer) = self.peers.get(&token) {
+            warn!("{} fell {OUTBOX_SIZE} packets behind", peer.addr());
+        }
+        self.drop_peer(token)
+    }
+
+    /// Queue a packet for every player.
+    pub(super) fn broadcast(&mut self, packet: &[u8]) -> Result {
+        let frame = frame(packet)?;
+
+        let stalled = self
+            .peers
+            .iter_mut()
+            .filter(|(_, peer)| matches!(peer.stage, Stage::Playing { .. }))
+            .filter_map(|(&token, peer)| (!peer.send(frame.clone())).then_some(token))
+            .collect::<Vec<_>>();
+
+        for token in stalled {
+            self.stall(token)?;
         }
         Ok(())
-    })
-}
+    }
 
-fn handle_dist(
-    s: &SyncSelect,
-    clients_tcp: TcpClients,
-    receiver_packet: Receiver<Packet>,
-) -> JoinHandle<Result> {
-    s.spawn(move || {
-        loop {
-            let packet = receiver_packet.recv()?;
+    /// Write whatever is queued for a stream.
+    pub(super) fn flush(&mut self, token: Token) -> Result {
+        let Some(peer) = self.peers.get_mut(&token) else {
+            return Ok(());
+        };
 
-            // distribute updates
-            for tcp in clients_tcp.read().values() {
-                tcp.send(&packet)?;
+        if let Err(e) = peer.flush() {
+            if e.kind() != ErrorKind::ConnectionReset && e.kind() != ErrorKind::BrokenPipe {
+                warn!("{} {e}", peer.addr());
             }
+            self.drop_peer(token)?;
         }
-    })
-}
+        Ok(())
+    }
 
-fn handle_incoming(
-    s: &SyncSelect,
-    tcp_listener: TcpServer,
-    clients_tcp: TcpClients,
-    clients_udp: UdpClients,
-    sender_packet: Sender<Packet>,
-    receiver_addr: Receiver<SocketAddr>,
-    updates: Updates,
-    id: Arc<AtomicId>,
-) -> JoinHandle<Result> {
-    s.spawn(move || {
-        for tcp in tcp_listener.incoming() {
-            // the client's tcp address
-            let addr_tcp = tcp.peer_addr()?;
-            info!("{addr_tcp} attempting to join");
-
-            // init handshake process
-            match handshake(
-                tcp.clone(),
-                clients_udp.clone(),
-                &receiver_addr,
-                id.load(Ordering::Relaxed),
-            ) {
-                Ok(addr_udp) => {
-                    info!("{addr_tcp} has joined");
-
-                    // increment if handshake was successful
-                    let id = id.fetch_add(1, Ordering::Relaxed);
-
-                    // contruct client's initial object data
-                    let data = UptObj {
-                        id,
-                        kind: ObjType::Player,
-                        dim: Vec3::new(1.0, 1.0, 1.0),
-                        color: Color::new([1.0, 1.0, 1.0, 1.0], false),
-                        ..Default::default()
-                    };
+    pub(super) fn flush_all(&mut self) -> Result {
+        let tokens = self.peers.keys().copied().collect::<Vec<_>>();
+        for token in tokens {
+            self.flush(token)?;
+        }
+        Ok(())
+    }
 
-                    // initialize object update structure
-                    updates.lock().insert(
-                        addr_udp,
-                        UptObjOpt {
-                            id,
-                            ..Default::default()
-                        },
-                    );
-
-                    // add client stream to TCP table
-                    clients_tcp.write().insert(id, tcp.clone());
-
-                    // add player data to UDP table
-                    clients_udp.write().insert(addr_udp, data);
-
-                    // player joined
-                    sender_packet.send(data.serialize().to_vec())?;
-
-                    let _alive = handle_alive(
-                        tcp,
-                        [addr_tcp, addr_udp],
-                        clients_tcp.clone(),
-                        clients_udp.clone(),
-                        sender_packet.clone(),
-                    );
-                }
-                Err(e) => error!("[handle_incoming] {e:?}"),
+    /// Drop every stream past its deadline.
+    pub(super) fn expire(&mut self, now: Instant) -> Result {
+        let expired = self
+            .peers
+            .iter()
+            .filter_map(|(&token, peer)| (peer.deadline <= now).then_some(token))
+            .collect::<Vec<_>>();
+
+        for token in expired {
+            let peer = &self.peers[&token];
+            if !peer.is_closing() {
+                warn!("{} timed out", peer.addr());
             }
+            self.drop_peer(token)?;
         }
-        unreachable!()
-    })
-}
-
-pub fn init_tcp(
-    s: &SyncSelect,
-    tcp: TcpServer,
-    clients_tcp: TcpClients,
-    clients_udp: UdpClients,
-    sender_packet: Sender<Packet>,
-    receiver_addr: Receiver<SocketAddr>,
-    receiver_packet: Receiver<Packet>,
-    updates: Updates,
-    id: Arc<AtomicId>,
-) {
-    handle_incoming(
-        s,
-        tcp,
-        clients_tcp.clone(),
-        clients_udp,
-        sender_packet,
-        receiver_addr,
-        updates,
-        id,
-    );
-
-    // init TCP distribution thread
-    handle_dist(s, clients_tcp, receiver_packet);
+        Ok(())
+    }
 }
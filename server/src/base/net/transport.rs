@@ -0,0 +1,220 @@
+use crate::*;
+use mio::{Interest, Registry, Token, Waker, net::TcpListener};
+use std::{
+    io::{self, Read, Write},
+    net::Shutdown,
+    time::Duration,
+};
+
+/// Sockets that something has arrived at, as told by the sockets themselves
+/// (in-memory ones can't be polled by mio, so they wake the event loop instead).
+#[derive(Clone, Debug)]
+pub struct Ready {
+    tokens: Arc<Mutex<Vec<Token>>>,
+    waker: Arc<Waker>,
+}
+
+impl Ready {
+    pub fn new(waker: Arc<Waker>) -> Self {
+        Self {
+            tokens: Default::default(),
+            waker,
+        }
+    }
+
+    /// Have the event loop read from a socket.
+    pub fn notify(&self, token: Token) {
+        self.tokens.lock().push(token);
+        _ = self.waker.wake();
+    }
+
+    pub fn take(&self) -> Vec<Token> {
+        std::mem::take(&mut self.tokens.lock())
+    }
+}
+
+/// A socket the event loop is told about once it's ready.
+pub trait Source {
+    fn register(&mut self, registry: &Registry, token: Token, ready: &Ready) -> io::Result<()>;
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()>;
+}
+
+/// Accepts streams.
+pub trait Listener: Source + Send {
+    type Stream: TcpConn + Source;
+
+    fn accept(&self) -> io::Result<(Self::Stream, SocketAddr)>;
+
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+}
+
+/// What the server's sockets are.
+pub trait Transport: 'static {
+    type Stream: TcpConn + Source;
+    type Listener: Listener<Stream = Self::Stream>;
+    type Udp: UdpConn + Source;
+}
+
+/// Real (non-blocking) sockets, polled by mio.
+#[derive(Debug)]
+pub struct Sockets;
+
+impl Transport for Sockets {
+    type Stream = MioStream;
+    type Listener = TcpListener;
+    type Udp = MioUdp;
+}
+
+/// Sockets within an in-memory network (e.g., for tests or a local game).
+#[derive(Debug)]
+pub struct Memory;
+
+impl Transport for Memory {
+    type Stream = MemStream;
+    type Listener = MemListener;
+    type Udp = MemUdp;
+}
+
+/// A TCP stream polled by mio.
+#[derive(Debug)]
+pub struct MioStream(mio::net::TcpStream);
+
+impl TcpConn for MioStream {
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        (&self.0).read(buf)
+    }
+
+    fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        (&self.0).write(buf)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.0.shutdown(how)
+    }
+
+    fn set_read_timeout(&self, _: Option<Duration>) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+impl Source for MioStream {
+    fn register(&mut self, registry: &Registry, token: Token, _: &Ready) -> io::Result<()> {
+        registry.register(&mut self.0, token, Interest::READABLE | Interest::WRITABLE)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        registry.deregister(&mut self.0)
+    }
+}
+
+impl Source for TcpListener {
+    fn register(&mut self, registry: &Registry, token: Token, _: &Ready) -> io::Result<()> {
+        registry.register(self, token, Interest::READABLE)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        registry.deregister(self)
+    }
+}
+
+impl Listener for TcpListener {
+    type Stream = MioStream;
+
+    fn accept(&self) -> io::Result<(Self::Stream, SocketAddr)> {
+        TcpListener::accept(self).map(|(stream, addr)| (MioStream(stream), addr))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        TcpListener::local_addr(self)
+    }
+}
+
+/// A UDP socket polled by mio.
+#[derive(Debug)]
+pub struct MioUdp(mio::net::UdpSocket);
+
+impl MioUdp {
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        mio::net::UdpSocket::bind(addr).map(Self)
+    }
+}
+
+impl UdpConn for MioUdp {
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        self.0.send_to(buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.0.recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    fn set_read_timeout(&self, _: Option<Duration>) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+impl Source for MioUdp {
+    fn register(&mut self, registry: &Registry, token: Token, _: &Ready) -> io::Result<()> {
+        registry.register(&mut self.0, token, Interest::READABLE)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        registry.deregister(&mut self.0)
+    }
+}
+
+/// The hook of an in-memory socket, reporting it to the event loop.
+fn hook(token: Token, ready: &Ready) -> Option<ReadyHook> {
+    let ready = ready.clone();
+    Some(Arc::new(move || ready.notify(token)))
+}
+
+impl Source for MemStream {
+    fn register(&mut self, _: &Registry, token: Token, ready: &Ready) -> io::Result<()> {
+        self.set_nonblocking(true)?;
+        self.set_ready_hook(hook(token, ready))
+    }
+
+    fn deregister(&mut self, _: &Registry) -> io::Result<()> {
+        self.set_ready_hook(None)
+    }
+}
+
+impl Source for MemListener {
+    fn register(&mut self, _: &Registry, token: Token, ready: &Ready) -> io::Result<()> {
+        self.set_nonblocking(true)?;
+        self.set_ready_hook(hook(token, ready))
+    }
+
+    fn deregister(&mut self, _: &Registry) -> io::Result<()> {
+        self.set_ready_hook(None)
+    }
+}
+
+impl Listener for MemListener {
+    type Stream = MemStream;
+
+    fn accept(&self) -> io::Result<(Self::Stream, SocketAddr)> {
+        MemListener::accept(self)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(MemListener::local_addr(self))
+    }
+}
+
+impl Source for MemUdp {
+    fn register(&mut self, _: &Registry, token: Token, ready: &Ready) -> io::Result<()> {
+        self.set_nonblocking(true)?;
+        self.set_ready_hook(hook(token, ready))
+    }
+
+    fn deregister(&mut self, _: &Registry) -> io::Result<()> {
+        self.set_ready_hook(None)
+    }
+}
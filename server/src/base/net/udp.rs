@@ -1,273 +1,260 @@
 use crate::*;
-use crossbeam_channel::{Receiver, Sender, bounded};
-use std::{net::SocketAddr, time::Duration};
+use std::{collections::HashMap, io::ErrorKind, net::SocketAddr, time::Instant};
+use ultraviolet::Vec3;
 
-fn game_handler(
-    s: &SyncSelect,
-    waiter_game: Waiter,
-    clients_udp: UdpClients,
-    updates: Updates,
-) -> JoinHandle<Result> {
-    s.spawn(move || {
-        let spinner: SpinSleeper = Default::default();
-        let setter_game = waiter_game.setter();
+/// most simulation steps caught up on at once (the rest are skipped)
+const MAX_LAG: u32 = 8;
+
+impl<T: Transport> Server<T> {
+    /// Receive every pending datagram.
+    pub(super) fn recv_udp(&mut self) -> Result {
+        let mut buf = [0; PACKET_SIZE];
 
         loop {
-            waiter_game.wait();
+            // receive datagram message from any client
+            match self.udp.recv_from(&mut buf) {
+                Ok((0, _)) => (),
+                Ok((n, addr)) => self.on_datagram(&buf[..n], addr)?,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
 
-            // begin game updates
-            loop {
-                let mut is_idle = true;
-                for (addr, client) in clients_udp.write().iter_mut() {
-                    if !client.keys.is_empty() {
-                        client.cam.input(client.keys);
-                        updates.lock().get_mut(addr).unwrap().cam.eye = Some(client.cam.eye);
-                        is_idle = false;
-                    }
-                }
-                if is_idle {
-                    setter_game.set_ready();
-                    break;
+                // e.g., a player that has just left (reported by ICMP)
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset
+                    ) => {}
+                Err(e) => {
+                    error!("{e:?}");
+                    return Ok(());
                 }
-
-                // respect game speed
-                spinner.sleep(GAME_SPEED);
             }
-            waiter_game.reset();
         }
-    })
-}
-
-fn handle_dist(
-    s: &SyncSelect,
-    waiter_dist: Waiter,
-    spec_game: Spectator,
-    udp: UdpServer,
-    clients_udp: UdpClients,
-    updates: Updates,
-    tps: Duration,
-) -> JoinHandle<Result> {
-    s.spawn(move || -> Result {
-        let spinner: SpinSleeper = Default::default();
+    }
 
-        loop {
-            // if idle, yield until a packet is received
-            if !spec_game.is_ready() {
-                waiter_dist.wait();
-                waiter_dist.reset();
+    fn on_datagram(&mut self, packet: &[u8], addr: SocketAddr) -> Result {
+        // if the client exists, handle its input
+        if self.clients_udp.read().contains_key(&addr) {
+            if self.window.elapsed() >= SECOND {
+                self.window = Instant::now();
+                self.counts.clear();
             }
 
-            // TODO - improve this (try not to collect)
-            let new_updates = updates
-                .lock()
-                .iter_mut()
-                .filter_map(|(.., upt)| upt.is_modified().then_some(upt.take()))
-                .collect::<Vec<UptObjOpt>>();
-
-            // distribute updates to each player
-            for upt in new_updates {
-                // send update to each client
-                for addr in clients_udp.read().keys() {
-                    if let Err(e) = udp.send_to(&upt.serialize(), addr) {
-                        error!("{e:?}")
-                    }
+            // drop whatever exceeds the limit
+            let limit = self.game.rules().packets_per_sec;
+            let count = self.counts.entry(addr).or_default();
+            *count += 1;
+            if *count > limit {
+                if *count == limit + 1 {
+                    warn!("[UDP] {addr} exceeded {limit} packets/sec");
                 }
+                return Ok(());
             }
 
-            // respect the TPS
-            spinner.sleep(tps);
+            self.input(packet, addr);
+            return Ok(());
         }
-    })
-}
-
-/// control updates and handle status (parked/unparked) of TPS thread
-fn handle_packets(
-    s: &SyncSelect,
-    waiter_game: Waiter,
-    notifier_dist: Notifier,
-    clients_udp: UdpClients,
-    receiver: Receiver<(Packet, SocketAddr)>,
-    updates: Updates,
-) {
-    /// process packet and return recipient's address
-    fn _handle_packets(
-        notifier: &Notifier,
-        clients_udp: &UdpClients,
-        receiver: &Receiver<(Packet, SocketAddr)>,
-        updates: &Updates,
-    ) -> Result<()> {
-        // receive packet with address
-        let (packet, addr) = receiver.recv()?;
 
-        // prepare to update player data
-        let mut clients = clients_udp.write();
-        let obj = clients.get_mut(&addr).ok_or("Object no longer exists")?;
-
-        // only processing input-based events for now
-        match packet[0] {
-            // Keys
-            Keyboard::ID => {
-                let kb = Keyboard::deserialize(&packet[1..]);
-                if kb.is_pressed == 1 {
-                    let was_empty = obj.keys.is_empty();
-                    obj.keys |= Keys::from_bits_retain(kb.bits);
-                    if was_empty {
-                        notifier.notify();
-                    }
-                } else {
-                    obj.keys -= Keys::from_bits_retain(kb.bits);
-                }
-            }
+        // if client doesn't exist, assume packet is client handshake
+        if packet[0] == ClientHandshake::ID {
+            debug!("[UDP] [4] Received client handshake");
+        } else {
+            error!("[UDP] [4] Expected PacketClientHandshake");
+            return Ok(());
+        }
 
-            // Wheel
-            Wheel::ID => {
-                let wheel = Wheel::deserialize(&packet[1..]);
-                obj.cam.upt_fov(wheel.precise_y);
-                updates.lock().get_mut(&addr).unwrap().cam.fov = Some(obj.cam.fov);
-            }
+        // the earliest (identical) TCP handshake, preferably from the same host
+        let token = self
+            .peers
+            .iter()
+            .filter(|(_, peer)| matches!(&peer.stage, Stage::Udp { packet: p, .. } if p == packet))
+            .min_by_key(|(token, peer)| (peer.addr().ip() != addr.ip(), token.0))
+            .map(|(&token, _)| token);
 
-            // Motion
-            MotionOpt::ID => {
-                let motion = MotionOpt::deserialize(&packet[1..]);
-                obj.cam.look_at(
-                    motion.xrel.unwrap_or_default(),
-                    motion.yrel.unwrap_or_default(),
-                );
-                let mut lock = updates.lock();
-                let client_cam = &mut lock.get_mut(&addr).unwrap().cam;
-                client_cam.yaw = Some(obj.cam.yaw);
-                client_cam.pitch = Some(obj.cam.pitch);
-                client_cam.target = Some(obj.cam.target)
+        debug!("[UDP] [5] Matching UDP address");
+        match token {
+            Some(token) => self.admit(token, addr),
+            None => {
+                warn!("[UDP] {addr} sent an unexpected handshake");
+                Ok(())
             }
-            _ => unreachable!(),
         }
-
-        Ok(())
     }
 
-    let notifier_game = waiter_game.notifier();
+    /// Apply a player's input (only input-based events for now).
+    fn input(&mut self, packet: &[u8], addr: SocketAddr) {
+        let clients_udp = self.clients_udp.clone();
+        let mut clients = clients_udp.write();
+        let (Some(obj), Some(upt)) = (clients.get_mut(&addr), self.updates.get_mut(&addr)) else {
+            return;
+        };
 
-    game_handler(s, waiter_game, clients_udp.clone(), updates.clone());
+        // (malformed inputs are dropped)
+        let bytes = &packet[1..];
+        let input = match packet[0] {
+            Keyboard::ID => payload(bytes, Keyboard::UNPADDED_SIZE)
+                .map(|bytes| Input::Keyboard(Keyboard::deserialize(bytes))),
+            Wheel::ID => payload(bytes, Wheel::UNPADDED_SIZE)
+                .map(|bytes| Input::Wheel(Wheel::deserialize(bytes))),
+            MotionOpt::ID => MotionOpt::parse(bytes).map(Input::Motion),
+            _ => None,
+        };
+        let Some(input) = input else {
+            return;
+        };
+        let was_still = obj.keys.is_empty();
+        obj.apply(input, &self.game.movement());
 
-    s.spawn(move || -> Result {
-        loop {
-            match _handle_packets(&notifier_game, &clients_udp, &receiver, &updates) {
-                Ok(_) => {
-                    // notify distribution thread
-                    notifier_dist.notify();
+        match input {
+            Input::Keyboard(_) => {
+                if was_still && !obj.keys.is_empty() {
+                    self.resume();
                 }
-                Err(e) => error!("{e:?}"),
+            }
+            Input::Wheel(_) => upt.cam.fov = Some(obj.cam.fov),
+            Input::Motion(_) => {
+                upt.cam.yaw = Some(obj.cam.yaw);
+                upt.cam.pitch = Some(obj.cam.pitch);
+                upt.cam.target = Some(obj.cam.target);
             }
         }
-    });
-}
-
-/// instantiates TPS and packet processing threads
-fn init_write(
-    s: &SyncSelect,
-    udp: UdpServer,
-    clients_udp: UdpClients,
-    receiver: Receiver<(Packet, SocketAddr)>,
-    updates: Updates,
-    tps: Duration,
-) {
-    let waiter_dist = Waiter::default();
-    let notifier_dist = waiter_dist.notifier();
+        let id = obj.id;
+        drop(clients);
+        self.latest_inputs.insert(addr, (self.tick, input.seq()));
+        self.record(ReplayEvent::Input { id, input });
 
-    let waiter_game = Waiter::default();
-    let spectator_game = waiter_game.spectator();
+        // distribute on the next tick
+        self.is_modified = true;
+    }
 
-    handle_dist(
-        s,
-        waiter_dist,
-        spectator_game,
-        udp,
-        clients_udp.clone(),
-        updates.clone(),
-        tps,
-    );
+    /// (Re)start simulating (e.g., someone has started moving).
+    pub(super) fn resume(&mut self) {
+        self.next_step.get_or_insert_with(Instant::now);
+    }
 
-    handle_packets(
-        s,
-        waiter_game,
-        notifier_dist,
-        clients_udp,
-        receiver,
-        updates,
-    );
-}
+    /// Simulate a single step, stopping once every player has come to rest.
+    pub(super) fn step(&mut self) -> Result {
+        let mut is_idle = true;
+        let movement = self.game.movement();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.set_movement(movement);
+        }
 
-/// UDP datagram message distributing thread
-fn handle_incoming(
-    s: &SyncSelect,
-    udp: UdpServer,
-    clients_udp: UdpClients,
-    sender_packet: Sender<(Packet, SocketAddr)>,
-    sender_addr: Sender<SocketAddr>,
-) -> JoinHandle<Result> {
-    s.spawn(move || {
-        let mut buf = [0; PACKET_SIZE];
+        // players that have fallen out of the world within the last step
+        let mut fallen = self
+            .clients_udp
+            .read()
+            .iter()
+            .filter(|(_, c)| self.game.is_out_of_bounds(c.cam.eye))
+            .map(|(&addr, c)| (c.id, addr))
+            .collect::<Vec<_>>();
+        fallen.sort_unstable();
 
-        loop {
-            // receive datagram message from any client
-            match udp.recv_from(&mut buf) {
-                Ok((n, addr)) => {
-                    let packet = buf[..n].to_vec();
+        for (_, addr) in fallen {
+            let others = self
+                .clients_udp
+                .read()
+                .iter()
+                .filter_map(|(&other, c)| (other != addr).then_some(c.cam.eye))
+                .collect::<Vec<_>>();
+            let pos = self.game.spawn(&others);
+            self.respawn(addr, pos)?;
+        }
 
-                    // if the client exists,
-                    // channel packet and source to process handling thread
-                    if clients_udp.read().contains_key(&addr) {
-                        // send to read channel
-                        _ = sender_packet.try_send((packet, addr));
-                        continue;
-                    }
+        let clients_udp = self.clients_udp.clone();
+        let mut clients = clients_udp.write();
 
-                    // if client doesn't exist, assume packet is client handshake
-                    if packet[0] == ClientHandshake::ID {
-                        debug!("[UDP] [4] Received client handshake");
-                    } else {
-                        error!("[UDP] [4] Expected PacketClientHandshake");
-                        continue;
-                    }
+        // in order of identity, so the outcome only ever depends on the inputs
+        let mut players = clients.iter_mut().collect::<Vec<_>>();
+        players.sort_unstable_by_key(|(_, client)| client.id);
 
-                    // share address with TCP server
-                    debug!("[UDP] [5] Channeling UDP address");
-                    if let Err(e) = sender_addr.send(addr) {
-                        error!("{e:?}");
-                        continue;
-                    }
-                }
-                Err(e) => {
-                    // debugging
-                    error!("{e:?}");
-                    break Ok(());
+        for (addr, client) in players {
+            // keep simulating until the player comes to rest
+            if client.step(self.game.collider(), &movement) {
+                if let Some(upt) = self.updates.get_mut(addr) {
+                    upt.cam.eye = Some(client.cam.eye);
+                    upt.cam.velocity = Some(client.cam.velocity);
+                    upt.cam.state = Some(client.cam.state);
                 }
+                is_idle = false;
             }
         }
-    })
-}
+        self.tick += 1;
+        if self.tick.is_multiple_of(SNAPSHOT_RATE) {
+            self.snapshot(&clients);
+        }
+        drop(clients);
+        self.end_tick();
 
-pub fn init_udp(
-    s: &SyncSelect,
-    udp: UdpServer,
-    clients_udp: UdpClients,
-    sender_addr: Sender<SocketAddr>,
-    updates: Updates,
-    tps: Duration,
-) {
-    // real-time game data channel
-    let (sender_packet, receiver_packet) = bounded(8);
+        // distribute the latest (or final, resting) state
+        self.is_modified = true;
 
-    // handle outgoing
-    init_write(
-        s,
-        udp.clone(),
-        clients_udp.clone(),
-        receiver_packet,
-        updates,
-        tps,
-    );
+        // respect game speed (without snowballing when late)
+        self.next_step = match self.next_step {
+            _ if is_idle => None,
+            Some(at) if at.elapsed() < GAME_SPEED * MAX_LAG => Some(at + GAME_SPEED),
+            _ => Some(Instant::now() + GAME_SPEED),
+        };
+        Ok(())
+    }
+
+    /// Move a player (e.g., once fallen out of the world or teleported), telling everyone.
+    pub(super) fn respawn(&mut self, addr: SocketAddr, pos: Vec3) -> Result {
+        let Some(id) = self.clients_udp.write().get_mut(&addr).map(|client| {
+            client.cam.respawn(pos);
+            client.id
+        }) else {
+            return Ok(());
+        };
+        info!("Respawned {id} @ {pos:?}");
+
+        // simulated from the next tick on (e.g., walking players start falling)
+        self.latest_inputs.insert(addr, (self.tick, 0));
+        self.record(ReplayEvent::Respawned { id, pos });
+        self.broadcast(&Respawn { id, pos }.serialize())
+    }
 
-    // handle incoming UDP packets
-    handle_incoming(s, udp.clone(), clients_udp, sender_packet, sender_addr);
+    /// Send each player the server's simulation of them (to check their prediction against).
+    fn snapshot(&self, clients: &HashMap<SocketAddr, UptObj>) {
+        for (&addr, client) in clients {
+            let (input_tick, input_seq) =
+                self.latest_inputs.get(&addr).copied().unwrap_or_default();
+            let snapshot = Snapshot {
+                tick: self.tick,
+                hash: client.state_hash(),
+                input_tick,
+                input_seq,
+                keys: client.keys,
+                cam: client.cam,
+            };
+            match self.udp.send_to(&snapshot.serialize(), addr) {
+                Err(e) if e.kind() != ErrorKind::WouldBlock => error!("{e:?}"),
+                _ => (),
+            }
+        }
+    }
+
+    /// Send every modified update to every player.
+    pub(super) fn distribute(&mut self) {
+        // TODO - improve this (try not to collect)
+        let new_updates = self
+            .updates
+            .values_mut()
+            .filter_map(|upt| upt.is_modified().then_some(upt.take()))
+            .collect::<Vec<UptObjOpt>>();
+        let addrs = self.clients_udp.read().keys().copied().collect::<Vec<_>>();
+
+        // distribute updates to each player
+        for upt in new_updates {
+            let bytes = upt.serialize();
+            for &addr in &addrs {
+                match self.udp.send_to(&bytes, addr) {
+                    // lost like any other datagram
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+                    Err(e) => error!("{e:?}"),
+                    Ok(_) => (),
+                }
+            }
+        }
+        self.is_modified = false;
+    }
 }
@@ -0,0 +1,230 @@
+use crate::*;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, unbounded};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::BufWriter,
+    io::ErrorKind,
+    path::PathBuf,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Where (and how much) the game is recorded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    pub dir: PathBuf,
+    pub max_file_size: u64, // bytes of a file before another is started
+    pub max_files: usize,   // files kept in the directory (the oldest are deleted)
+}
+
+/// Records every tick of the game (e.g., to debug what the server saw), writing it in the background.
+///
+/// A frame holds whatever has happened since the last tick, then the tick's own outcome, so
+/// replaying its events before a single step reproduces the server.
+///
+/// Files are rotated once they reach their size, each starting with the whole game so it can be
+/// played on its own (see [`ReplayReader`]).
+pub struct Recorder {
+    frame: ReplayFrame,        // events of the current tick
+    last: HashMap<Id, UptObj>, // every player as last recorded
+    movement: Movement,
+    started: Instant,
+    sender: Option<Sender<ReplayFrame>>, // gone once writing has failed
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Recorder {
+    /// Start recording the game (before anyone has joined).
+    pub fn start(recording: Recording, game: &Game) -> Result<Self> {
+        fs::create_dir_all(&recording.dir).map_err(|e| {
+            format!(
+                "Couldn't create recording directory '{}': {e}",
+                recording.dir.display()
+            )
+        })?;
+        info!("[Record] Recording to {}", recording.dir.display());
+
+        let movement = game.movement();
+        let state = ReplayState::new(game.world().clone(), movement);
+        let (sender, receiver) = unbounded();
+        let worker = spawn(move || {
+            if let Err(e) = write(recording, state, receiver) {
+                error!("[Record] Stopped recording: {e}");
+            }
+        });
+
+        Ok(Self {
+            frame: Default::default(),
+            last: HashMap::new(),
+            movement,
+            started: Instant::now(),
+            sender: Some(sender),
+            worker: Some(worker),
+        })
+    }
+
+    /// Something has happened within the current tick.
+    pub fn record(&mut self, event: ReplayEvent) {
+        match event {
+            ReplayEvent::Joined(obj) => {
+                self.last.insert(obj.id, obj);
+            }
+            ReplayEvent::Left(id) => {
+                self.last.remove(&id);
+            }
+            _ => (),
+        }
+        self.frame.events.push(event);
+    }
+
+    /// The rules the tick is about to be simulated by (recorded once they have changed).
+    pub fn set_movement(&mut self, movement: Movement) {
+        if movement != self.movement {
+            self.movement = movement;
+            self.frame.events.push(ReplayEvent::Movement(movement));
+        }
+    }
+
+    /// Finish the tick, recording whatever has changed about the game since the last one.
+    pub fn end_tick<'a>(
+        &mut self,
+        tick: u64,
+        hash: u64,
+        players: impl IntoIterator<Item = &'a UptObj>,
+    ) {
+        for player in players {
+            let Some(last) = self.last.get_mut(&player.id) else {
+                continue;
+            };
+            let upt = delta(last, player);
+            if upt.is_modified() {
+                *last = *player;
+                self.frame.events.push(ReplayEvent::State(upt));
+            }
+        }
+
+        if self.frame.events.is_empty() {
+            return;
+        }
+        let frame = ReplayFrame {
+            tick,
+            time: self.started.elapsed(),
+            hash,
+            events: std::mem::take(&mut self.frame.events),
+        };
+        if let Some(sender) = &self.sender
+            && sender.send(frame).is_err()
+        {
+            self.sender = None;
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // let the writer finish everything that's been recorded
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            _ = worker.join();
+        }
+    }
+}
+
+/// Whatever has changed about a player (colors never do).
+fn delta(old: &UptObj, new: &UptObj) -> UptObjOpt {
+    fn changed<T: Copy + PartialEq>(old: T, new: T) -> Option<T> {
+        (old != new).then_some(new)
+    }
+    let (a, b) = (&old.cam, &new.cam);
+
+    UptObjOpt {
+        id: new.id,
+        kind: changed(old.kind, new.kind),
+        name: changed(old.name, new.name),
+        dim: changed(old.dim, new.dim),
+        color: None,
+        cam: CameraAttrOpt {
+            fov: changed(a.fov, b.fov),
+            yaw: changed(a.yaw, b.yaw),
+            pitch: changed(a.pitch, b.pitch),
+            eye: changed(a.eye, b.eye),
+            velocity: changed(a.velocity, b.velocity),
+            target: changed(a.target, b.target),
+            up: changed(a.up, b.up),
+            state: changed(a.state, b.state),
+        },
+        keys: new.keys,
+    }
+}
+
+/// Write every frame, starting another file whenever one is full (until the recorder is dropped).
+fn write(recording: Recording, mut state: ReplayState, frames: Receiver<ReplayFrame>) -> Result {
+    // files of this session are named after it, so they sort by age
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    // (numbered, so servers started within the same second don't collide)
+    let mut index = 0;
+    let (session, mut writer) = (0..1000)
+        .map(|n| format!("{secs:012}-{n:03}"))
+        .find_map(|session| {
+            create(&recording, &session, index, &state)
+                .map(|writer| writer.map(|writer| (session, writer)))
+                .transpose()
+        })
+        .ok_or("Ran out of replay file names")??;
+
+    loop {
+        let frame = match frames.recv_timeout(SECOND) {
+            Ok(frame) => frame,
+
+            // written out while the game is quiet (so little is lost on a crash)
+            Err(RecvTimeoutError::Timeout) => {
+                writer.flush()?;
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        if writer.size() >= recording.max_file_size {
+            writer.flush()?;
+            index += 1;
+            writer = create(&recording, &session, index, &state)?
+                .ok_or_else(|| format!("Replay file {index} of {session} already exists"))?;
+        }
+        writer.write(&frame)?;
+        state.apply(&frame);
+    }
+    writer.flush().map_err(Into::into)
+}
+
+/// Start another file at the given state, deleting the oldest ones beyond the limit
+/// (none if it already exists).
+fn create(
+    recording: &Recording,
+    session: &str,
+    index: u32,
+    state: &ReplayState,
+) -> Result<Option<ReplayWriter<BufWriter<File>>>> {
+    let files = replay_files(&recording.dir)?;
+    let excess = (files.len() + 1).saturating_sub(recording.max_files);
+    for path in &files[..excess] {
+        if let Err(e) = fs::remove_file(path) {
+            warn!("[Record] Couldn't delete {}: {e}", path.display());
+        }
+    }
+
+    let path = recording
+        .dir
+        .join(format!("{session}-{index:05}.{REPLAY_EXT}"));
+    let file = match File::create_new(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => return Ok(None),
+        Err(e) => return Err(format!("Couldn't create '{}': {e}", path.display()).into()),
+    };
+    debug!("[Record] Writing {}", path.display());
+
+    Ok(Some(ReplayWriter::new(BufWriter::new(file), state)?))
+}
@@ -0,0 +1,178 @@
+use crate::*;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+use ultraviolet::Vec3;
+
+/// How far behind the server a player sees the game.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Lag {
+    pub rtt: Duration,    // the update's way to the player and their input's way back
+    pub interp: Duration, // how long remote players are shown behind the latest update (none when extrapolated)
+}
+
+impl Lag {
+    /// When the game was as the player saw it, given the time their input is judged at.
+    pub fn view_time(&self, now: Instant) -> Instant {
+        now.checked_sub(self.rtt + self.interp).unwrap_or(now)
+    }
+}
+
+/// Where every player was as of a tick.
+#[derive(Clone, Debug)]
+struct Frame {
+    tick: u64,
+    time: Instant,
+    bounds: Vec<(Id, Aabb)>, // in order of identity
+}
+
+/// Past bounds of every player, kept as far back as anyone may be judged from.
+#[derive(Debug)]
+pub struct History {
+    frames: VecDeque<Frame>, // oldest first
+}
+
+impl History {
+    pub const fn new() -> Self {
+        Self {
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Remember the players as of the tick (unless none has changed), forgetting what's beyond reach.
+    pub fn push<'a>(
+        &mut self,
+        tick: u64,
+        now: Instant,
+        players: impl IntoIterator<Item = &'a UptObj>,
+        max_rewind: Duration,
+    ) {
+        let mut bounds = players
+            .into_iter()
+            .map(|p| (p.id, p.cam.bounds()))
+            .collect::<Vec<_>>();
+        bounds.sort_unstable_by_key(|&(id, _)| id);
+
+        if self.frames.back().is_none_or(|last| last.bounds != bounds) {
+            self.frames.push_back(Frame {
+                tick,
+                time: now,
+                bounds,
+            });
+        }
+
+        // the latest frame before the limit is still how the game was at it
+        let limit = now.checked_sub(max_rewind).unwrap_or(now);
+        while self.frames.get(1).is_some_and(|next| next.time <= limit) {
+            self.frames.pop_front();
+        }
+    }
+
+    /// The game as it was at the time (but no further back than the limit).
+    pub fn rewind<'a>(
+        &self,
+        time: Instant,
+        now: Instant,
+        max_rewind: Duration,
+        world: &'a Collider,
+    ) -> Rewind<'a> {
+        let limit = now.checked_sub(max_rewind).unwrap_or(now);
+        let time = time.clamp(limit, now);
+
+        // the latest frame by then (or the earliest there is)
+        let idx = self.frames.partition_point(|f| f.time <= time);
+        let (tick, bounds) = self
+            .frames
+            .get(idx.saturating_sub(1))
+            .map(|f| (f.tick, f.bounds.clone()))
+            .unwrap_or_default();
+
+        Rewind {
+            tick,
+            time,
+            bounds,
+            world,
+        }
+    }
+
+    /// Frames kept.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A player struck by a query.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub id: Id,
+    pub dist: f32, // along the ray
+    pub point: Vec3,
+}
+
+/// The players as they were at some (past) time, to judge an interaction from someone's point of view.
+///
+/// It's a copy of the past, so queries never disturb the live game (and nothing needs restoring).
+#[derive(Clone, Debug)]
+pub struct Rewind<'a> {
+    tick: u64,
+    time: Instant,
+    bounds: Vec<(Id, Aabb)>,
+    world: &'a Collider,
+}
+
+impl Rewind<'_> {
+    /// Tick of the game rewound to.
+    pub const fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Time rewound to (after the limit).
+    pub const fn time(&self) -> Instant {
+        self.time
+    }
+
+    /// Bounds of a player back then.
+    pub fn bounds(&self, id: Id) -> Option<Aabb> {
+        self.bounds
+            .binary_search_by_key(&id, |&(id, _)| id)
+            .ok()
+            .map(|idx| self.bounds[idx].1)
+    }
+
+    /// The nearest player (other than the shooter) a ray strikes within reach, unless the world is in the way.
+    pub fn raycast(&self, shooter: Id, origin: Vec3, dir: Vec3, reach: f32) -> Option<Hit> {
+        let dir = dir.normalized();
+        let reach = self.world.raycast(origin, dir, reach).unwrap_or(reach);
+
+        self.bounds
+            .iter()
+            .filter(|&&(id, _)| id != shooter)
+            .filter_map(|&(id, aabb)| aabb.ray(origin, dir).map(|dist| (id, dist)))
+            .filter(|&(_, dist)| dist <= reach)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(id, dist)| Hit {
+                id,
+                dist,
+                point: origin + dir * dist,
+            })
+    }
+
+    /// Every player within the volume back then.
+    pub fn overlapping<'b>(&'b self, volume: &'b Aabb) -> impl Iterator<Item = Id> + 'b {
+        self.bounds
+            .iter()
+            .filter(|(_, aabb)| aabb.intersects(volume))
+            .map(|&(id, _)| id)
+    }
+}
@@ -0,0 +1,14 @@
+mod base;
+
+pub use base::*;
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::Arc,
+    thread::{JoinHandle, spawn},
+};
+
+pub type TcpClients = Arc<RwLock<HashMap<Id, SocketAddr>>>;
+pub type UdpClients = Arc<RwLock<HashMap<SocketAddr, UptObj>>>;
+pub type Packet = Vec<u8>;
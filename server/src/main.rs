@@ -1,75 +1,53 @@
-mod base;
-
-use base::*;
-use crossbeam_channel::unbounded;
+use server::*;
 use std::{
-    collections::HashMap,
-    net::SocketAddr,
-    sync::{Arc, atomic::Ordering},
-    thread::{JoinHandle, spawn},
+    process::ExitCode,
+    thread::{Thread, current, park},
 };
-use sync_select::*;
 
-pub type TcpClients = Arc<RwLock<HashMap<Id, TcpClient>>>;
-pub type UdpClients = Arc<RwLock<HashMap<SocketAddr, UptObj>>>;
-pub type Updates = Arc<Mutex<HashMap<SocketAddr, UptObjOpt>>>;
-pub type Packet = Vec<u8>;
+fn handle_ctrlc(main: Thread) -> Result {
+    ctrlc::set_handler(move || main.unpark()).map_err(Into::into)
+}
 
-fn handle_ctrlc(s: &SyncSelect) -> Result {
-    let thread = s.thread();
-    ctrlc::set_handler(move || thread.unpark()).map_err(Into::into)
+#[cfg(unix)]
+fn handle_sighup(admin: Admin) -> Result {
+    use signal_hook::{consts::SIGHUP, iterator::Signals};
+    use std::thread::spawn;
+
+    let mut signals = Signals::new([SIGHUP])?;
+    spawn(move || {
+        for _ in signals.forever() {
+            match admin.execute(Command::Reload) {
+                Ok(out) => info!("{out}"),
+                Err(e) => error!("[Admin] {e}"),
+            }
+        }
+    });
+    Ok(())
 }
 
-fn main() -> Result {
+fn main() -> Result<ExitCode> {
     env_logger::init();
-    let cfg = Config::default();
-
-    // init TCP server
-    let tcp = TcpServer::new(cfg.tcp_addr())?;
-    info!("[TCP] Binded @ {:?}", cfg.tcp_addr());
-
-    // init UDP server
-    let udp = UdpServer::new(cfg.udp_addr())?;
-    info!("[UDP] Binded @ {:?}", cfg.udp_addr());
-
-    // map of player streams
-    let clients_tcp: TcpClients = Default::default();
-
-    // map of player data
-    let clients_udp: UdpClients = Default::default();
-
-    // share client UDP sockets between main thread and 'alive' thread
-    let (sender_addr, receiver_addr) = unbounded::<SocketAddr>();
-
-    // share client TCP packets
-    let (sender_packet, receiver_packet) = unbounded::<Vec<u8>>();
-
-    // monotonic user identity (default: 0)
-    let id = Default::default();
-
-    let updates: Updates = Default::default();
-
-    // short-circuiting local thread manager
-    let s = SyncSelect::default();
+    let server = ServerHandle::start(Config::load()?)?;
 
     // handle SIGINT
-    handle_ctrlc(&s)?;
-
-    // handle TCP packets
-    init_tcp(
-        &s,
-        tcp,
-        clients_tcp,
-        clients_udp.clone(),
-        sender_packet,
-        receiver_addr,
-        receiver_packet,
-        updates.clone(),
-        id,
-    );
-
-    // handle UDP packets
-    init_udp(&s, udp, clients_udp, sender_addr, updates, cfg.tps());
-
-    Ok(())
+    handle_ctrlc(current())?;
+
+    // reload the config on SIGHUP
+    #[cfg(unix)]
+    handle_sighup(server.admin().clone())?;
+
+    // from stdin
+    init_console(server.admin().clone());
+
+    // until SIGINT, a shutdown or the event loop failing
+    park();
+
+    // stop the event loop, telling players why, and report whether it succeeded
+    Ok(match server.shutdown() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("[Server] {e}");
+            ExitCode::FAILURE
+        }
+    })
 }
@@ -0,0 +1,219 @@
+//! Runs a server on ephemeral loopback ports (or within an in-memory network)
+//! with headless clients, all within a test.
+
+#![allow(dead_code)] // not every test uses everything
+
+use server::*;
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// how long anything is waited for before failing
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A map without obstacles (see `maps/open.ron`).
+pub const OPEN_MAP: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/maps/open.ron");
+
+/// Poll a condition until it holds (`false` once timed out).
+pub fn eventually(mut f: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + TIMEOUT;
+    while !f() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        sleep(MILISECOND);
+    }
+    true
+}
+
+/// An empty directory of the test, removed (with whatever was written to it) once dropped.
+pub struct ScratchDir(PathBuf);
+
+impl Deref for ScratchDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for ScratchDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+pub fn scratch_dir(name: &str) -> ScratchDir {
+    let dir = std::env::temp_dir().join(format!("blazed-{}-{name}", std::process::id()));
+    _ = std::fs::remove_dir_all(&dir);
+    ScratchDir(dir)
+}
+
+fn config(args: &[&str]) -> Config {
+    let mut all = vec!["server", "-t", "127.0.0.1:0", "-u", "127.0.0.1:0"];
+    all.extend(["--map", OPEN_MAP, "--seed", "0"]);
+    all.extend(args);
+    Config::parse_from(all).expect("invalid options")
+}
+
+/// A server shut down once dropped.
+pub struct TestServer {
+    handle: Option<ServerHandle>,
+    net: Option<MemNet>, // that everyone is connected over (rather than real sockets)
+}
+
+impl TestServer {
+    /// Start a server on the open map with any additional options.
+    pub fn start(args: &[&str]) -> Self {
+        let handle = ServerHandle::start(config(args)).expect("failed to start the server");
+        Self {
+            handle: Some(handle),
+            net: None,
+        }
+    }
+
+    /// Start a server within an in-memory network, as [`TestServer::start`] does.
+    pub fn start_in(net: &MemNet, args: &[&str]) -> Self {
+        let handle = ServerHandle::start_in(config(args), net).expect("failed to start the server");
+        Self {
+            handle: Some(handle),
+            net: Some(net.clone()),
+        }
+    }
+
+    pub fn handle(&self) -> &ServerHandle {
+        self.handle.as_ref().unwrap()
+    }
+
+    pub fn connect(&self, name: &str) -> TestClient {
+        self.try_connect(name).expect("failed to join")
+    }
+
+    pub fn try_connect(&self, name: &str) -> BlazedResult<TestClient> {
+        let hello = ClientHandshake {
+            name: Name::new(name)?,
+            color: Default::default(),
+        };
+        let handle = self.handle();
+        let (tcp_addr, udp_addr) = (handle.tcp_addr(), handle.udp_addr());
+        let conn = match &self.net {
+            Some(net) => {
+                let tcp = MemStream::connect(net, tcp_addr)?;
+                let udp = MemUdp::bind(net, "127.0.0.1:0".parse().unwrap())?;
+                Connection::over(tcp, udp, udp_addr, hello, Clock::default())?
+            }
+            None => Connection::connect(tcp_addr, udp_addr, None, hello, Clock::default())?,
+        };
+
+        Ok(TestClient {
+            conn,
+            players: HashMap::new(),
+            rtts: Vec::new(),
+            reason: None,
+        })
+    }
+
+    /// Execute an administrative command.
+    pub fn admin(&self, line: &str) -> Result<String> {
+        self.handle().admin().run(line)
+    }
+
+    /// A player, as the server sees them.
+    pub fn player(&self, id: Id) -> Option<UptObj> {
+        self.handle().players().into_iter().find(|p| p.id == id)
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.handle().players().len()
+    }
+
+    pub fn shutdown(mut self) -> Result {
+        self.handle.take().unwrap().shutdown()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            _ = handle.shutdown();
+        }
+    }
+}
+
+/// A headless client that keeps track of every player it has been told about.
+pub struct TestClient {
+    conn: Connection,
+    pub players: HashMap<Id, UptObj>,
+    pub rtts: Vec<Duration>,                  // of every clock sync
+    pub reason: Option<Option<RejectReason>>, // once disconnected (and why, if told)
+}
+
+impl TestClient {
+    pub fn id(&self) -> Id {
+        self.conn.id()
+    }
+
+    /// This client's player, as last told by the server.
+    pub fn me(&self) -> Option<&UptObj> {
+        self.players.get(&self.id())
+    }
+
+    pub fn press(&mut self, keys: Keys) {
+        self.conn.send(Input::key(keys, true)).unwrap();
+    }
+
+    pub fn release(&mut self, keys: Keys) {
+        self.conn.send(Input::key(keys, false)).unwrap();
+    }
+
+    /// Handle events until the condition holds (`false` once timed out).
+    pub fn wait_until(&mut self, f: impl Fn(&Self) -> bool) -> bool {
+        let deadline = Instant::now() + TIMEOUT;
+
+        while !f(self) {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.conn.events().recv_timeout(timeout) {
+                Ok(event) => self.apply(event),
+                Err(_) => return f(self),
+            }
+        }
+        true
+    }
+
+    fn apply(&mut self, event: NetEvent) {
+        match event {
+            NetEvent::Joined { players, .. } => {
+                self.players.extend(players.into_iter().map(|p| (p.id, p)));
+            }
+            NetEvent::Added(data) => {
+                self.players.insert(data.id, data);
+            }
+            NetEvent::Updated(mut data) => {
+                if let Some(player) = self.players.get_mut(&data.id) {
+                    player.cam.patch(&mut data.cam);
+                }
+            }
+            NetEvent::Removed(id) => {
+                self.players.remove(&id);
+            }
+            NetEvent::Respawned { id, pos } => {
+                if let Some(player) = self.players.get_mut(&id) {
+                    player.cam.respawn(pos);
+                }
+            }
+            NetEvent::Synced(sample) => self.rtts.push(Duration::from_micros(sample.rtt())),
+            NetEvent::Disconnected(reason) => self.reason = Some(reason),
+            NetEvent::Movement(_) | NetEvent::Notice(_) | NetEvent::Snapshot(_) => (),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use common::*;
+use server::*;
+use std::{thread::sleep, time::Duration};
+use ultraviolet::Vec3;
+
+/// A player placed (as the server would) at the position.
+fn player(id: Id, pos: Vec3) -> UptObj {
+    UptObj {
+        id,
+        kind: ObjType::Player,
+        dim: Vec3::new(1.0, 1.0, 1.0),
+        cam: CameraAttr::new(pos),
+        ..Default::default()
+    }
+}
+
+/// Inputs of two players walking (and turning) about the demo scene, by tick.
+fn script(tick: u64) -> Vec<(Id, Input)> {
+    let [a, b] = [PLAYER_IDS.start, PLAYER_IDS.start + 1];
+    let turn = Input::Motion(MotionOpt {
+        xrel: Some(7),
+        yrel: Some(-2),
+        seq: 0,
+    });
+    match tick {
+        0 => vec![
+            (a, Input::key(Keys::W, true)),
+            (b, Input::key(Keys::A, true)),
+        ],
+        150 => vec![(a, turn), (b, Input::key(Keys::SPACE, true))],
+        300 => vec![(a, Input::key(Keys::W, false)), (b, turn)],
+        450 => vec![(b, Input::key(Keys::A | Keys::SPACE, false))],
+        _ => Vec::new(),
+    }
+}
+
+/// Play the script from the start, returning the hash after every tick.
+fn run(ticks: u64, script: impl Fn(u64) -> Vec<(Id, Input)>) -> Vec<u64> {
+    let mut sim = Simulation::new(&World::demo(7), Movement::walking());
+    sim.join(player(PLAYER_IDS.start, Vec3::new(0.0, 0.0, 0.0)));
+    sim.join(player(PLAYER_IDS.start + 1, Vec3::new(2.0, 0.0, 2.0)));
+
+    (0..ticks)
+        .map(|tick| {
+            for (id, input) in script(tick) {
+                sim.input(id, input);
+            }
+            sim.step()
+        })
+        .collect()
+}
+
+#[test]
+fn same_inputs_hash_the_same() {
+    let first = run(600, script);
+    let second = run(600, script);
+    assert_eq!(first, second);
+
+    // the hashes follow the game (rather than being constant)
+    assert!(first.windows(2).filter(|w| w[0] != w[1]).count() > 100);
+
+    // any other input diverges from then on
+    let other = run(600, |tick| match tick {
+        300 => Vec::new(),
+        _ => script(tick),
+    });
+    assert_eq!(first[..300], other[..300]);
+    assert!(first[300..].iter().zip(&other[300..]).all(|(a, b)| a != b));
+}
+
+#[test]
+fn recording_reproduces_the_server() {
+    let dir = scratch_dir("determinism");
+    let server = TestServer::start(&["--record", dir.to_str().unwrap()]);
+    let mut alice = server.connect("alice");
+    let mut bob = server.connect("bob");
+    let bob_id = bob.id();
+    assert!(alice.wait_until(|c| c.me().is_some()));
+    assert!(bob.wait_until(|c| c.me().is_some()));
+
+    alice.press(Keys::W | Keys::D);
+    bob.press(Keys::S);
+    sleep(Duration::from_millis(150));
+    bob.release(Keys::S);
+    let pos = Vec3::new(3.0, 10.0, -3.0);
+    let admin = server.handle().admin();
+    admin.execute(Command::Teleport(bob_id, pos)).unwrap();
+    sleep(Duration::from_millis(150));
+    alice.release(Keys::W | Keys::D);
+    assert!(eventually(|| {
+        server
+            .handle()
+            .players()
+            .iter()
+            .all(|p| p.cam.velocity == Default::default())
+    }));
+    drop((alice, bob));
+    assert!(eventually(|| server.player_count() == 0));
+    server.shutdown().unwrap();
+
+    let path = replay_files(&dir).unwrap().remove(0);
+    let mut reader = ReplayReader::open(&path).unwrap();
+    let start = reader.start().clone();
+    let mut sim = Simulation::new(&start.world, start.movement);
+    for &player in start.players.values() {
+        sim.join(player);
+    }
+    let frames = reader.by_ref().collect::<BlazedResult<Vec<_>>>().unwrap();
+    assert!(frames.iter().any(|f| f.tick > 1));
+    let teleported = frames
+        .iter()
+        .flat_map(|f| &f.events)
+        .any(|e| matches!(*e, ReplayEvent::Respawned { id, pos: at } if id == bob_id && at == pos));
+    assert!(teleported);
+
+    assert!(frames.windows(2).all(|w| w[0].tick < w[1].tick));
+
+    for frame in &frames {
+        // ticks without frames changed nothing that's recorded
+        while sim.tick() + 1 < frame.tick {
+            sim.step();
+        }
+
+        // whatever happened since the last tick, then the tick itself
+        for event in &frame.events {
+            apply(&mut sim, event);
+        }
+        assert_eq!(sim.step(), frame.hash, "at tick {}", frame.tick);
+    }
+}
+
+/// Apply a recorded event to the simulation (its effects on the state are what's checked).
+fn apply(sim: &mut Simulation, event: &ReplayEvent) {
+    match *event {
+        ReplayEvent::Joined(obj) => sim.join(obj),
+        ReplayEvent::Left(id) => sim.leave(id),
+        ReplayEvent::Input { id, input } => sim.input(id, input),
+        ReplayEvent::Respawned { id, pos } => sim.respawn(id, pos),
+        ReplayEvent::Movement(movement) => sim.set_movement(movement),
+        ReplayEvent::State(_) => (),
+    }
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use common::*;
+use server::*;
+use std::{
+    net::SocketAddr,
+    thread::{sleep, spawn},
+    time::Duration,
+};
+
+#[test]
+fn plays_entirely_in_memory() {
+    let net = MemNet::new(Link::default());
+    let server = TestServer::start_in(&net, &[]);
+    let mut alice = server.connect("alice");
+    let mut bob = server.connect("bob");
+    let bob_id = bob.id();
+    assert!(alice.wait_until(|c| c.players.contains_key(&bob_id)));
+
+    // input over datagrams, updates back over them
+    bob.press(Keys::W);
+    assert!(alice.wait_until(|c| c.players[&bob_id].cam.velocity != Default::default()));
+    bob.release(Keys::W);
+    assert!(bob.wait_until(|c| {
+        c.me()
+            .is_some_and(|me| me.cam.velocity == Default::default())
+    }));
+
+    drop(bob);
+    assert!(alice.wait_until(|c| !c.players.contains_key(&bob_id)));
+    assert!(eventually(|| server.player_count() == 1));
+}
+
+#[test]
+fn latency_delays_both_ways() {
+    let latency = Duration::from_millis(25);
+    let net = MemNet::new(Link {
+        latency,
+        ..Default::default()
+    });
+    let server = TestServer::start_in(&net, &[]);
+    let mut client = server.connect("far");
+
+    assert!(client.wait_until(|c| !c.rtts.is_empty()));
+    assert!(client.rtts[0] >= latency * 2, "{:?}", client.rtts[0]);
+}
+
+#[test]
+fn input_waits_for_the_clock() {
+    let net = MemNet::manual(Link::default());
+    let server = TestServer::start_in(&net, &[]);
+    let mut client = server.connect("held");
+    let id = client.id();
+
+    // in flight until the network's clock has moved
+    net.set_link(Link {
+        latency: Duration::from_millis(50),
+        ..Default::default()
+    });
+    client.press(Keys::W);
+    sleep(Duration::from_millis(100));
+    assert!(server.player(id).unwrap().keys.is_empty());
+
+    net.advance(Duration::from_millis(49));
+    sleep(Duration::from_millis(100));
+    assert!(server.player(id).unwrap().keys.is_empty());
+
+    net.advance(Duration::from_millis(1));
+    assert!(eventually(|| server.player(id).unwrap().keys == Keys::W));
+}
+
+#[test]
+fn loss_spares_streams() {
+    let net = MemNet::new(Link::default());
+    let server = TestServer::start_in(&net, &[]);
+    let mut client = server.connect("lossy");
+    let id = client.id();
+
+    net.set_link(Link {
+        loss: 1.0,
+        ..Default::default()
+    });
+    client.press(Keys::W);
+    sleep(Duration::from_millis(100));
+    assert!(server.player(id).unwrap().keys.is_empty());
+
+    server.admin(&format!("kick {id}")).unwrap();
+    assert!(client.wait_until(|c| c.reason == Some(Some(RejectReason::Kicked))));
+}
+
+#[test]
+fn handshake_is_retried_through_loss() {
+    let net = MemNet::new(Link {
+        loss: 0.75,
+        ..Default::default()
+    });
+    let server = TestServer::start_in(&net, &[]);
+
+    for name in ["first", "second", "third"] {
+        server.connect(name);
+    }
+}
+
+/// Admit a client by hand (without a game), returning it with the server's ends of its session.
+fn admit_by_hand(net: &MemNet) -> (Connection, MemStream, MemUdp, SocketAddr) {
+    let tcp_addr: SocketAddr = "127.0.0.1:7000".parse().unwrap();
+    let listener = MemListener::bind(net, tcp_addr).unwrap();
+    let udp = MemUdp::bind(net, "127.0.0.1:7001".parse().unwrap()).unwrap();
+    let udp_addr = udp.local_addr().unwrap();
+
+    let client = {
+        let net = net.clone();
+        spawn(move || {
+            let hello = ClientHandshake {
+                name: Name::new("client").unwrap(),
+                color: Default::default(),
+            };
+            let tcp = MemStream::connect(&net, tcp_addr).unwrap();
+            let udp = MemUdp::bind(&net, "127.0.0.1:0".parse().unwrap()).unwrap();
+            Connection::over(tcp, udp, udp_addr, hello, Clock::default()).unwrap()
+        })
+    };
+
+    let (tcp, _) = listener.accept().unwrap();
+    let mut buf = [0; PACKET_SIZE];
+    assert!(tcp.recv(&mut buf).unwrap() > 0);
+    tcp.send(&ServerHandshake::new(1).serialize()).unwrap();
+    let (_, client_udp) = udp.recv_from(&mut buf).unwrap();
+    tcp.send(&Movement::default().serialize()).unwrap();
+    tcp.send(&Flush::serialize()).unwrap();
+    (client.join().unwrap(), tcp, udp, client_udp)
+}
+
+/// Wait for the session to end, returning why.
+fn disconnected(conn: &Connection) -> Option<RejectReason> {
+    loop {
+        match conn.events().recv_timeout(TIMEOUT) {
+            Ok(NetEvent::Disconnected(reason)) => return reason,
+            Ok(_) => (),
+            Err(e) => panic!("still connected: {e}"),
+        }
+    }
+}
+
+#[test]
+fn malformed_packets_end_the_session() {
+    // over the stream
+    let net = MemNet::new(Link::default());
+    let (conn, tcp, _udp, _) = admit_by_hand(&net);
+    tcp.send(&[Notice::ID, 1]).unwrap();
+    assert_eq!(disconnected(&conn), None);
+    assert!(conn.is_closed());
+
+    // over datagrams
+    let net = MemNet::new(Link::default());
+    let (conn, _tcp, udp, client_udp) = admit_by_hand(&net);
+    udp.send_to(&[Snapshot::ID, 1, 2, 3], client_udp).unwrap();
+    assert_eq!(disconnected(&conn), None);
+}
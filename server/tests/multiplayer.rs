@@ -0,0 +1,177 @@
+mod common;
+
+use common::*;
+use server::*;
+use std::{thread::sleep, time::Duration};
+
+#[test]
+fn joins_and_leaves_propagate() {
+    let server = TestServer::start(&[]);
+    let mut alice = server.connect("alice");
+    let bob = server.connect("bob");
+    let bob_id = bob.id();
+
+    assert!(alice.wait_until(|c| {
+        c.players
+            .get(&bob_id)
+            .is_some_and(|p| p.name.as_str() == "bob")
+    }));
+    assert_eq!(server.player_count(), 2);
+
+    drop(bob);
+    assert!(alice.wait_until(|c| !c.players.contains_key(&bob_id)));
+    assert!(eventually(|| server.player_count() == 1));
+}
+
+#[test]
+fn duplicate_names_are_distinguished() {
+    let server = TestServer::start(&[]);
+    let mut first = server.connect("twin");
+    let second = server.connect("twin");
+    let id = second.id();
+
+    assert!(first.wait_until(|c| c.players.contains_key(&id)));
+    assert_ne!(first.me().unwrap().name, first.players[&id].name);
+}
+
+#[test]
+fn duplicate_multibyte_names_are_distinguished() {
+    let long = "語".repeat(10);
+    let server = TestServer::start(&[]);
+    let mut first = server.connect(&long);
+    let second = server.connect(&long);
+    let id = second.id();
+
+    assert!(first.wait_until(|c| c.players.contains_key(&id)));
+    assert_eq!(first.me().unwrap().name.as_str(), long);
+    assert_eq!(
+        first.players[&id].name.as_str(),
+        format!("{}#2", "語".repeat(9))
+    );
+}
+
+#[test]
+fn input_moves_player_by_whole_ticks() {
+    let server = TestServer::start(&[]);
+    let mut client = server.connect("mover");
+    let id = client.id();
+    assert!(client.wait_until(|c| c.me().is_some()));
+    let spawn = server.player(id).unwrap().cam.eye;
+
+    client.press(Keys::W);
+    sleep(Duration::from_millis(300));
+    client.release(Keys::W);
+
+    // once at rest, the client has been told exactly where the server put it
+    assert!(eventually(
+        || server.player(id).unwrap().cam.velocity == Default::default()
+    ));
+    let eye = server.player(id).unwrap().cam.eye;
+    assert!(client.wait_until(|c| c.me().unwrap().cam.eye == eye));
+
+    // straight ahead (the initial target), by a whole number of steps
+    let moved = eye - spawn;
+    assert!(moved.z < 0.0 && moved.x.abs() < 1e-4 && moved.y.abs() < 1e-4);
+
+    let step = Movement::free_fly().speed * GAME_SPEED.as_secs_f32();
+    let ticks = moved.mag() / step;
+    assert!(ticks >= 1.0, "moved {ticks} ticks");
+    assert!((ticks - ticks.round()).abs() < 1e-2, "moved {ticks} ticks");
+}
+
+#[test]
+fn kicked_player_can_rejoin() {
+    let server = TestServer::start(&[]);
+    let mut client = server.connect("kicked");
+    let id = client.id();
+
+    server.admin(&format!("kick {id}")).unwrap();
+    assert!(client.wait_until(|c| c.reason == Some(Some(RejectReason::Kicked))));
+    assert!(eventually(|| server.player_count() == 0));
+
+    let mut client = server.connect("kicked");
+    assert!(client.wait_until(|c| c.me().is_some()));
+    assert_eq!(server.player_count(), 1);
+}
+
+#[test]
+fn full_server_rejects_players() {
+    let server = TestServer::start(&["--max-players", "1"]);
+    let _first = server.connect("first");
+
+    let Err(e) = server.try_connect("second") else {
+        panic!("joined a full server");
+    };
+    assert!(e.to_string().contains("server full"), "{e}");
+}
+
+#[test]
+fn shutdown_tells_players() {
+    let server = TestServer::start(&[]);
+    let mut client = server.connect("player");
+
+    server.shutdown().unwrap();
+    assert!(client.wait_until(|c| c.reason.is_some()));
+    assert_eq!(client.reason, Some(Some(RejectReason::ShuttingDown)));
+}
+
+#[test]
+fn shutdown_closes_the_admin_socket() {
+    // a free port (the handle doesn't tell which one the socket was given)
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .unwrap();
+    let server = TestServer::start(&["--admin-addr", &addr.to_string(), "--admin-secret", "s"]);
+    let mut admin = ControlClient::connect(addr, "s").unwrap();
+    assert!(admin.send(&Command::Status).unwrap().is_ok());
+
+    // no longer accepting anyone once stopped
+    server.shutdown().unwrap();
+    assert!(std::net::TcpStream::connect(addr).is_err());
+}
+
+#[test]
+fn server_collides_with_the_world_it_sends() {
+    let server = TestServer::start(&["--movement", "walking"]);
+    let game = server.handle().game();
+    assert!(!game.world().is_empty());
+    assert_eq!(game.collider().len(), game.world().len());
+
+    // players land on the floor rather than falling through it
+    let mut client = server.connect("walker");
+    let id = client.id();
+    assert!(client.wait_until(|c| c.me().is_some()));
+    client.press(Keys::W);
+    client.release(Keys::W);
+    assert!(eventually(|| {
+        let cam = server.player(id).unwrap().cam;
+        cam.is_resting(&Movement::walking())
+    }));
+    let feet = server.player(id).unwrap().cam.bounds().min.y;
+    assert!((feet - -63.9).abs() < 1e-2, "standing at {feet}");
+}
+
+#[test]
+fn malformed_packets_are_dropped() {
+    use std::net::{TcpStream, UdpSocket};
+
+    let server = TestServer::start(&[]);
+    let mut client = server.connect("player");
+    assert!(client.wait_until(|c| c.me().is_some()));
+    let (tcp_addr, udp_addr) = (server.handle().tcp_addr(), server.handle().udp_addr());
+
+    // a truncated handshake, then bare packet ids from a stranger
+    let stream = TcpStream::connect(tcp_addr).unwrap();
+    stream.write_all(&[1, 0, ClientHandshake::ID]).unwrap();
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for id in [Keyboard::ID, Wheel::ID, MotionOpt::ID, ClientHandshake::ID] {
+        udp.send_to(&[id], udp_addr).unwrap();
+    }
+    client.press(Keys::W);
+    client.release(Keys::W);
+
+    // the server still serves everyone
+    let mut late = server.connect("late");
+    assert!(late.wait_until(|c| c.players.len() == 2));
+    assert_eq!(server.player_count(), 2);
+}
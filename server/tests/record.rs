@@ -0,0 +1,172 @@
+mod common;
+
+use common::*;
+use server::*;
+use std::{thread::sleep, time::Duration};
+
+/// Replay every frame of a file, returning the final state (and every frame).
+fn replay(path: &std::path::Path) -> (ReplayState, Vec<ReplayFrame>) {
+    let mut reader = ReplayReader::open(path).unwrap();
+
+    let mut state = reader.start().clone();
+    let frames = reader.by_ref().collect::<BlazedResult<Vec<_>>>().unwrap();
+    for frame in &frames {
+        state.apply(frame);
+    }
+    (state, frames)
+}
+
+#[test]
+fn replay_matches_the_server() {
+    let dir = scratch_dir("replay");
+    let server = TestServer::start(&["--record", dir.to_str().unwrap()]);
+    let mut client = server.connect("recorded");
+    let id = client.id();
+    assert!(client.wait_until(|c| c.me().is_some()));
+
+    client.press(Keys::W | Keys::D);
+    sleep(Duration::from_millis(200));
+    client.release(Keys::W | Keys::D);
+    assert!(eventually(
+        || server.player(id).unwrap().cam.velocity == Default::default()
+    ));
+    let eye = server.player(id).unwrap().cam.eye;
+    server.shutdown().unwrap();
+
+    let files = replay_files(&dir).unwrap();
+    assert_eq!(files.len(), 1);
+    let (state, frames) = replay(&files[0]);
+
+    // joined, moved by its (accepted) input and left, tick by tick
+    let events = frames.iter().flat_map(|f| &f.events).collect::<Vec<_>>();
+    assert!(matches!(events.first(), Some(ReplayEvent::Joined(obj)) if obj.id == id));
+    assert!(matches!(events.last(), Some(ReplayEvent::Left(left)) if *left == id));
+    let presses = events
+        .iter()
+        .filter(|e| {
+            matches!(
+                e,
+                ReplayEvent::Input {
+                    input: Input::Keyboard(_),
+                    ..
+                }
+            )
+        })
+        .count();
+    assert_eq!(presses, 2);
+    assert!(
+        frames
+            .windows(2)
+            .all(|w| w[0].tick < w[1].tick && w[0].time <= w[1].time)
+    );
+    assert!(frames.last().unwrap().tick > 1);
+    assert!(state.players.is_empty());
+
+    // where the server last had the player
+    let mut state = ReplayReader::open(&files[0]).unwrap().start().clone();
+    for frame in &frames[..frames.len() - 1] {
+        state.apply(frame);
+    }
+    assert_eq!(state.players[&id].cam.eye, eye);
+}
+
+#[test]
+fn files_rotate_within_limits() {
+    let dir = scratch_dir("rotate");
+    let server = TestServer::start(&[
+        "--record",
+        dir.to_str().unwrap(),
+        "--record-file-size",
+        "0.001",
+        "--record-max-files",
+        "3",
+    ]);
+    let mut client = server.connect("rotated");
+    let id = client.id();
+    assert!(client.wait_until(|c| c.me().is_some()));
+
+    client.press(Keys::W);
+    sleep(Duration::from_millis(300));
+    client.release(Keys::W);
+    assert!(eventually(
+        || server.player(id).unwrap().cam.velocity == Default::default()
+    ));
+    let eye = server.player(id).unwrap().cam.eye;
+    drop(client);
+    assert!(eventually(|| server.player_count() == 0));
+    server.shutdown().unwrap();
+
+    // only the newest files are kept, each playable on its own
+    let files = replay_files(&dir).unwrap();
+    assert_eq!(files.len(), 3);
+    for path in &files {
+        assert!(std::fs::metadata(path).unwrap().len() < 2 * 1024);
+    }
+    let reader = ReplayReader::open(&files[0]).unwrap();
+    assert!(reader.start().players.contains_key(&id));
+
+    // the player was last seen at rest where the server left it
+    let mut last = None;
+    for path in &files {
+        let mut reader = ReplayReader::open(path).unwrap();
+        let mut state = reader.start().clone();
+        for frame in reader.by_ref() {
+            state.apply(&frame.unwrap());
+            last = state.players.get(&id).map(|p| p.cam.eye).or(last);
+        }
+    }
+    assert_eq!(last, Some(eye));
+}
+
+#[test]
+fn truncated_files_end_early() {
+    let dir = scratch_dir("truncated");
+    let server = TestServer::start(&["--record", dir.to_str().unwrap()]);
+    let mut client = server.connect("truncated");
+    assert!(client.wait_until(|c| c.me().is_some()));
+    drop(client);
+    assert!(eventually(|| server.player_count() == 0));
+    server.shutdown().unwrap();
+
+    let path = replay_files(&dir).unwrap().remove(0);
+    let (_, frames) = replay(&path);
+    assert_eq!(frames.len(), 2);
+
+    // as if the server was killed partway through writing
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+    let (state, frames) = replay(&path);
+    assert!(frames.last().unwrap().events.is_empty());
+    assert_eq!(state.players.len(), 1);
+
+    // written by a build of another format
+    let mut other = bytes.clone();
+    other[REPLAY_MAGIC.len()..][..2].copy_from_slice(&(REPLAY_VERSION - 1).to_le_bytes());
+    std::fs::write(&path, &other).unwrap();
+    assert!(matches!(
+        ReplayReader::open(&path),
+        Err(BlazedError::Replay(ReplayError::Version(v))) if v == REPLAY_VERSION - 1
+    ));
+
+    std::fs::write(&path, b"not a replay").unwrap();
+    assert!(matches!(
+        ReplayReader::open(&path),
+        Err(BlazedError::Replay(ReplayError::NotReplay))
+    ));
+}
+
+#[test]
+fn servers_share_a_directory() {
+    // started within the same second
+    let dir = scratch_dir("shared");
+    let servers = [(); 2].map(|_| TestServer::start(&["--record", dir.to_str().unwrap()]));
+    for server in servers {
+        server.shutdown().unwrap();
+    }
+
+    let files = replay_files(&dir).unwrap();
+    assert_eq!(files.len(), 2);
+    for path in &files {
+        ReplayReader::open(path).unwrap();
+    }
+}
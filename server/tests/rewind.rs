@@ -0,0 +1,163 @@
+mod common;
+
+use common::*;
+use server::*;
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+use ultraviolet::Vec3;
+
+fn player(id: Id, eye: Vec3) -> UptObj {
+    UptObj {
+        id,
+        cam: CameraAttr::new(eye),
+        ..Default::default()
+    }
+}
+
+fn contains(aabb: Aabb, pos: Vec3) -> bool {
+    (0..3).all(|i| aabb.min[i] < pos[i] && pos[i] < aabb.max[i])
+}
+
+#[test]
+fn history_rewinds_within_limit() {
+    let [a, b] = [PLAYER_IDS.start, PLAYER_IDS.start + 1];
+    let ms = Duration::from_millis;
+    let max = Duration::from_secs(1);
+    let t0 = Instant::now();
+    let mut history = History::new();
+
+    // b walks away from in front of a, while a stays put
+    let shooter = player(a, Vec3::zero());
+    for (tick, z) in [(1, 0.0), (2, -5.0), (3, -10.0)] {
+        let target = player(b, Vec3::new(10.0, 0.0, z));
+        history.push(tick, t0 + ms(100 * tick), [&shooter, &target], max);
+    }
+    history.push(
+        4,
+        t0 + ms(300),
+        [&shooter, &player(b, Vec3::new(10.0, 0.0, -10.0))],
+        max,
+    );
+    assert_eq!(history.len(), 3); // nobody moved
+
+    let world = Collider::default();
+    let now = t0 + ms(300);
+    let past = history.rewind(t0 + ms(150), now, max, &world);
+    assert_eq!(past.tick(), 1);
+    assert!(contains(past.bounds(b).unwrap(), Vec3::new(10.0, 0.0, 0.0)));
+
+    // as a saw it back then, but not as it is now
+    let hit = past
+        .raycast(a, Vec3::zero(), Vec3::unit_x(), 100.0)
+        .unwrap();
+    assert_eq!(hit.id, b);
+    assert!((hit.point - Vec3::new(hit.dist, 0.0, 0.0)).mag() < 1e-4);
+    assert!(past.raycast(a, Vec3::zero(), Vec3::unit_x(), 5.0).is_none());
+    let present = history.rewind(now, now, max, &world);
+    assert_eq!(present.tick(), 3);
+    assert!(
+        present
+            .raycast(a, Vec3::zero(), Vec3::unit_x(), 100.0)
+            .is_none()
+    );
+
+    // shooters don't hit themselves, and walls stop rays
+    assert!(
+        past.raycast(a, Vec3::zero(), -Vec3::unit_x(), 100.0)
+            .is_none()
+    );
+    let wall = Collider::new([Aabb::new(
+        Vec3::new(4.0, -5.0, -5.0),
+        Vec3::new(5.0, 5.0, 5.0),
+    )]);
+    let blocked = history.rewind(t0 + ms(150), now, max, &wall);
+    assert!(
+        blocked
+            .raycast(a, Vec3::zero(), Vec3::unit_x(), 100.0)
+            .is_none()
+    );
+
+    let volume = Aabb::from_center(Vec3::new(10.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0));
+    assert_eq!(past.overlapping(&volume).collect::<Vec<_>>(), [b]);
+    assert_eq!(present.overlapping(&volume).count(), 0);
+
+    // no further back than the limit
+    let limited = history.rewind(t0, now, ms(50), &world);
+    assert_eq!(limited.tick(), 2);
+
+    // frames beyond the limit are forgotten (but the one as of the limit)
+    history.push(5, t0 + ms(400), [&shooter], ms(150));
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.rewind(t0, t0 + ms(400), max, &world).tick(), 2);
+}
+
+#[test]
+fn server_rewinds_to_the_view_of_a_lagging_player() {
+    let server = TestServer::start(&["--max-rewind", "2"]);
+    let mut client = server.connect("target");
+    let id = client.id();
+    assert!(client.wait_until(|c| c.me().is_some()));
+    sleep(Duration::from_millis(50));
+    let spawn = server.player(id).unwrap().cam.eye;
+    let seen = Instant::now();
+
+    client.press(Keys::W);
+    sleep(Duration::from_millis(300));
+    client.release(Keys::W);
+    assert!(eventually(
+        || server.player(id).unwrap().cam.velocity == Default::default()
+    ));
+    let eye = server.player(id).unwrap().cam.eye;
+    assert!((eye - spawn).mag() > 1.0);
+
+    // a shot from the side, at where the player was seen
+    let game = server.handle().game();
+    let origin = spawn + Vec3::new(5.0, 0.0, 0.0);
+    let lag = Lag {
+        rtt: seen.elapsed() - Duration::from_millis(20),
+        interp: Duration::from_millis(20),
+    };
+    let past = game.rewind(lag);
+    assert!(contains(past.bounds(id).unwrap(), spawn));
+    let hit = past.raycast(0, origin, -Vec3::unit_x(), 10.0).unwrap();
+    assert_eq!(hit.id, id);
+
+    // which misses the player as they are now
+    let present = game.rewind(Lag::default());
+    assert!(contains(present.bounds(id).unwrap(), eye));
+    assert!(present.raycast(0, origin, -Vec3::unit_x(), 10.0).is_none());
+}
+
+#[test]
+fn server_times_each_players_round_trip() {
+    let latency = Duration::from_millis(40);
+    let net = MemNet::new(Link {
+        latency,
+        ..Default::default()
+    });
+    let server = TestServer::start_in(&net, &["--max-rewind", "1"]);
+    let mut client = server.connect("distant");
+    let id = client.id();
+    assert!(client.wait_until(|c| c.me().is_some()));
+
+    // once a reply has been echoed back
+    let game = server.handle().game();
+    assert!(eventually(|| game.rtt(id).is_some()));
+    let lag = game.lag(id);
+    assert!(lag.rtt >= latency * 2 && lag.rtt < latency * 4, "{lag:?}");
+    assert_eq!(lag.interp, Duration::ZERO);
+
+    // the player is seen (where they are) from their own point of view
+    let spawn = server.player(id).unwrap().cam.eye;
+    let past = game.rewind_for(id);
+    assert!(past.time() <= Instant::now() - latency * 2);
+    assert!(contains(past.bounds(id).unwrap(), spawn));
+
+    // and nobody else is timed
+    assert!(game.rtt(id + 1).is_none());
+    drop(client);
+    assert!(eventually(|| server.player_count() == 0));
+    assert!(game.rtt(id).is_none());
+}
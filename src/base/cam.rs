@@ -5,7 +5,7 @@ use ultraviolet::{Mat4, projection::perspective_gl};
 use wopt::*;
 
 #[repr(transparent)]
-#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
 pub struct Axis([f32; 3]);
 
 impl Axis {
@@ -54,28 +54,127 @@ impl SubAssign<i32> for Axis {
     }
 }
 
+/// Server-wide movement rules (shared by the simulation and client prediction).
+#[derive(Clone, Copy, Debug, PartialEq, WithOpt)]
+#[wopt(derive(Clone, Copy, Debug, Default))]
+pub struct Movement {
+    pub mode: MoveMode,
+    pub speed: f32,          // horizontal (and flying) velocity (units/sec)
+    pub fov_min: f32,        // narrowest field-of-vision (degrees)
+    pub fov_max: f32,        // widest field-of-vision (degrees)
+    pub gravity: f32,        // downwards acceleration (units/sec²)
+    pub jump_speed: f32,     // initial upwards velocity of a jump (units/sec)
+    pub terminal_speed: f32, // maximum falling velocity (units/sec)
+    pub crouch_speed: f32,   // speed multiplier while crouched
+    pub air_control: f32,    // rate (1/sec) at which airborne velocity follows input
+}
+
+impl Movement {
+    pub const fn free_fly() -> Self {
+        Self {
+            mode: MoveMode::FreeFly,
+            ..Self::walking()
+        }
+    }
+
+    pub const fn walking() -> Self {
+        Self {
+            mode: MoveMode::Walking,
+            speed: 8.0,
+            fov_min: 30.0,
+            fov_max: 110.0,
+            gravity: 24.0,
+            jump_speed: 8.0,
+            terminal_speed: 64.0,
+            crouch_speed: 0.4,
+            air_control: 2.0,
+        }
+    }
+
+    /// Movement rules by mode name (`free-fly` or `walking`).
+    pub fn parse(s: &str) -> BlazedResult<Self> {
+        match s {
+            "free-fly" => Ok(Self::free_fly()),
+            "walking" => Ok(Self::walking()),
+            _ => Err(format!("Unknown movement mode '{s}' (expected free-fly or walking).").into()),
+        }
+    }
+
+    pub const fn is_walking(&self) -> bool {
+        self.mode.contains(MoveMode::Walking)
+    }
+
+    pub fn validate(&self) -> BlazedResult {
+        let fields = [
+            self.speed,
+            self.fov_min,
+            self.fov_max,
+            self.gravity,
+            self.jump_speed,
+            self.terminal_speed,
+            self.crouch_speed,
+            self.air_control,
+        ];
+        if !fields.iter().all(|x| x.is_finite() && *x >= 0.0) {
+            return Err("Movement parameters must be finite and not negative.".into());
+        }
+        if self.speed == 0.0 || self.terminal_speed == 0.0 {
+            return Err("Movement speeds must be greater than zero.".into());
+        }
+        if !(1.0 <= self.fov_min && self.fov_min <= self.fov_max && self.fov_max < 180.0) {
+            return Err(
+                "Field-of-vision limits must be within [1, 180) degrees (min <= max).".into(),
+            );
+        }
+        if self.crouch_speed > 1.0 {
+            return Err("Crouch speed must be within [0.0, 1.0].".into());
+        }
+        Ok(())
+    }
+}
+
+impl Default for Movement {
+    fn default() -> Self {
+        Self::free_fly()
+    }
+}
+
 #[derive(Clone, Copy, Debug, WithOpt)]
 #[wopt(derive(Clone, Copy, Debug, Default))]
 pub struct CameraAttr {
-    pub fov: f32,     // client's (player) field-of-vision
-    pub speed: f32,   // player speed
-    pub yaw: Axis,    // player's camera yaw   (used in rotation)
-    pub pitch: Axis,  // player's camera pitch (used in rotation)
-    pub eye: Vec3,    // player's position
-    pub target: Vec3, // player's camera (target) vector
-    pub up: Vec3,     // player's camera (up) vector
+    pub fov: f32,         // client's (player) field-of-vision
+    pub yaw: Axis,        // player's camera yaw   (used in rotation)
+    pub pitch: Axis,      // player's camera pitch (used in rotation)
+    pub eye: Vec3,        // player's position
+    pub velocity: Vec3,   // player's velocity (units/sec)
+    pub target: Vec3,     // player's camera (target) vector
+    pub up: Vec3,         // player's camera (up) vector
+    pub state: MoveState, // player's grounded/crouched state
 }
 
 impl CameraAttr {
+    /// half-width of the player's body
+    pub const HALF_WIDTH: f32 = 0.4;
+
+    /// distance from the eye down to the player's feet
+    pub const EYE_HEIGHT: f32 = 1.6;
+
+    /// distance from the eye down to the player's feet (while crouched)
+    pub const CROUCH_HEIGHT: f32 = 1.0;
+
+    /// distance from the eye up to the top of the player's head
+    pub const HEAD_ROOM: f32 = 0.2;
+
     pub fn new(pos: Vec3) -> Self {
         let mut attr = Self {
             fov: 90.0,
-            speed: 0.024,
             yaw: Axis::new(0.052),
             pitch: Axis::new(0.050),
             eye: pos,
+            velocity: Vec3::zero(),
             target: Vec3::new(0.0, 0.0, -1.0),
             up: Vec3::new(0.0, 1.0, 0.0),
+            state: MoveState::empty(),
         };
 
         // initial setup
@@ -84,8 +183,16 @@ impl CameraAttr {
         attr
     }
 
-    pub fn upt_fov(&mut self, precise_y: f32) {
-        self.fov = clamp_unchecked(self.fov - precise_y, 30.0, 110.0);
+    /// Reset everything (but the field-of-vision) at a new position.
+    pub fn respawn(&mut self, pos: Vec3) {
+        *self = Self {
+            fov: self.fov,
+            ..Self::new(pos)
+        };
+    }
+
+    pub fn upt_fov(&mut self, precise_y: f32, movement: &Movement) {
+        self.fov = clamp_unchecked(self.fov - precise_y, movement.fov_min, movement.fov_max);
     }
 
     pub fn look_at(&mut self, xrel: i32, yrel: i32) {
@@ -118,23 +225,151 @@ impl CameraAttr {
         self.target.normalize();
     }
 
-    pub fn input(&mut self, kb: Keys) {
+    /// distance from the eye down to the player's feet (in the current stance)
+    pub fn eye_height(&self) -> f32 {
+        if self.state.contains(MoveState::Crouched) {
+            Self::CROUCH_HEIGHT
+        } else {
+            Self::EYE_HEIGHT
+        }
+    }
+
+    /// The player's collision box.
+    pub fn bounds(&self) -> Aabb {
+        let w = Self::HALF_WIDTH;
+        Aabb::new(
+            self.eye - Vec3::new(w, self.eye_height(), w),
+            self.eye + Vec3::new(w, Self::HEAD_ROOM, w),
+        )
+    }
+
+    /// Advance the player by a single game step.
+    pub fn input(&mut self, kb: Keys, world: &Collider, movement: &Movement) {
+        if movement.is_walking() {
+            self.walk(kb, world, movement)
+        } else {
+            self.fly(kb, world, movement)
+        }
+    }
+
+    fn fly(&mut self, kb: Keys, world: &Collider, movement: &Movement) {
+        let speed = movement.speed * GAME_SPEED.as_secs_f32();
+
         let mut target = self.target;
         target.y = 0.0;
 
+        // total displacement of this step
+        let mut step = Vec3::zero();
+
         for key in kb.iter() {
             match key {
-                Keys::W => self.eye += target.normalized() * self.speed,
-                Keys::A => self.eye -= target.cross(self.up).normalized() * self.speed,
-                Keys::S => self.eye -= target.normalized() * self.speed,
-                Keys::D => self.eye += target.cross(self.up).normalized() * self.speed,
+                Keys::W => step += target.normalized() * speed,
+                Keys::A => step -= target.cross(self.up).normalized() * speed,
+                Keys::S => step -= target.normalized() * speed,
+                Keys::D => step += target.cross(self.up).normalized() * speed,
+
+                Keys::SPACE => step += self.up * speed,
+                Keys::SHIFT => step -= self.up * speed,
+
+                _ => (),
+            }
+        }
+
+        // slide along any obstructing geometry
+        let step = world.slide(self.bounds(), step);
+
+        self.eye += step;
+        self.velocity = step / GAME_SPEED.as_secs_f32();
+        self.state = MoveState::empty();
+    }
 
-                Keys::SPACE => self.eye += self.up * self.speed,
-                Keys::SHIFT => self.eye -= self.up * self.speed,
+    fn walk(&mut self, kb: Keys, world: &Collider, movement: &Movement) {
+        let dt = GAME_SPEED.as_secs_f32();
 
+        self.crouch(kb.contains(Keys::CTRL), world);
+
+        let mut forward = self.target;
+        forward.y = 0.0;
+        forward.normalize();
+        let right = forward.cross(self.up).normalized();
+
+        // horizontal direction the player wants to go
+        let mut wish = Vec3::zero();
+        for key in kb.iter() {
+            match key {
+                Keys::W => wish += forward,
+                Keys::A => wish -= right,
+                Keys::S => wish -= forward,
+                Keys::D => wish += right,
                 _ => (),
             }
         }
+        if wish != Vec3::zero() {
+            wish.normalize();
+        }
+
+        let mut speed = movement.speed;
+        if self.state.contains(MoveState::Crouched) {
+            speed *= movement.crouch_speed;
+        }
+
+        let is_grounded = self.state.contains(MoveState::Grounded);
+        let desired = wish * speed;
+
+        // full control on the ground, limited control in the air
+        let mut velocity = if is_grounded {
+            desired
+        } else {
+            let horizontal = Vec3::new(self.velocity.x, 0.0, self.velocity.z);
+            let control = (movement.air_control * dt).min(1.0);
+            horizontal + (desired - horizontal) * control
+        };
+
+        velocity.y = if is_grounded && kb.contains(Keys::SPACE) {
+            movement.jump_speed
+        } else {
+            self.velocity.y
+        };
+        velocity.y = (velocity.y - movement.gravity * dt).max(-movement.terminal_speed);
+
+        // slide along any obstructing geometry
+        let step = world.slide(self.bounds(), velocity * dt);
+
+        self.eye += step;
+        self.velocity = step / dt;
+        self.state
+            .set(MoveState::Grounded, world.is_supported(&self.bounds()));
+
+        // standing still on the ground
+        if self.state.contains(MoveState::Grounded) && self.velocity.y < 0.0 {
+            self.velocity.y = 0.0;
+        }
+    }
+
+    /// Crouch (lowering the eye to the feet) or stand back up if there's room.
+    fn crouch(&mut self, is_crouching: bool, world: &Collider) {
+        let diff = Self::EYE_HEIGHT - Self::CROUCH_HEIGHT;
+
+        if is_crouching && !self.state.contains(MoveState::Crouched) {
+            self.state.insert(MoveState::Crouched);
+            self.eye.y -= diff;
+        } else if !is_crouching && self.state.contains(MoveState::Crouched) {
+            let mut standing = *self;
+            standing.state.remove(MoveState::Crouched);
+            standing.eye.y += diff;
+
+            if world.is_clear(&standing.bounds()) {
+                *self = standing;
+            }
+        }
+    }
+
+    /// the player isn't moving (nothing left to simulate)
+    pub fn is_resting(&self, movement: &Movement) -> bool {
+        // walking players may still fall, and crouched ones stand up once there's room
+        self.velocity == Vec3::zero()
+            && (!movement.is_walking() || self.state.contains(MoveState::Grounded))
+            && !self.state.contains(MoveState::Crouched)
     }
 }
 
@@ -150,6 +385,7 @@ pub struct RawCamera {
     view: Mat4,
     projection: Mat4,
     aspect_ratio: f32,
+    movement: Movement,
 }
 
 impl RawCamera {
@@ -178,8 +414,19 @@ impl RawCamera {
         &self.attr.eye
     }
 
+    pub const fn movement(&self) -> &Movement {
+        &self.movement
+    }
+
+    pub fn set_movement(&mut self, movement: Movement) {
+        self.movement = movement;
+    }
+
     pub fn reset(&mut self) {
-        *self = Self::init(self.aspect_ratio);
+        *self = Self {
+            movement: self.movement,
+            ..Self::init(self.aspect_ratio)
+        };
     }
 
     pub fn upt_aspect_ratio(&mut self, w: i32, h: i32) {
@@ -189,7 +436,7 @@ impl RawCamera {
     }
 
     pub fn upt_fov(&mut self, precise_y: f32) {
-        self.attr.upt_fov(precise_y);
+        self.attr.upt_fov(precise_y, &self.movement);
         self.projection = Self::perspective(self.attr.fov, self.aspect_ratio);
         self.upt();
     }
@@ -199,11 +446,20 @@ impl RawCamera {
         self.upt();
     }
 
-    pub fn input(&mut self, kb: Keys) {
-        self.attr.input(kb);
+    pub fn input(&mut self, kb: Keys, world: &Collider) {
+        self.attr.input(kb, world, &self.movement);
         self.upt();
     }
 
+    pub fn respawn(&mut self, pos: Vec3) {
+        self.attr.respawn(pos);
+        self.upt();
+    }
+
+    pub fn is_resting(&self) -> bool {
+        self.attr.is_resting(&self.movement)
+    }
+
     pub fn replace(&mut self, attr: CameraAttr) {
         self.attr = attr;
         self.upt();
@@ -231,6 +487,7 @@ impl RawCamera {
             view,
             projection,
             aspect_ratio,
+            movement: Default::default(),
         };
 
         // initial setup
@@ -244,3 +501,63 @@ impl RawCamera {
         perspective_gl(fov * RADIAN, aspect_ratio, 0.01, 10_000.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A floor whose top is at zero (with a low ceiling, if asked for).
+    fn room(has_ceiling: bool) -> Collider {
+        let floor = Aabb::new(Vec3::new(-8.0, -1.0, -8.0), Vec3::new(8.0, 0.0, 8.0));
+        let ceiling = Aabb::new(Vec3::new(-8.0, 1.2, -8.0), Vec3::new(8.0, 2.0, 8.0));
+        match has_ceiling {
+            true => Collider::new([floor, ceiling]),
+            false => Collider::new([floor]),
+        }
+    }
+
+    /// Step with the keys held until at rest, returning the steps taken.
+    fn settle(cam: &mut CameraAttr, keys: Keys, world: &Collider) -> usize {
+        let movement = Movement::walking();
+        for steps in 0..1000 {
+            if keys.is_empty() && cam.is_resting(&movement) {
+                return steps;
+            }
+            cam.input(keys, world, &movement);
+        }
+        panic!("never came to rest: {cam:?}");
+    }
+
+    #[test]
+    fn releasing_crouch_stands_up() {
+        let world = room(false);
+        let mut cam = CameraAttr::new(Vec3::new(0.0, CameraAttr::EYE_HEIGHT, 0.0));
+        settle(&mut cam, Keys::empty(), &world);
+
+        cam.input(Keys::CTRL, &world, &Movement::walking());
+        assert!(cam.state.contains(MoveState::Crouched));
+        assert!(!cam.is_resting(&Movement::walking()));
+
+        assert!(settle(&mut cam, Keys::empty(), &world) > 0);
+        assert!(!cam.state.contains(MoveState::Crouched));
+        assert!((cam.eye.y - CameraAttr::EYE_HEIGHT).abs() < 1e-2);
+    }
+
+    #[test]
+    fn crouched_player_stands_once_theres_room() {
+        let movement = Movement::walking();
+        let mut cam = CameraAttr::new(Vec3::new(0.0, CameraAttr::EYE_HEIGHT, 0.0));
+        cam.input(Keys::CTRL, &room(false), &movement);
+
+        // under a low ceiling, standing is tried again and again
+        let low = room(true);
+        for _ in 0..10 {
+            cam.input(Keys::empty(), &low, &movement);
+        }
+        assert!(cam.state.contains(MoveState::Crouched));
+        assert!(!cam.is_resting(&movement));
+
+        settle(&mut cam, Keys::empty(), &room(false));
+        assert!(!cam.state.contains(MoveState::Crouched));
+    }
+}
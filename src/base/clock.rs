@@ -0,0 +1,253 @@
+use crate::*;
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+/// A single NTP-style round-trip measurement.
+#[derive(Clone, Copy, Debug)]
+pub struct ClockSample {
+    offset: i64, // server clock - client clock (µs)
+    rtt: u64,    // round-trip time excluding server processing (µs)
+}
+
+impl ClockSample {
+    /// Calculate a sample from a completed exchange received at `client_recv`.
+    pub fn new(sync: TimeSync, client_recv: u64) -> Self {
+        let t0 = sync.client_send as i64;
+        let t1 = sync.server_recv as i64;
+        let t2 = sync.server_send as i64;
+        let t3 = client_recv as i64;
+
+        let rtt = ((t3 - t0) - (t2 - t1)).max(0) as u64;
+        let offset = ((t1 - t0) + (t2 - t3)) / 2;
+
+        Self { offset, rtt }
+    }
+
+    /// Calculate the server's sample of the exchange its latest reply started, as echoed by a
+    /// request received at `server_recv` (unless there's no reply to echo yet).
+    ///
+    /// The roles are swapped, so the offset is the client's clock - the server's.
+    pub fn echoed(request: TimeSync, server_recv: u64) -> Option<Self> {
+        let exchange = TimeSync {
+            client_send: request.echo_send,
+            server_recv: request.echo_recv,
+            server_send: request.client_send,
+            ..TimeSync::new(0)
+        };
+        (request.echo_send != 0).then(|| Self::new(exchange, server_recv))
+    }
+
+    pub const fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    pub const fn rtt(&self) -> u64 {
+        self.rtt
+    }
+}
+
+/// Filtered estimation of the server's clock.
+#[derive(Debug, Default)]
+pub struct ClockSync {
+    samples: VecDeque<ClockSample>,
+    offset: i64,
+    rtt: u64,
+    is_synced: bool,
+    latest: Option<(TimeSync, u64)>, // reply (and when it was received) to echo with the next request
+}
+
+impl ClockSync {
+    /// number of recent samples considered
+    const WINDOW: usize = 16;
+
+    /// divisor of the offset correction applied per sample (once synced)
+    const SMOOTHING: i64 = 8;
+
+    pub fn add(&mut self, sample: ClockSample) {
+        if self.samples.len() == Self::WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+
+        // the least delayed sample has the least asymmetric (most accurate) offset
+        let best = self
+            .samples
+            .iter()
+            .min_by_key(|s| s.rtt)
+            .copied()
+            .unwrap_or(sample);
+
+        if self.is_synced {
+            self.offset += (best.offset - self.offset) / Self::SMOOTHING;
+        } else {
+            self.offset = best.offset;
+            self.is_synced = true;
+        }
+
+        // median is resilient against the occasional spike
+        let mut rtts = self.samples.iter().map(|s| s.rtt).collect::<Vec<_>>();
+        rtts.sort_unstable();
+        self.rtt = rtts[rtts.len() / 2];
+    }
+
+    pub const fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    pub const fn rtt(&self) -> Duration {
+        Duration::from_micros(self.rtt)
+    }
+
+    pub const fn is_synced(&self) -> bool {
+        self.is_synced
+    }
+
+    /// Sample a reply received at `client_recv`, remembering it to echo.
+    pub fn receive(&mut self, reply: TimeSync, client_recv: u64) -> ClockSample {
+        let sample = ClockSample::new(reply, client_recv);
+        self.add(sample);
+        self.latest = Some((reply, client_recv));
+        sample
+    }
+
+    /// The next request, sent at `client_send` (echoing the latest reply, if any).
+    pub fn request(&self, client_send: u64) -> TimeSync {
+        let request = TimeSync::new(client_send);
+        match &self.latest {
+            Some((reply, client_recv)) => request.echoing(reply, *client_recv),
+            None => request,
+        }
+    }
+
+    /// Estimated server time (µs) corresponding to the given local time.
+    pub const fn to_server(&self, local: u64) -> u64 {
+        local.saturating_add_signed(self.offset)
+    }
+}
+
+/// A thread-safe [`ClockSync`].
+#[derive(Clone, Debug, Default)]
+pub struct Clock {
+    inner: Arc<RwLock<ClockSync>>,
+}
+
+impl Clock {
+    pub fn add(&self, sample: ClockSample) {
+        self.inner.write().add(sample)
+    }
+
+    /// Sample a reply received at `client_recv`, remembering it to echo.
+    pub fn receive(&self, reply: TimeSync, client_recv: u64) -> ClockSample {
+        self.inner.write().receive(reply, client_recv)
+    }
+
+    /// The next request, sent now.
+    pub fn request(&self) -> TimeSync {
+        self.inner.read().request(timestamp())
+    }
+
+    /// Estimated current server time (µs).
+    pub fn server_time(&self) -> u64 {
+        self.inner.read().to_server(timestamp())
+    }
+
+    /// Filtered round-trip time.
+    pub fn rtt(&self) -> Duration {
+        self.inner.read().rtt()
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.inner.read().is_synced()
+    }
+
+    /// Forget every sample (e.g., when connecting to a new server).
+    pub fn reset(&self) {
+        *self.inner.write() = Default::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(offset: i64, rtt: u64) -> ClockSample {
+        ClockSample { offset, rtt }
+    }
+
+    #[test]
+    fn sample_excludes_server_processing() {
+        let sync = TimeSync {
+            server_recv: 1600,
+            server_send: 1700,
+            ..TimeSync::new(1000)
+        };
+        let sample = ClockSample::new(sync, 1300);
+        assert_eq!(sample.rtt(), 200);
+        assert_eq!(sample.offset(), 500);
+    }
+
+    #[test]
+    fn server_times_the_exchange_its_reply_started() {
+        // the server's clock is 500µs ahead, and each way takes 100µs
+        let mut sync = ClockSync::default();
+        let first = sync.request(1000);
+        assert!(ClockSample::echoed(first, 1600).is_none());
+
+        let reply = TimeSync {
+            server_recv: 1600,
+            server_send: 1700,
+            ..first
+        };
+        sync.receive(reply, 1300);
+
+        // held by the client for a while, which doesn't count
+        let next = sync.request(2000);
+        assert_eq!((next.echo_send, next.echo_recv), (1700, 1300));
+        let sample = ClockSample::echoed(next, 2600).unwrap();
+        assert_eq!(sample.rtt(), 200);
+        assert_eq!(sample.offset(), -500);
+    }
+
+    #[test]
+    fn first_sample_syncs_at_once() {
+        let mut sync = ClockSync::default();
+        assert!(!sync.is_synced());
+
+        sync.add(sample(-2000, 300));
+        assert!(sync.is_synced());
+        assert_eq!(sync.offset(), -2000);
+        assert_eq!(sync.rtt(), Duration::from_micros(300));
+        assert_eq!(sync.to_server(1500), 0); // saturating
+    }
+
+    #[test]
+    fn least_delayed_sample_is_followed_smoothly() {
+        let mut sync = ClockSync::default();
+        sync.add(sample(1000, 500));
+
+        // a delayed sample is outweighed by the accurate one
+        sync.add(sample(5000, 10_000));
+        assert_eq!(sync.offset(), 1000);
+
+        // a better one is approached by an eighth of the way
+        sync.add(sample(1800, 100));
+        assert_eq!(sync.offset(), 1100);
+        sync.add(sample(9000, 900));
+        assert_eq!(sync.offset(), 1100 + 700 / 8);
+    }
+
+    #[test]
+    fn rtt_is_the_median_of_the_window() {
+        let mut sync = ClockSync::default();
+        for rtt in [100, 10_000, 500] {
+            sync.add(sample(0, rtt));
+        }
+        assert_eq!(sync.rtt(), Duration::from_micros(500));
+
+        // the spike and the rest leave the window eventually
+        for _ in 0..ClockSync::WINDOW {
+            sync.add(sample(0, 2000));
+        }
+        assert_eq!(sync.samples.len(), ClockSync::WINDOW);
+        assert_eq!(sync.rtt(), Duration::from_micros(2000));
+    }
+}
@@ -0,0 +1,285 @@
+use crate::*;
+use std::collections::HashMap;
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub const fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// The box of an object at `pos` scaled by `dim` (see [`Transformations`]).
+    pub fn from_center(pos: Vec3, dim: Vec3) -> Self {
+        let half = dim.abs();
+        Self::new(pos - half, pos + half)
+    }
+
+    pub fn translated(&self, by: Vec3) -> Self {
+        Self::new(self.min + by, self.max + by)
+    }
+
+    /// The smallest box containing both boxes.
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(
+            self.min.min_by_component(other.min),
+            self.max.max_by_component(other.max),
+        )
+    }
+
+    /// Boxes overlap (touching doesn't count).
+    pub fn intersects(&self, other: &Self) -> bool {
+        (0..3).all(|i| self.overlaps_on(other, i))
+    }
+
+    /// Distance along a ray (of unit direction) to where it enters the box,
+    /// zero from within and none if it misses.
+    pub fn ray(&self, origin: Vec3, dir: Vec3) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, f32::INFINITY);
+        for axis in 0..3 {
+            let inv = 1.0 / dir[axis];
+            let a = (self.min[axis] - origin[axis]) * inv;
+            let b = (self.max[axis] - origin[axis]) * inv;
+
+            // parallel rays outside of the slab miss (and produce NaN on its boundary)
+            if a.is_nan() || b.is_nan() {
+                return None;
+            }
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        (near <= far).then_some(near)
+    }
+
+    fn overlaps_on(&self, other: &Self, axis: usize) -> bool {
+        self.min[axis] < other.max[axis] && self.max[axis] > other.min[axis]
+    }
+}
+
+/// Static world geometry with a uniform grid broadphase.
+#[derive(Debug, Default)]
+pub struct Collider {
+    boxes: Vec<Aabb>,
+    cells: HashMap<[i32; 3], Vec<u32>>,
+    large: Vec<u32>, // boxes spanning too many cells (always tested)
+}
+
+impl Collider {
+    /// width of a single grid cell
+    const CELL_SIZE: f32 = 16.0;
+
+    /// any box spanning more cells than this isn't gridded
+    const MAX_CELLS: usize = 512;
+
+    /// tolerance of resting contact
+    const SKIN: f32 = 1e-3;
+
+    pub fn new(boxes: impl IntoIterator<Item = Aabb>) -> Self {
+        let mut collider = Self::default();
+        boxes.into_iter().for_each(|aabb| collider.insert(aabb));
+        collider
+    }
+
+    pub fn insert(&mut self, aabb: Aabb) {
+        let idx = self.boxes.len() as u32;
+        self.boxes.push(aabb);
+
+        let [min, max] = Self::cell_range(&aabb);
+        let span = (0..3)
+            .map(|i| max[i].abs_diff(min[i]) as usize + 1)
+            .fold(1, usize::saturating_mul);
+
+        if span > Self::MAX_CELLS {
+            self.large.push(idx);
+            return;
+        }
+
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    self.cells.entry([x, y, z]).or_default().push(idx);
+                }
+            }
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.boxes.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.boxes.is_empty()
+    }
+
+    /// Every box that might intersect the given area.
+    pub fn query(&self, area: &Aabb) -> impl Iterator<Item = &Aabb> {
+        let [min, max] = Self::cell_range(area);
+
+        let mut found = self.large.clone();
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    if let Some(idxs) = self.cells.get(&[x, y, z]) {
+                        found.extend_from_slice(idxs);
+                    }
+                }
+            }
+        }
+
+        // boxes may occupy multiple cells
+        found.sort_unstable();
+        found.dedup();
+
+        found.into_iter().map(|idx| &self.boxes[idx as usize])
+    }
+
+    /// The body doesn't intersect any geometry.
+    pub fn is_clear(&self, body: &Aabb) -> bool {
+        !self.query(body).any(|aabb| aabb.intersects(body))
+    }
+
+    /// The body is resting on top of some geometry.
+    pub fn is_supported(&self, body: &Aabb) -> bool {
+        let probe = Vec3::new(0.0, -2.0 * Self::SKIN, 0.0);
+        self.slide(*body, probe).y > probe.y
+    }
+
+    /// Move a body by `delta`, sliding along whatever it runs into.
+    /// Returns the displacement actually travelled.
+    pub fn slide(&self, body: Aabb, delta: Vec3) -> Vec3 {
+        if self.is_empty() || delta == Vec3::zero() {
+            return delta;
+        }
+
+        let area = body.union(&body.translated(delta));
+        let nearby = self.query(&area).collect::<Vec<_>>();
+
+        let mut body = body;
+        let mut moved = Vec3::zero();
+
+        // resolve each axis separately (vertical first)
+        for axis in [1, 0, 2] {
+            let mut d = delta[axis];
+            if d == 0.0 {
+                continue;
+            }
+
+            for aabb in nearby.iter() {
+                // only boxes in the way along this axis
+                let is_aligned = (0..3)
+                    .filter(|&i| i != axis)
+                    .all(|i| body.overlaps_on(aabb, i));
+                if !is_aligned {
+                    continue;
+                }
+
+                if d > 0.0 && body.max[axis] <= aabb.min[axis] + Self::SKIN {
+                    d = d.min(aabb.min[axis] - body.max[axis]);
+                } else if d < 0.0 && body.min[axis] >= aabb.max[axis] - Self::SKIN {
+                    d = d.max(aabb.max[axis] - body.min[axis]);
+                }
+            }
+
+            body.min[axis] += d;
+            body.max[axis] += d;
+            moved[axis] = d;
+        }
+        moved
+    }
+
+    /// Distance along a ray (of unit direction) to the nearest geometry within reach.
+    pub fn raycast(&self, origin: Vec3, dir: Vec3, reach: f32) -> Option<f32> {
+        let end = origin + dir * reach;
+        let area = Aabb::new(origin.min_by_component(end), origin.max_by_component(end));
+        self.query(&area)
+            .filter_map(|aabb| aabb.ray(origin, dir))
+            .filter(|&dist| dist <= reach)
+            .min_by(f32::total_cmp)
+    }
+
+    fn cell_range(aabb: &Aabb) -> [[i32; 3]; 2] {
+        let cell = |v: f32| (v / Self::CELL_SIZE).floor() as i32;
+        [
+            [cell(aabb.min.x), cell(aabb.min.y), cell(aabb.min.z)],
+            [cell(aabb.max.x), cell(aabb.max.y), cell(aabb.max.z)],
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box(center: Vec3) -> Aabb {
+        Aabb::from_center(center, Vec3::new(0.5, 0.5, 0.5))
+    }
+
+    /// A floor whose top is at zero and a wall whose face is at x = 2.
+    fn room() -> Collider {
+        Collider::new([
+            Aabb::new(Vec3::new(-8.0, -1.0, -8.0), Vec3::new(8.0, 0.0, 8.0)),
+            Aabb::new(Vec3::new(2.0, 0.0, -8.0), Vec3::new(3.0, 4.0, 8.0)),
+        ])
+    }
+
+    #[test]
+    fn falling_body_lands_on_the_floor() {
+        let world = room();
+        let body = unit_box(Vec3::new(0.0, 1.0, 0.0));
+
+        let moved = world.slide(body, Vec3::new(0.0, -3.0, 0.0));
+        assert_eq!(moved, Vec3::new(0.0, -0.5, 0.0));
+        let landed = body.translated(moved);
+        assert!(world.is_supported(&landed));
+        assert!(world.is_clear(&landed));
+    }
+
+    #[test]
+    fn blocked_axis_stops_while_others_slide() {
+        let world = room();
+        let body = unit_box(Vec3::new(1.0, 1.0, 0.0));
+
+        // into the wall and along it
+        let moved = world.slide(body, Vec3::new(2.0, 0.0, 3.0));
+        assert_eq!(moved, Vec3::new(0.5, 0.0, 3.0));
+
+        // away from it, unhindered
+        let moved = world.slide(body, Vec3::new(-2.0, 0.5, -3.0));
+        assert_eq!(moved, Vec3::new(-2.0, 0.5, -3.0));
+    }
+
+    #[test]
+    fn overlapping_body_isnt_clear() {
+        let world = room();
+        assert!(!world.is_clear(&unit_box(Vec3::new(2.0, 1.0, 0.0))));
+        assert!(world.is_clear(&unit_box(Vec3::new(0.0, 0.5, 0.0))));
+        assert!(!world.is_supported(&unit_box(Vec3::new(0.0, 2.0, 0.0))));
+    }
+
+    #[test]
+    fn broadphase_finds_only_nearby_boxes() {
+        let near = unit_box(Vec3::new(1.0, 1.0, 1.0));
+        let far = unit_box(Vec3::new(100.0, 1.0, 100.0));
+        let huge = Aabb::new(
+            Vec3::new(-1000.0, -2.0, -1000.0),
+            Vec3::new(1000.0, -1.0, 1000.0),
+        );
+        let world = Collider::new([near, far, huge]);
+        assert_eq!(world.len(), 3);
+
+        // huge boxes are always tested, each box is found once
+        let found = world
+            .query(&unit_box(Vec3::zero()))
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(found, [near, huge]);
+
+        let across = Aabb::new(Vec3::zero(), Vec3::new(100.0, 1.0, 100.0));
+        assert_eq!(world.query(&across).count(), 3);
+        assert!(Collider::default().query(&across).next().is_none());
+    }
+}
@@ -0,0 +1,196 @@
+use crate::*;
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+/// An administrative server command.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Help,
+    List,
+    Status,
+    Kick(Id),
+    Ban(IpAddr),
+    Say(String),
+    Tps(u16),
+    Teleport(Id, Vec3),
+    Reload,
+    Shutdown(Duration), // after a countdown
+}
+
+impl Command {
+    /// summary of every command
+    pub const USAGE: &str = "\
+help                  show this summary
+list                  list every player
+status                show the state of the server
+kick <id>             disconnect a player
+ban <addr>            disconnect and refuse every player from an IP address
+say <msg>             send a message to every player
+tps <n>               change the server's tick rate
+teleport <id> x y z   move a player
+reload                reload the config file
+shutdown [seconds]    stop the server (after a countdown)";
+
+    /// Parse a single command line (e.g., `teleport 61440 0 10 0`).
+    pub fn parse(line: &str) -> BlazedResult<Self> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = rest.split_whitespace().collect::<Vec<_>>();
+
+        let arity = |n: usize| -> BlazedResult {
+            if args.len() != n {
+                return Err(format!(
+                    "'{name}' expects {n} argument(s), found {} (see 'help').",
+                    args.len()
+                )
+                .into());
+            }
+            Ok(())
+        };
+
+        let cmd = match name {
+            "help" => arity(0).map(|_| Self::Help)?,
+            "list" => arity(0).map(|_| Self::List)?,
+            "status" => arity(0).map(|_| Self::Status)?,
+            "reload" => arity(0).map(|_| Self::Reload)?,
+            "kick" => {
+                arity(1)?;
+                Self::Kick(args[0].parse()?)
+            }
+            "ban" => {
+                arity(1)?;
+                Self::Ban(Self::parse_ip(args[0])?)
+            }
+            "say" => {
+                // validated here rather than when sent
+                Text::new(rest)?;
+                Self::Say(rest.trim().to_string())
+            }
+            "tps" => {
+                arity(1)?;
+                let tps = args[0].parse::<u16>()?;
+                if tps == 0 || tps > MAX_TPS {
+                    return Err(format!("TPS must be within 1..={MAX_TPS}.").into());
+                }
+                Self::Tps(tps)
+            }
+            "teleport" => {
+                arity(4)?;
+                let mut pos = Vec3::zero();
+                for (i, arg) in args[1..].iter().enumerate() {
+                    pos[i] = arg
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|x| x.is_finite())
+                        .ok_or(format!("Invalid coordinate '{arg}'."))?;
+                }
+                Self::Teleport(args[0].parse()?, pos)
+            }
+            "shutdown" => match args.as_slice() {
+                [] => Self::Shutdown(Duration::ZERO),
+                [secs] => Self::Shutdown(Duration::from_secs(secs.parse()?)),
+                _ => return Err("'shutdown' expects at most 1 argument (see 'help').".into()),
+            },
+            "" => return Err("Empty command (see 'help').".into()),
+            _ => return Err(format!("Unknown command '{name}' (see 'help').").into()),
+        };
+        Ok(cmd)
+    }
+
+    /// Either a bare IP address or a socket address.
+    fn parse_ip(s: &str) -> BlazedResult<IpAddr> {
+        s.parse::<IpAddr>()
+            .or_else(|_| s.parse::<SocketAddr>().map(|addr| addr.ip()))
+            .map_err(|_| format!("Invalid address '{s}'.").into())
+    }
+}
+
+/// The command line (as parsed by [`Command::parse`]).
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Help => write!(f, "help"),
+            Self::List => write!(f, "list"),
+            Self::Status => write!(f, "status"),
+            Self::Kick(id) => write!(f, "kick {id}"),
+            Self::Ban(ip) => write!(f, "ban {ip}"),
+            Self::Say(msg) => write!(f, "say {msg}"),
+            Self::Tps(tps) => write!(f, "tps {tps}"),
+            Self::Teleport(id, pos) => write!(f, "teleport {id} {} {} {}", pos.x, pos.y, pos.z),
+            Self::Reload => write!(f, "reload"),
+            Self::Shutdown(delay) => write!(f, "shutdown {}", delay.as_secs()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_commands_parse() {
+        let ip = IpAddr::from([10, 0, 0, 1]);
+        let cases = [
+            ("help", Command::Help),
+            ("  list  ", Command::List),
+            ("status", Command::Status),
+            ("reload", Command::Reload),
+            ("kick 61440", Command::Kick(61440)),
+            ("ban 10.0.0.1", Command::Ban(ip)),
+            ("ban 10.0.0.1:7000", Command::Ban(ip)),
+            (
+                "ban ::1",
+                Command::Ban(IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1])),
+            ),
+            ("say hello  there ", Command::Say("hello  there".into())),
+            ("tps 1", Command::Tps(1)),
+            (&format!("tps {MAX_TPS}"), Command::Tps(MAX_TPS)),
+            (
+                "teleport 61440 0 10.5 -3",
+                Command::Teleport(61440, Vec3::new(0.0, 10.5, -3.0)),
+            ),
+            ("shutdown", Command::Shutdown(Duration::ZERO)),
+            ("shutdown 10", Command::Shutdown(Duration::from_secs(10))),
+        ];
+
+        for (line, cmd) in cases {
+            assert_eq!(Command::parse(line).unwrap(), cmd, "{line:?}");
+
+            // and as written back
+            assert_eq!(Command::parse(&cmd.to_string()).unwrap(), cmd, "{cmd}");
+        }
+    }
+
+    #[test]
+    fn invalid_commands_are_refused() {
+        let cases = [
+            ("", "Empty command"),
+            ("   ", "Empty command"),
+            ("jump", "Unknown command 'jump'"),
+            ("HELP", "Unknown command 'HELP'"),
+            ("help me", "expects 0 argument(s), found 1"),
+            ("kick", "expects 1 argument(s), found 0"),
+            ("kick 1 2", "expects 1 argument(s), found 2"),
+            ("kick alice", ""),
+            ("ban localhost", "Invalid address 'localhost'"),
+            ("say", "must not be empty"),
+            (&format!("say {}", "x".repeat(1024)), "must not exceed"),
+            ("tps 0", "TPS must be within"),
+            (&format!("tps {}", MAX_TPS + 1), "TPS must be within"),
+            ("tps fast", ""),
+            ("teleport 61440 0 10", "expects 4 argument(s), found 3"),
+            ("teleport 61440 0 NaN 0", "Invalid coordinate 'NaN'"),
+            ("teleport 61440 inf 0 0", "Invalid coordinate 'inf'"),
+            ("shutdown 1 2", "at most 1 argument"),
+            ("shutdown -1", ""),
+        ];
+
+        for (line, error) in cases {
+            let e = Command::parse(line).unwrap_err().to_string();
+            assert!(e.contains(error), "{line:?}: {e}");
+        }
+    }
+}
@@ -18,6 +18,45 @@ pub enum PacketError {
 
     #[error("Expected {lhs}, found {rhs}")]
     Unexpected { lhs: String, rhs: String },
+
+    #[error("Malformed packet {id} ({len} bytes)")]
+    Malformed { id: u8, len: usize },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MapError {
+    #[error(transparent)]
+    Parse(#[from] ron::error::SpannedError),
+
+    #[error("cube #{index}: {reason}")]
+    Cube { index: usize, reason: String },
+
+    #[error("spawn #{index}: {reason}")]
+    Spawn { index: usize, reason: String },
+
+    #[error("{0}")]
+    Invalid(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ReplayError {
+    #[error("not a replay file")]
+    NotReplay,
+
+    #[error("unsupported version {0} (expected {REPLAY_VERSION})")]
+    Version(u16),
+
+    #[error("ends early")]
+    Truncated,
+
+    #[error("missing {0}")]
+    Missing(&'static str),
+
+    #[error("record {tag} has {len} bytes")]
+    Malformed { tag: u8, len: usize },
+
+    #[error("unexpected record {0}")]
+    Unexpected(u8),
 }
 
 #[derive(thiserror::Error, Debug, Display)]
@@ -75,6 +114,12 @@ pub enum BlazedError {
     #[error(transparent)]
     Packet(#[from] PacketError),
 
+    #[error("Invalid map: {0}")]
+    Map(#[from] MapError),
+
+    #[error("Invalid replay: {0}")]
+    Replay(#[from] ReplayError),
+
     #[error(transparent)]
     Sync(SyncError),
 
@@ -110,7 +155,10 @@ impl From<Box<dyn std::any::Any + Send>> for BlazedError {
     fn from(value: Box<dyn std::any::Any + Send>) -> Self {
         match value.downcast::<&str>() {
             Ok(s) => Self::Misc(s.to_string()),
-            Err(_) => Self::Unknown,
+            Err(value) => match value.downcast::<String>() {
+                Ok(s) => Self::Misc(*s),
+                Err(_) => Self::Unknown,
+            },
         }
     }
 }
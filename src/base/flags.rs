@@ -36,21 +36,63 @@ bitflags! {
         const Basic  = 0b_0000_0001;
         const Player = 0b_0000_0010;
     }
+
+    #[repr(transparent)]
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Pod, Zeroable)]
+    pub struct MeshKind: u8 {
+        const SimpleCube = 0b_0000_0001; // unlit
+        const NormalCube = 0b_0000_0010; // lit
+    }
+
+    #[repr(transparent)]
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Pod, Zeroable)]
+    pub struct MoveMode: u8 {
+        const FreeFly = 0b_0000_0001;
+        const Walking = 0b_0000_0010;
+    }
+
+    #[repr(transparent)]
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Pod, Zeroable)]
+    pub struct MoveState: u8 {
+        const Grounded = 0b_0000_0001;
+        const Crouched = 0b_0000_0010;
+    }
+
+    #[repr(transparent)]
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Pod, Zeroable)]
+    pub struct RejectReason: u8 {
+        const ServerFull   = 0b_0000_0001;
+        const Banned       = 0b_0000_0010;
+        const Kicked       = 0b_0000_0100;
+        const ShuttingDown = 0b_0000_1000;
+    }
+}
+
+impl std::fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::ServerFull => write!(f, "server full"),
+            Self::Banned => write!(f, "banned"),
+            Self::Kicked => write!(f, "kicked"),
+            Self::ShuttingDown => write!(f, "server shutting down"),
+            _ => write!(f, "unknown reason ({:#04x})", self.bits()),
+        }
+    }
 }
 
 impl Keys {
     const NORMAL: Self = Self::UP
         .union(Self::LEFT)
         .union(Self::DOWN)
-        .union(Self::RIGHT)
-        .union(Self::CTRL);
+        .union(Self::RIGHT);
 
     const CONTINUOUS: Self = Self::W
         .union(Self::A)
         .union(Self::S)
         .union(Self::D)
         .union(Self::SPACE)
-        .union(Self::SHIFT);
+        .union(Self::SHIFT)
+        .union(Self::CTRL);
 
     // check if keys are all normal
     pub const fn is_normal(self) -> bool {
@@ -0,0 +1,369 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// Shading (and mesh) of a map cube.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub enum CubeKind {
+    #[default]
+    Simple, // unlit
+    Normal, // lit by emitting cubes
+}
+
+impl From<CubeKind> for MeshKind {
+    fn from(value: CubeKind) -> Self {
+        match value {
+            CubeKind::Simple => Self::SimpleCube,
+            CubeKind::Normal => Self::NormalCube,
+        }
+    }
+}
+
+/// A single cube of a map.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CubeDef {
+    pub pos: [f32; 3],
+    pub dim: [f32; 3], // half extents
+    pub color: [f32; 3],
+    #[serde(default = "CubeDef::opaque")]
+    pub alpha: f32,
+    #[serde(default)]
+    pub emits: bool,
+    #[serde(default)]
+    pub kind: CubeKind,
+}
+
+impl CubeDef {
+    const fn opaque() -> f32 {
+        1.0
+    }
+}
+
+/// Ambient settings of a map.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AmbientDef {
+    pub sky: [f32; 3],
+    pub strength: f32,
+}
+
+impl Default for AmbientDef {
+    fn default() -> Self {
+        let ambient = Ambient::default();
+        Self {
+            sky: ambient.sky.into(),
+            strength: ambient.strength,
+        }
+    }
+}
+
+/// Procedurally generated cubes of a map (seeded separately, see [`Generator`]).
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratorDef {
+    pub count: u16,
+    pub extent: f32,
+    pub size: (f32, f32),  // (min, max) half extent
+    pub alpha: (f32, f32), // (min, max)
+    #[serde(default)]
+    pub exclusion: f32, // clearance around spawn points
+}
+
+impl GeneratorDef {
+    pub const fn with_seed(&self, seed: u64) -> Generator {
+        Generator {
+            seed,
+            count: self.count,
+            extent: self.extent,
+            size_min: self.size.0,
+            size_max: self.size.1,
+            alpha_min: self.alpha.0,
+            alpha_max: self.alpha.1,
+            exclusion: self.exclusion,
+        }
+    }
+}
+
+/// A human-editable level (RON), e.g.:
+///
+/// ```ron
+/// (
+///     ambient: (sky: (0.4, 0.4, 0.4), strength: 0.2),
+///     spawns: [(0.0, 0.0, 0.0)],
+///     cubes: [
+///         (pos: (0.0, -2.0, 0.0), dim: (7.5, 0.1, 7.5), color: (1.0, 1.0, 1.0), kind: Normal),
+///         (pos: (0.0, 20.0, 0.0), dim: (0.5, 0.5, 0.5), color: (1.0, 1.0, 1.0), emits: true),
+///     ],
+///     generator: Some((count: 500, extent: 200.0, size: (1.0, 8.0), alpha: (0.5, 1.0), exclusion: 10.0)),
+/// )
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Map {
+    #[serde(default)]
+    pub ambient: AmbientDef,
+    #[serde(default)]
+    pub spawns: Vec<[f32; 3]>,
+    pub cubes: Vec<CubeDef>,
+    #[serde(default)]
+    pub generator: Option<GeneratorDef>,
+}
+
+impl Map {
+    /// maximum number of light-emitting cubes (see the normal shader)
+    pub const MAX_LIGHTS: usize = 16;
+
+    /// Read and validate a map file.
+    pub fn load(path: impl AsRef<Path>) -> BlazedResult<Self> {
+        let path = path.as_ref();
+        let s = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read map '{}': {e}", path.display()))?;
+        Self::parse(&s)
+    }
+
+    /// Parse and validate a map.
+    pub fn parse(s: &str) -> BlazedResult<Self> {
+        let map = ron::from_str::<Self>(s).map_err(MapError::from)?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    pub fn validate(&self) -> Result<(), MapError> {
+        let is_finite = |v: &[f32]| v.iter().all(|x| x.is_finite());
+        let is_unit = |v: &[f32]| v.iter().all(|x| (0.0..=1.0).contains(x));
+
+        let generated = self.generator.map_or(0, |g| g.count as usize);
+        if self.cubes.len() + generated > WORLD_IDS.len() {
+            return Err(MapError::Invalid(format!(
+                "too many cubes ({} + {generated} generated, max: {})",
+                self.cubes.len(),
+                WORLD_IDS.len()
+            )));
+        }
+        if let Some(def) = self.generator {
+            def.with_seed(0)
+                .validate()
+                .map_err(|e| MapError::Invalid(e.to_string()))?;
+        }
+
+        let mut lights = 0;
+        for (index, cube) in self.cubes.iter().enumerate() {
+            let invalid = |reason: &str| MapError::Cube {
+                index,
+                reason: reason.to_string(),
+            };
+
+            if !is_finite(&cube.pos) || !is_finite(&cube.dim) {
+                return Err(invalid("position and dimensions must be finite"));
+            }
+            if cube.dim.iter().any(|&x| x <= 0.0) {
+                return Err(invalid("dimensions must be positive"));
+            }
+            if !is_unit(&cube.color) {
+                return Err(invalid("color components must be within 0.0..=1.0"));
+            }
+            if !(cube.alpha > 0.0 && cube.alpha <= 1.0) {
+                return Err(invalid("alpha must be within (0.0, 1.0]"));
+            }
+            lights += cube.emits as usize;
+        }
+
+        if lights > Self::MAX_LIGHTS {
+            return Err(MapError::Invalid(format!(
+                "too many light-emitting cubes ({lights}, max: {})",
+                Self::MAX_LIGHTS
+            )));
+        }
+
+        // players must be able to stand at each spawn point
+        let collider = Collider::new(
+            self.cubes
+                .iter()
+                .map(|c| Aabb::from_center(c.pos.into(), c.dim.into())),
+        );
+        for (index, &spawn) in self.spawns.iter().enumerate() {
+            let invalid = |reason: &str| MapError::Spawn {
+                index,
+                reason: reason.to_string(),
+            };
+
+            if !is_finite(&spawn) {
+                return Err(invalid("position must be finite"));
+            }
+            if !collider.is_clear(&CameraAttr::new(spawn.into()).bounds()) {
+                return Err(invalid("obstructed by a cube"));
+            }
+        }
+
+        if !is_finite(&self.ambient.sky) || !is_unit(&self.ambient.sky) {
+            return Err(MapError::Invalid(
+                "ambient sky color components must be within 0.0..=1.0".into(),
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.ambient.strength) {
+            return Err(MapError::Invalid(
+                "ambient strength must be within 0.0..=1.0".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The world described by this map (generated cubes are seeded by `seed`).
+    pub fn world(&self, seed: u64) -> World {
+        let objects = self
+            .cubes
+            .iter()
+            .enumerate()
+            .map(|(id, cube)| {
+                let [r, g, b] = cube.color;
+                StaticObj::new(
+                    id as Id,
+                    cube.kind.into(),
+                    cube.pos.into(),
+                    cube.dim.into(),
+                    Color::new([r, g, b, cube.alpha], cube.emits),
+                )
+            })
+            .collect();
+
+        let spawns = self.spawns.iter().map(|&s| s.into()).collect();
+
+        let ambient = Ambient {
+            sky: self.ambient.sky.into(),
+            strength: self.ambient.strength,
+        };
+
+        let generator = self.generator.map(|def| def.with_seed(seed));
+
+        World::new(objects, generator, spawns, ambient)
+    }
+}
+
+impl World {
+    /// Load the world described by a map file.
+    pub fn load(path: impl AsRef<Path>, seed: u64) -> BlazedResult<Self> {
+        Map::load(path).map(|map| map.world(seed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: &str = "(
+        spawns: [(0.0, 0.0, 0.0)],
+        cubes: [(pos: (0.0, -2.0, 0.0), dim: (8.0, 0.1, 8.0), color: (1.0, 1.0, 1.0))],
+    )";
+
+    fn valid() -> Map {
+        Map::parse(VALID).unwrap()
+    }
+
+    fn cube_error(map: &Map) -> String {
+        match map.validate() {
+            Err(MapError::Cube { index, reason }) => format!("{index}: {reason}"),
+            result => panic!("expected an invalid cube, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn shipped_map_is_valid() {
+        let map = Map::parse(include_str!("../../maps/arena.ron")).unwrap();
+        assert!(!map.cubes.is_empty());
+        assert!(map.validate().is_ok());
+    }
+
+    #[test]
+    fn malformed_maps_dont_parse() {
+        assert!(matches!(
+            Map::parse("(cubes: [], walls: [])"),
+            Err(BlazedError::Map(MapError::Parse(_)))
+        ));
+        assert!(Map::parse("(spawns: [])").is_err()); // cubes are required
+    }
+
+    #[test]
+    fn invalid_cubes_are_pointed_out() {
+        let mut map = valid();
+        map.cubes.push(map.cubes[0]);
+
+        map.cubes[1].dim[1] = 0.0;
+        assert_eq!(cube_error(&map), "1: dimensions must be positive");
+        map.cubes[1].dim[1] = f32::NAN;
+        assert_eq!(
+            cube_error(&map),
+            "1: position and dimensions must be finite"
+        );
+        map.cubes[1].dim[1] = 0.1;
+
+        map.cubes[1].color[2] = 1.5;
+        assert_eq!(
+            cube_error(&map),
+            "1: color components must be within 0.0..=1.0"
+        );
+        map.cubes[1].color[2] = 1.0;
+
+        map.cubes[1].alpha = 0.0;
+        assert_eq!(cube_error(&map), "1: alpha must be within (0.0, 1.0]");
+        map.cubes[1].alpha = 1.0;
+        assert!(map.validate().is_ok());
+    }
+
+    #[test]
+    fn too_many_lights_or_cubes_are_refused() {
+        let mut map = valid();
+        let light = CubeDef {
+            emits: true,
+            ..map.cubes[0]
+        };
+        map.cubes.extend([light; Map::MAX_LIGHTS]);
+        assert!(map.validate().is_ok());
+        map.cubes.push(light);
+        assert!(matches!(map.validate(), Err(MapError::Invalid(_))));
+
+        let mut map = valid();
+        map.generator = Some(GeneratorDef {
+            count: u16::MAX,
+            extent: 100.0,
+            size: (1.0, 2.0),
+            alpha: (0.5, 1.0),
+            exclusion: 0.0,
+        });
+        assert!(matches!(map.validate(), Err(MapError::Invalid(_))));
+
+        map.generator = Some(GeneratorDef {
+            count: 10,
+            size: (2.0, 1.0), // min > max
+            ..map.generator.unwrap()
+        });
+        assert!(matches!(map.validate(), Err(MapError::Invalid(_))));
+    }
+
+    #[test]
+    fn spawns_must_be_clear() {
+        let mut map = valid();
+        map.spawns.push([0.0, -1.0, 0.0]); // within the floor
+        assert!(matches!(
+            map.validate(),
+            Err(MapError::Spawn { index: 1, .. })
+        ));
+
+        map.spawns[1] = [f32::INFINITY, 0.0, 0.0];
+        assert!(matches!(
+            map.validate(),
+            Err(MapError::Spawn { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn ambient_must_be_within_range() {
+        let mut map = valid();
+        map.ambient.strength = 2.0;
+        assert!(matches!(map.validate(), Err(MapError::Invalid(_))));
+
+        map.ambient.strength = 0.5;
+        map.ambient.sky = [0.0, -0.1, 0.0];
+        assert!(matches!(map.validate(), Err(MapError::Invalid(_))));
+    }
+}
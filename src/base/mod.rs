@@ -1,18 +1,40 @@
 mod cam;
+mod clock;
+mod collision;
+mod command;
 mod err;
 mod flags;
+mod map;
+mod name;
 mod net;
 mod obj;
+mod procgen;
+mod replay;
+mod sim;
+mod spawn;
+mod text;
 mod threading;
 mod util;
+mod world;
 
 pub use cam::*;
+pub use clock::*;
+pub use collision::*;
+pub use command::*;
 pub use err::*;
 pub use flags::*;
+pub use map::*;
+pub use name::*;
 pub use net::*;
 pub use obj::*;
+pub use procgen::*;
+pub use replay::*;
+pub use sim::*;
+pub use spawn::*;
+pub use text::*;
 pub use threading::*;
 pub use util::*;
+pub use world::*;
 
 pub use crossbeam_utils::Backoff;
 pub use log::{debug, error, info, trace, warn};
@@ -0,0 +1,152 @@
+use crate::*;
+use bytemuck::{Pod, Zeroable};
+use std::fmt;
+
+/// name of players that didn't (validly) choose one
+pub const DEFAULT_NAME: &str = "Player";
+
+/// maximum encoded length of a name (bytes)
+const CAPACITY: usize = 31;
+
+/// A player's (display) name.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Eq, PartialEq, Pod, Zeroable)]
+pub struct Name {
+    len: u8,
+    bytes: [u8; CAPACITY],
+}
+
+impl Name {
+    /// maximum number of characters
+    pub const MAX_CHARS: usize = 16;
+
+    /// Validate a name (surrounding whitespace is ignored).
+    pub fn new(s: &str) -> BlazedResult<Self> {
+        let s = s.trim();
+        let chars = s.chars().count();
+
+        if chars == 0 {
+            return Err("Names must not be empty.".into());
+        }
+        if chars > Self::MAX_CHARS || s.len() > CAPACITY {
+            return Err(format!(
+                "Names must not exceed {} characters ({CAPACITY} bytes).",
+                Self::MAX_CHARS
+            )
+            .into());
+        }
+        if s.chars().any(char::is_control) {
+            return Err("Names must not contain control characters.".into());
+        }
+
+        let mut bytes = [0; CAPACITY];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+
+        Ok(Self {
+            len: s.len() as u8,
+            bytes,
+        })
+    }
+
+    /// The name (empty if it isn't valid UTF-8).
+    pub fn as_str(&self) -> &str {
+        let len = (self.len as usize).min(CAPACITY);
+        std::str::from_utf8(&self.bytes[..len]).unwrap_or_default()
+    }
+
+    /// This name, or the first numbered variant (e.g., `name#2`) that isn't taken,
+    /// falling back to one made of the player's identity (e.g., `Player#61440`).
+    pub fn dedupe(self, id: Id, is_taken: impl Fn(&Self) -> bool) -> Self {
+        if !is_taken(&self) {
+            return self;
+        }
+
+        // there can't be more players than identities to tell them apart
+        (2..=PLAYER_IDS.len())
+            .filter_map(|n| {
+                let suffix = format!("#{n}");
+                Self::new(&format!("{}{suffix}", self.base(suffix.len()))).ok()
+            })
+            .find(|name| !is_taken(name))
+            .or_else(|| Self::new(&format!("{DEFAULT_NAME}#{id}")).ok())
+            .unwrap_or(self)
+    }
+
+    /// The longest start of this name that leaves room for a suffix (of ASCII characters).
+    fn base(&self, suffix: usize) -> &str {
+        let (mut chars, mut end) = (0, 0);
+        for (idx, c) in self.as_str().char_indices() {
+            if chars + suffix >= Self::MAX_CHARS || idx + c.len_utf8() + suffix > CAPACITY {
+                break;
+            }
+            chars += 1;
+            end = idx + c.len_utf8();
+        }
+        &self.as_str()[..end]
+    }
+}
+
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(s: &str) -> Name {
+        Name::new(s).unwrap()
+    }
+
+    #[test]
+    fn free_names_are_kept() {
+        assert_eq!(
+            name("alice").dedupe(PLAYER_IDS.start, |_| false),
+            name("alice")
+        );
+    }
+
+    #[test]
+    fn taken_names_are_numbered() {
+        let taken = [name("bob"), name("bob#2")];
+        let deduped = name("bob").dedupe(PLAYER_IDS.start, |n| taken.contains(n));
+        assert_eq!(deduped.as_str(), "bob#3");
+    }
+
+    #[test]
+    fn long_names_are_shortened_to_fit() {
+        let long = name("abcdefghijklmnop");
+        let deduped = long.dedupe(PLAYER_IDS.start, |n| *n == long);
+        assert_eq!(deduped.as_str(), "abcdefghijklmn#2");
+    }
+
+    #[test]
+    fn multibyte_names_are_cut_on_char_boundaries() {
+        // ten 3-byte characters (30 bytes) leave no room for a suffix
+        let long = name(&"語".repeat(10));
+        let deduped = long.dedupe(PLAYER_IDS.start, |n| *n == long);
+        assert_eq!(deduped.as_str(), format!("{}#2", "語".repeat(9)));
+
+        let taken = (2..=10)
+            .map(|n| name(&format!("{}#{n}", "語".repeat(9))))
+            .chain([long])
+            .collect::<Vec<_>>();
+        let deduped = long.dedupe(PLAYER_IDS.start, |n| taken.contains(n));
+        assert_eq!(deduped.as_str(), format!("{}#11", "語".repeat(9)));
+    }
+
+    #[test]
+    fn identity_is_the_last_resort() {
+        let id = PLAYER_IDS.start + 7;
+        let deduped = name(&"é".repeat(15)).dedupe(id, |n| n.as_str() != format!("Player#{id}"));
+        assert_eq!(deduped.as_str(), format!("Player#{id}"));
+    }
+}
@@ -1,42 +1,124 @@
 use crate::*;
 use std::{
-    io::{Read, Write},
-    net::{SocketAddr, TcpStream, UdpSocket},
-    ops::Deref,
+    fmt::Debug,
+    io::{self, ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream, UdpSocket},
+    time::Duration,
 };
 
-pub trait UdpConn {
-    fn socket(&self) -> &UdpSocket;
+/// Prefix a packet with its (u16 LE) length, as sent over TCP.
+pub fn frame(buf: &[u8]) -> BlazedResult<Vec<u8>> {
+    let len = u16::try_from(buf.len()).map_err(|_| "Packet is too large to frame")?;
 
-    fn send_to(&self, buf: &[u8], addr: &SocketAddr) -> BlazedResult<usize> {
-        self.socket().send_to(buf, addr).map_err(Into::into)
-    }
+    let mut frame = Vec::with_capacity(size_of::<u16>() + buf.len());
+    frame.extend_from_slice(&len.to_le_bytes());
+    frame.extend_from_slice(buf);
+    Ok(frame)
+}
 
-    fn recv_from(&self, buf: &mut [u8]) -> BlazedResult<(usize, SocketAddr)> {
-        self.socket().recv_from(buf).map_err(Into::into)
-    }
+/// Unreliable, unordered datagrams (e.g., a UDP socket or an in-memory one).
+pub trait UdpConn: Debug + Send + Sync {
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize>;
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    /// Bound how long receiving blocks for (unsupported by non-blocking sockets).
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
 }
 
-impl<T: Deref<Target = UdpSocket>> UdpConn for T {
-    fn socket(&self) -> &UdpSocket {
-        self
+/// A reliable, ordered stream of bytes carrying length-prefixed packets
+/// (e.g., a TCP stream or an in-memory one).
+pub trait TcpConn: Debug + Send + Sync {
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize>;
+
+    fn write(&self, buf: &[u8]) -> io::Result<usize>;
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()>;
+
+    /// Bound how long reading blocks for (unsupported by non-blocking streams).
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    fn write_all(&self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => buf = &buf[n..],
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
     }
-}
 
-pub trait TcpConn {
-    fn stream(&self) -> &TcpStream;
+    fn read_exact(&self, mut buf: &mut [u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => buf = &mut buf[n..],
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
 
+    /// Send a single (length-prefixed) packet.
     fn send(&self, buf: &[u8]) -> BlazedResult {
-        self.stream().write_all(buf).map_err(Into::into)
+        // write the frame at once so concurrent senders don't interleave
+        self.write_all(&frame(buf)?).map_err(Into::into)
     }
 
+    /// Receive a single packet, returning its length (0 if the stream has closed).
     fn recv(&self, buf: &mut [u8]) -> BlazedResult<usize> {
-        self.stream().read(buf).map_err(Into::into)
+        let mut len = [0; size_of::<u16>()];
+        match self.read_exact(&mut len) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(0),
+            result => result?,
+        }
+        let len = u16::from_le_bytes(len) as usize;
+
+        let packet = buf
+            .get_mut(..len)
+            .ok_or("Packet exceeds the receiving buffer")?;
+        self.read_exact(packet)?;
+        Ok(len)
+    }
+}
+
+impl UdpConn for UdpSocket {
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        UdpSocket::send_to(self, buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        UdpSocket::recv_from(self, buf)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        UdpSocket::local_addr(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UdpSocket::set_read_timeout(self, timeout)
     }
 }
 
-impl<T: Deref<Target = TcpStream>> TcpConn for T {
-    fn stream(&self) -> &TcpStream {
-        self
+impl TcpConn for TcpStream {
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        Read::read(&mut &*self, buf)
+    }
+
+    fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        Write::write(&mut &*self, buf)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        TcpStream::shutdown(self, how)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
     }
 }
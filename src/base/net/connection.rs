@@ -0,0 +1,420 @@
+use crate::*;
+use crossbeam_channel::{Receiver, Sender, unbounded};
+use std::{
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{sleep, spawn},
+    time::Duration,
+};
+use ultraviolet::Vec3;
+
+/// how often the UDP handshake is repeated (in case it's lost)
+const UDP_RETRY: Duration = Duration::from_millis(100);
+
+/// how often the UDP thread checks whether the connection has closed
+const UDP_POLL: Duration = Duration::from_millis(100);
+
+/// Everything the server tells a client, in the order it was received.
+#[derive(Clone, Debug)]
+pub enum NetEvent {
+    /// The handshake has finished (always the first event).
+    Joined {
+        id: Id,
+        world: World,
+        movement: Movement,
+        players: Vec<UptObj>, // already playing
+    },
+    Added(UptObj),      // a player (including this one) has joined, in full
+    Updated(UptObjOpt), // whatever has changed about a player (over UDP)
+    Removed(Id),
+    Respawned {
+        id: Id,
+        pos: Vec3,
+    },
+    Movement(Movement),                 // the server's rules have changed
+    Notice(String),                     // a message from the server
+    Synced(ClockSample),                // a round-trip to the server's clock (already added)
+    Snapshot(Snapshot),                 // the server's simulation of this player (over UDP)
+    Disconnected(Option<RejectReason>), // always the last event (with the reason, if told)
+}
+
+/// Input of the player, as sent to the server.
+#[derive(Clone, Copy, Debug)]
+pub enum Input {
+    Keyboard(Keyboard),
+    Wheel(Wheel),
+    Motion(MotionOpt),
+}
+
+impl Input {
+    pub fn key(keys: Keys, is_pressed: bool) -> Self {
+        Self::Keyboard(Keyboard {
+            bits: keys.bits(),
+            is_pressed: is_pressed as u8,
+            seq: 0,
+        })
+    }
+
+    /// Number of the input among the player's (0 until sent).
+    pub const fn seq(&self) -> u32 {
+        match self {
+            Self::Keyboard(kb) => kb.seq,
+            Self::Wheel(wheel) => wheel.seq,
+            Self::Motion(motion) => motion.seq,
+        }
+    }
+
+    /// Number the input (for the server to say which it has applied last).
+    pub const fn numbered(mut self, seq: u32) -> Self {
+        match &mut self {
+            Self::Keyboard(kb) => kb.seq = seq,
+            Self::Wheel(wheel) => wheel.seq = seq,
+            Self::Motion(motion) => motion.seq = seq,
+        }
+        self
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        match self {
+            Self::Keyboard(kb) => kb.serialize().to_vec(),
+            Self::Wheel(wheel) => wheel.serialize().to_vec(),
+            Self::Motion(motion) => motion.serialize().to_vec(),
+        }
+    }
+}
+
+/// A client's session with a server, independent of whatever consumes it
+/// (e.g., the renderer, headless bots or tests) and of the transport (e.g., sockets or memory).
+#[derive(Debug)]
+pub struct Connection {
+    id: Id,
+    tcp: Arc<dyn TcpConn>,
+    udp: Arc<dyn UdpConn>,
+    udp_addr: SocketAddr, // of the server
+    sent: u32,            // inputs
+    events: Receiver<NetEvent>,
+    is_closed: Arc<AtomicBool>,
+}
+
+impl Connection {
+    /// Join a server over TCP and UDP, returning once the handshake has finished (or failed).
+    pub fn connect(
+        tcp_addr: SocketAddr,
+        udp_addr: SocketAddr,
+        local_udp_addr: Option<SocketAddr>,
+        hello: ClientHandshake,
+        clock: Clock,
+    ) -> BlazedResult<Self> {
+        debug!("[TCP] Connecting");
+        let tcp = TcpClient::new(tcp_addr)?;
+
+        // any port of the same family as the server's
+        debug!("[UDP] Connecting");
+        let local_udp_addr = local_udp_addr.unwrap_or_else(|| match udp_addr.ip() {
+            IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        });
+        let udp = UdpClient::new(local_udp_addr, udp_addr)?;
+
+        Self::over(tcp, udp, udp_addr, hello, clock)
+    }
+
+    /// Join a server over any stream and datagram socket (e.g., in memory),
+    /// sending datagrams to the server's address.
+    pub fn over(
+        tcp: impl TcpConn + 'static,
+        udp: impl UdpConn + 'static,
+        udp_addr: SocketAddr,
+        hello: ClientHandshake,
+        clock: Clock,
+    ) -> BlazedResult<Self> {
+        let (tcp, udp): (Arc<dyn TcpConn>, Arc<dyn UdpConn>) = (Arc::new(tcp), Arc::new(udp));
+
+        let (sender, events) = unbounded();
+        let (id, joined) = handshake(&*tcp, &*udp, udp_addr, hello)?;
+        sender.send(joined)?;
+
+        // a new server means a new clock
+        clock.reset();
+
+        let is_closed = Arc::<AtomicBool>::default();
+        recv_tcp(
+            tcp.clone(),
+            sender.clone(),
+            clock.clone(),
+            is_closed.clone(),
+        );
+        recv_udp(udp.clone(), tcp.clone(), sender, is_closed.clone())?;
+        sync_clock(tcp.clone(), clock, is_closed.clone());
+
+        Ok(Self {
+            id,
+            tcp,
+            udp,
+            udp_addr,
+            sent: 0,
+            events,
+            is_closed,
+        })
+    }
+
+    /// The player's identity.
+    pub const fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Every event (ending with [`NetEvent::Disconnected`]).
+    pub const fn events(&self) -> &Receiver<NetEvent> {
+        &self.events
+    }
+
+    /// Send the player's input, returning it as numbered.
+    pub fn send(&mut self, input: Input) -> BlazedResult<Input> {
+        self.sent += 1;
+        let input = input.numbered(self.sent);
+        self.udp.send_to(&input.serialize(), self.udp_addr)?;
+        Ok(input)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.is_closed.load(Ordering::Acquire)
+    }
+
+    /// Disconnect (without waiting for anything).
+    pub fn close(&self) {
+        _ = self.tcp.shutdown(Shutdown::Both);
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Obtain the player's identity and the game states from the server.
+fn handshake(
+    tcp: &dyn TcpConn,
+    udp: &dyn UdpConn,
+    udp_addr: SocketAddr,
+    hello: ClientHandshake,
+) -> BlazedResult<(Id, NetEvent)> {
+    let hello = hello.serialize();
+
+    debug!("[TCP] [1] Sending client handshake");
+    tcp.send(&hello)?;
+
+    let mut buf = [0; PACKET_SIZE];
+
+    debug!("[TCP] [2] Receiving server handshake");
+    let n = tcp.recv(&mut buf)?;
+    match buf[0] {
+        _ if n == 0 => return Err("[TCP] [2] Disconnected by server".into()),
+        Rejected::ID => {
+            let reason = Rejected::deserialize(sized(&buf[..n], Rejected::UNPADDED_SIZE)?).reason;
+            return Err(format!("[TCP] [2] Rejected by server: {reason}").into());
+        }
+        ServerHandshake::ID => (),
+        other => return Err(format!("[TCP] [2] Found {other}, expected ServerHandshake").into()),
+    }
+    let id = ServerHandshake::deserialize(sized(&buf[..n], ServerHandshake::UNPADDED_SIZE)?).id();
+
+    // repeated until the world starts arriving
+    debug!("[UDP] [3] Sending client handshake");
+    udp.send_to(&hello, udp_addr)?;
+    tcp.set_read_timeout(Some(UDP_RETRY))?;
+
+    debug!("[TCP] [4] Receiving world and game states");
+    let (mut authored, mut spawns) = (Vec::new(), Vec::new());
+    let (mut generator, mut ambient) = (None, Ambient::default());
+    let (mut movement, mut players) = (None, Vec::new());
+
+    loop {
+        let n = match tcp.recv(&mut buf) {
+            Err(BlazedError::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                udp.send_to(&hello, udp_addr)?;
+                continue;
+            }
+            result => result?,
+        };
+        tcp.set_read_timeout(None)?;
+
+        let packet = &buf[..n];
+        match buf[0] {
+            _ if n == 0 => return Err("[TCP] [4] Disconnected by server".into()),
+            Flush::ID => break,
+            StaticObj::ID => authored.push(StaticObj::deserialize(sized(
+                packet,
+                StaticObj::UNPADDED_SIZE,
+            )?)),
+            Generator::ID => {
+                generator = Some(Generator::deserialize(sized(
+                    packet,
+                    Generator::UNPADDED_SIZE,
+                )?))
+            }
+            SpawnPoint::ID => {
+                spawns.push(SpawnPoint::deserialize(sized(packet, SpawnPoint::UNPADDED_SIZE)?).pos)
+            }
+            Ambient::ID => ambient = Ambient::deserialize(sized(packet, Ambient::UNPADDED_SIZE)?),
+            Movement::ID => {
+                movement = Some(Movement::deserialize(sized(
+                    packet,
+                    Movement::UNPADDED_SIZE,
+                )?))
+            }
+            UptObj::ID => players.push(UptObj::deserialize(sized(packet, UptObj::UNPADDED_SIZE)?)),
+            other => return Err(format!("[TCP] [4] Unexpected packet {other}").into()),
+        }
+    }
+    debug!("[TCP] [5] Finishing");
+
+    let joined = NetEvent::Joined {
+        id,
+        world: World::new(authored, generator, spawns, ambient),
+        movement: movement.ok_or("[TCP] [4] Missing movement rules")?,
+        players,
+    };
+    Ok((id, joined))
+}
+
+/// Receive every TCP packet until disconnected.
+fn recv_tcp(
+    tcp: Arc<dyn TcpConn>,
+    sender: Sender<NetEvent>,
+    clock: Clock,
+    is_closed: Arc<AtomicBool>,
+) {
+    spawn(move || {
+        let mut buf = [0; PACKET_SIZE];
+        let mut reason = None;
+
+        while let Ok(n @ 1..) = tcp.recv(&mut buf) {
+            let client_recv = timestamp();
+            let bytes = &buf[1..n];
+
+            let event = match buf[0] {
+                TimeSync::ID => payload(bytes, TimeSync::UNPADDED_SIZE).map(|bytes| {
+                    NetEvent::Synced(clock.receive(TimeSync::deserialize(bytes), client_recv))
+                }),
+                Respawn::ID => payload(bytes, Respawn::UNPADDED_SIZE).map(|bytes| {
+                    let Respawn { id, pos } = Respawn::deserialize(bytes);
+                    NetEvent::Respawned { id, pos }
+                }),
+                Movement::ID => payload(bytes, Movement::UNPADDED_SIZE)
+                    .map(|bytes| NetEvent::Movement(Movement::deserialize(bytes))),
+                Notice::ID => payload(bytes, Notice::UNPADDED_SIZE)
+                    .map(|bytes| NetEvent::Notice(Notice::deserialize(bytes).text.to_string())),
+                RemObj::ID => payload(bytes, RemObj::UNPADDED_SIZE)
+                    .map(|bytes| NetEvent::Removed(RemObj::deserialize(bytes).id)),
+                UptObj::ID => payload(bytes, UptObj::UNPADDED_SIZE)
+                    .map(|bytes| NetEvent::Added(UptObj::deserialize(bytes))),
+                Rejected::ID => {
+                    // the server closes the stream right after
+                    reason = payload(bytes, Rejected::UNPADDED_SIZE)
+                        .map(|bytes| Rejected::deserialize(bytes).reason);
+                    break;
+                }
+                _ => continue,
+            };
+
+            // a malformed packet ends the session (rather than the thread)
+            let Some(event) = event else {
+                error!("[TCP] Malformed packet {} ({} bytes)", buf[0], n - 1);
+                break;
+            };
+            if sender.send(event).is_err() {
+                break;
+            }
+        }
+
+        _ = tcp.shutdown(Shutdown::Both);
+        is_closed.store(true, Ordering::Release);
+        _ = sender.send(NetEvent::Disconnected(reason));
+    });
+}
+
+/// Receive every object update until disconnected.
+fn recv_udp(
+    udp: Arc<dyn UdpConn>,
+    tcp: Arc<dyn TcpConn>, // shut down if the server sends nonsense
+    sender: Sender<NetEvent>,
+    is_closed: Arc<AtomicBool>,
+) -> BlazedResult {
+    udp.set_read_timeout(Some(UDP_POLL))?;
+
+    spawn(move || {
+        let mut buf = [0; PACKET_SIZE];
+
+        while !is_closed.load(Ordering::Acquire) {
+            let n = match udp.recv_from(&mut buf) {
+                Ok((n, _)) => n,
+
+                // e.g., the server isn't reachable (yet), as reported by ICMP
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::WouldBlock
+                            | ErrorKind::TimedOut
+                            | ErrorKind::ConnectionRefused
+                            | ErrorKind::ConnectionReset
+                    ) =>
+                {
+                    continue;
+                }
+                Err(e) => {
+                    error!("[UDP] {e}");
+                    break;
+                }
+            };
+
+            if n == 0 {
+                continue;
+            }
+            let bytes = &buf[1..n];
+            let event = match buf[0] {
+                UptObjOpt::ID => UptObjOpt::parse(bytes).map(NetEvent::Updated),
+                Snapshot::ID => payload(bytes, Snapshot::UNPADDED_SIZE)
+                    .map(|bytes| NetEvent::Snapshot(Snapshot::deserialize(bytes))),
+                _ => continue,
+            };
+
+            // a malformed datagram ends the session too (the TCP thread reports it)
+            let Some(event) = event else {
+                error!("[UDP] Malformed packet {} ({} bytes)", buf[0], n - 1);
+                _ = tcp.shutdown(Shutdown::Both);
+                break;
+            };
+            if sender.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// The payload of a handshake packet, if it's exactly `len` bytes long.
+fn sized(packet: &[u8], len: usize) -> BlazedResult<&[u8]> {
+    payload(&packet[1..], len).ok_or_else(|| {
+        PacketError::Malformed {
+            id: packet[0],
+            len: packet.len() - 1,
+        }
+        .into()
+    })
+}
+
+/// Periodically request the server's time until disconnected.
+fn sync_clock(tcp: Arc<dyn TcpConn>, clock: Clock, is_closed: Arc<AtomicBool>) {
+    spawn(move || {
+        while !is_closed.load(Ordering::Acquire) && tcp.send(&clock.request().serialize()).is_ok() {
+            sleep(SYNC_RATE);
+        }
+    });
+}
@@ -0,0 +1,82 @@
+use crate::*;
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpStream},
+};
+
+/// environment variable holding the shared secret of the admin control socket
+pub const ADMIN_SECRET_VAR: &str = "BLAZED_ADMIN_SECRET";
+
+/// The outcome of an admin request (output or error message).
+pub type Reply = std::result::Result<String, String>;
+
+/// Write a reply: `ok <n>` followed by `n` lines of output, or `err <msg>`.
+pub fn write_reply(w: &mut impl Write, reply: &Reply) -> BlazedResult {
+    match reply {
+        Ok(out) if out.is_empty() => writeln!(w, "ok 0")?,
+        Ok(out) => writeln!(w, "ok {}\n{out}", out.lines().count())?,
+        Err(e) => writeln!(w, "err {}", e.replace('\n', " "))?,
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Read a reply written by [`write_reply`].
+pub fn read_reply(r: &mut impl BufRead) -> BlazedResult<Reply> {
+    let mut line = String::new();
+    if r.read_line(&mut line)? == 0 {
+        return Err(BlazedError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+
+    let line = line.trim_end();
+    if let Some(e) = line.strip_prefix("err ") {
+        return Ok(Err(e.to_string()));
+    }
+
+    let n = line
+        .strip_prefix("ok ")
+        .ok_or(format!("Malformed reply '{line}'."))?
+        .parse::<usize>()?;
+
+    let mut out = Vec::with_capacity(n);
+    for _ in 0..n {
+        let mut line = String::new();
+        r.read_line(&mut line)?;
+        out.push(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+    Ok(Ok(out.join("\n")))
+}
+
+/// An authenticated connection to a server's admin control socket.
+///
+/// The protocol is line-based: the client first sends `auth <secret>`,
+/// then one [`Command`] per line, each answered with a [`Reply`].
+#[derive(Debug)]
+pub struct ControlClient {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl ControlClient {
+    pub fn connect(addr: SocketAddr, secret: &str) -> BlazedResult<Self> {
+        let writer = TcpStream::connect(addr)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        let mut client = Self { reader, writer };
+
+        client
+            .request(&format!("auth {secret}"))?
+            .map_err(BlazedError::Misc)?;
+        Ok(client)
+    }
+
+    /// Execute a command on the server.
+    pub fn send(&mut self, cmd: &Command) -> BlazedResult<Reply> {
+        self.request(&cmd.to_string())
+    }
+
+    fn request(&mut self, line: &str) -> BlazedResult<Reply> {
+        writeln!(self.writer, "{line}")?;
+        self.writer.flush()?;
+        read_reply(&mut self.reader)
+    }
+}
@@ -0,0 +1,818 @@
+use crate::*;
+use parking_lot::Condvar;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+    io::{self, ErrorKind},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr},
+    sync::{Arc, Weak},
+    thread::spawn,
+    time::{Duration, Instant},
+};
+
+/// first port handed out to anything bound to port 0
+const EPHEMERAL_PORT: u16 = 49152;
+
+/// longest a real-time network's delivery thread sleeps before checking whether it's still in use
+const WIRE_POLL: Duration = Duration::from_millis(100);
+
+/// Conditions of every link within an in-memory network.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Link {
+    pub latency: Duration, // one way, of everything
+    pub jitter: Duration,  // most extra latency of a datagram (reordering them)
+    pub loss: f64,         // chance of a datagram being lost
+}
+
+/// Called whenever something arrives at a socket (e.g., to wake an event loop).
+pub type ReadyHook = Arc<dyn Fn() + Send + Sync>;
+
+/// Something in flight.
+#[derive(Debug)]
+enum Delivery {
+    Connect {
+        listener: SocketAddr,
+        pipe: usize,
+    },
+    Bytes {
+        pipe: usize,
+        to: usize, // end
+        bytes: Vec<u8>,
+    },
+    Eof {
+        pipe: usize,
+        to: usize,
+    },
+    Datagram {
+        from: SocketAddr,
+        to: SocketAddr,
+        bytes: Vec<u8>,
+    },
+}
+
+/// Options of a socket (or of an end of a stream).
+#[derive(Clone, Default)]
+struct Opts {
+    is_nonblocking: bool,
+    read_timeout: Option<Duration>,
+    hook: Option<ReadyHook>,
+}
+
+/// What a socket is, within the network.
+#[derive(Clone, Copy, Debug)]
+enum Handle {
+    End(usize, usize), // of a stream (0 has connected, 1 has been accepted)
+    Socket(SocketAddr),
+    Listener(SocketAddr),
+}
+
+/// Both ends of a stream.
+struct Pipe {
+    addrs: [SocketAddr; 2],
+    inbox: [VecDeque<u8>; 2], // arrived, yet to be read
+    last: [Duration; 2], // arrival of the latest bytes towards each end (keeping them in order)
+    is_eof: [bool; 2],   // the other end has stopped writing (and everything has arrived)
+    is_read_shut: [bool; 2],
+    is_write_shut: [bool; 2],
+    is_dropped: [bool; 2],
+    opts: [Opts; 2],
+}
+
+struct Socket {
+    inbox: VecDeque<(SocketAddr, Vec<u8>)>,
+    opts: Opts,
+}
+
+struct Acceptor {
+    backlog: VecDeque<usize>, // pipes
+    opts: Opts,
+}
+
+struct State {
+    now: Option<Duration>, // of a manual clock
+    link: Link,
+    rng: ChaCha8Rng,
+    seq: u64,
+    flights: BTreeMap<(Duration, u64), Delivery>, // by arrival (then by departure)
+
+    pipes: HashMap<usize, Pipe>,
+    next_pipe: usize,
+    sockets: HashMap<SocketAddr, Socket>,
+    listeners: HashMap<SocketAddr, Acceptor>,
+    next_port: u16,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    cond: Condvar, // something has arrived or changed
+    start: Instant,
+}
+
+/// A network of streams and datagrams that only exists in memory, for exercising
+/// servers and clients deterministically (e.g., within tests).
+///
+/// Everything sent arrives once the link's latency has passed on the network's clock,
+/// which is either real time or advanced by hand. Datagrams may be lost or reordered
+/// (by a seeded generator), whereas streams stay reliable and ordered.
+#[derive(Clone)]
+pub struct MemNet {
+    inner: Arc<Inner>,
+}
+
+impl MemNet {
+    /// A network in real time (delivering in the background).
+    pub fn new(link: Link) -> Self {
+        let net = Self::with_clock(None, link);
+        let inner = Arc::downgrade(&net.inner);
+        spawn(move || wire(inner));
+        net
+    }
+
+    /// A network whose clock only moves by [`MemNet::advance`].
+    pub fn manual(link: Link) -> Self {
+        Self::with_clock(Some(Duration::ZERO), link)
+    }
+
+    fn with_clock(now: Option<Duration>, link: Link) -> Self {
+        let state = State {
+            now,
+            link,
+            rng: ChaCha8Rng::seed_from_u64(0),
+            seq: 0,
+            flights: BTreeMap::new(),
+            pipes: HashMap::new(),
+            next_pipe: 0,
+            sockets: HashMap::new(),
+            listeners: HashMap::new(),
+            next_port: EPHEMERAL_PORT,
+        };
+        let inner = Inner {
+            state: Mutex::new(state),
+            cond: Condvar::new(),
+            start: Instant::now(),
+        };
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+
+    pub fn link(&self) -> Link {
+        self.lock().link
+    }
+
+    /// Change the conditions of every link (affecting only what's sent from now on).
+    pub fn set_link(&self, link: Link) {
+        self.lock().link = link;
+    }
+
+    /// Time on the network's clock.
+    pub fn elapsed(&self) -> Duration {
+        self.now(&self.lock())
+    }
+
+    /// Move a manual clock forward, delivering everything that has arrived by then
+    /// (no effect on a real-time network).
+    pub fn advance(&self, by: Duration) {
+        let mut state = self.lock();
+        if let Some(now) = &mut state.now {
+            *now += by;
+        }
+        self.deliver(state);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner.state.lock()
+    }
+
+    fn now(&self, state: &State) -> Duration {
+        state.now.unwrap_or_else(|| self.inner.start.elapsed())
+    }
+
+    /// Hand over everything that has arrived, then wake whoever is waiting for it.
+    fn deliver(&self, mut state: MutexGuard<'_, State>) {
+        let now = self.now(&state);
+        let mut hooks = Vec::new();
+
+        while let Some(entry) = state.flights.first_entry()
+            && entry.key().0 <= now
+        {
+            let delivery = entry.remove();
+            hooks.extend(state.arrive(delivery));
+        }
+        drop(state);
+
+        self.inner.cond.notify_all();
+        for hook in hooks {
+            hook();
+        }
+    }
+
+    /// Wait for anything to change (`false` once past the deadline).
+    fn wait(&self, state: &mut MutexGuard<'_, State>, deadline: Option<Instant>) -> bool {
+        match deadline {
+            Some(deadline) => !self.inner.cond.wait_until(state, deadline).timed_out(),
+            None => {
+                self.inner.cond.wait(state);
+                true
+            }
+        }
+    }
+
+    /// Wait until a socket can be read from, returning the state to read it with.
+    fn readable(&self, handle: Handle) -> io::Result<MutexGuard<'_, State>> {
+        let mut state = self.lock();
+        let opts = state.opts(handle)?.clone();
+        let deadline = opts.read_timeout.map(|timeout| Instant::now() + timeout);
+
+        while !state.is_ready(handle) {
+            if opts.is_nonblocking || !self.wait(&mut state, deadline) {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+        }
+        Ok(state)
+    }
+
+    fn set_nonblocking(&self, handle: Handle, is_nonblocking: bool) -> io::Result<()> {
+        self.lock().opts(handle)?.is_nonblocking = is_nonblocking;
+        Ok(())
+    }
+
+    fn set_read_timeout(&self, handle: Handle, timeout: Option<Duration>) -> io::Result<()> {
+        if timeout == Some(Duration::ZERO) {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+        self.lock().opts(handle)?.read_timeout = timeout;
+        Ok(())
+    }
+
+    /// Call a hook whenever something arrives (and at once, if something already has).
+    fn set_ready_hook(&self, handle: Handle, hook: Option<ReadyHook>) -> io::Result<()> {
+        let mut state = self.lock();
+        state.opts(handle)?.hook = hook.clone();
+        let is_ready = state.is_ready(handle);
+        drop(state);
+
+        if let Some(hook) = hook
+            && is_ready
+        {
+            hook();
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for MemNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemNet")
+            .field("link", &self.link())
+            .field("elapsed", &self.elapsed())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Deliver in real time for as long as the network is in use.
+fn wire(inner: Weak<Inner>) {
+    while let Some(inner) = inner.upgrade() {
+        let net = MemNet { inner };
+        let mut state = net.lock();
+        let now = net.now(&state);
+
+        let wait = match state.flights.first_key_value() {
+            Some((&(at, _), _)) => at.saturating_sub(now).min(WIRE_POLL),
+            None => WIRE_POLL,
+        };
+        if !wait.is_zero() {
+            net.inner.cond.wait_for(&mut state, wait);
+        }
+        net.deliver(state);
+    }
+}
+
+impl State {
+    /// Send something, arriving at the given time.
+    fn schedule(&mut self, at: Duration, delivery: Delivery) {
+        self.flights.insert((at, self.seq), delivery);
+        self.seq += 1;
+    }
+
+    /// Hand something over to its destination, returning whom to tell.
+    fn arrive(&mut self, delivery: Delivery) -> Option<ReadyHook> {
+        match delivery {
+            Delivery::Connect { listener, pipe } => {
+                if !self.pipes.contains_key(&pipe) {
+                    return None;
+                }
+                match route(&mut self.listeners, listener) {
+                    Some(acceptor) => {
+                        acceptor.backlog.push_back(pipe);
+                        acceptor.opts.hook.clone()
+                    }
+
+                    // stopped listening meanwhile
+                    None => {
+                        let pipe = self.pipes.get_mut(&pipe)?;
+                        pipe.is_eof[0] = true;
+                        pipe.opts[0].hook.clone()
+                    }
+                }
+            }
+            Delivery::Bytes { pipe, to, bytes } => {
+                let pipe = self.pipes.get_mut(&pipe)?;
+                if pipe.is_read_shut[to] {
+                    return None;
+                }
+                pipe.inbox[to].extend(bytes);
+                pipe.opts[to].hook.clone()
+            }
+            Delivery::Eof { pipe, to } => {
+                let pipe = self.pipes.get_mut(&pipe)?;
+                pipe.is_eof[to] = true;
+                pipe.opts[to].hook.clone()
+            }
+            Delivery::Datagram { from, to, bytes } => {
+                let socket = route(&mut self.sockets, to)?;
+                socket.inbox.push_back((from, bytes));
+                socket.opts.hook.clone()
+            }
+        }
+    }
+
+    fn opts(&mut self, handle: Handle) -> io::Result<&mut Opts> {
+        let opts = match handle {
+            Handle::End(pipe, end) => self.pipes.get_mut(&pipe).map(|p| &mut p.opts[end]),
+            Handle::Socket(addr) => self.sockets.get_mut(&addr).map(|s| &mut s.opts),
+            Handle::Listener(addr) => self.listeners.get_mut(&addr).map(|l| &mut l.opts),
+        };
+        opts.ok_or_else(|| ErrorKind::NotConnected.into())
+    }
+
+    /// Whether reading wouldn't block.
+    fn is_ready(&self, handle: Handle) -> bool {
+        match handle {
+            Handle::End(pipe, end) => self
+                .pipes
+                .get(&pipe)
+                .is_none_or(|p| !p.inbox[end].is_empty() || p.is_eof[end] || p.is_read_shut[end]),
+            Handle::Socket(addr) => self.sockets.get(&addr).is_none_or(|s| !s.inbox.is_empty()),
+            Handle::Listener(addr) => self
+                .listeners
+                .get(&addr)
+                .is_none_or(|l| !l.backlog.is_empty()),
+        }
+    }
+
+    /// The address itself, or any free port of its host (if it's port 0).
+    fn bind_addr(
+        &mut self,
+        addr: SocketAddr,
+        is_bound: impl Fn(&Self, SocketAddr) -> bool,
+    ) -> io::Result<SocketAddr> {
+        if addr.port() != 0 {
+            if is_bound(self, addr) {
+                return Err(ErrorKind::AddrInUse.into());
+            }
+            return Ok(addr);
+        }
+
+        for _ in 0..=u16::MAX - EPHEMERAL_PORT {
+            let addr = SocketAddr::new(addr.ip(), self.ephemeral_port());
+            if !is_bound(self, addr) {
+                return Ok(addr);
+            }
+        }
+        Err(ErrorKind::AddrInUse.into())
+    }
+
+    fn ephemeral_port(&mut self) -> u16 {
+        let port = self.next_port;
+        self.next_port = port.checked_add(1).unwrap_or(EPHEMERAL_PORT);
+        port
+    }
+
+    /// Arrival of whatever is sent along the link now (`None` if it's lost).
+    fn arrival(&mut self, now: Duration, is_lossy: bool) -> Option<Duration> {
+        let link = self.link;
+        if !is_lossy {
+            return Some(now + link.latency);
+        }
+        if link.loss > 0.0 && self.rng.random_bool(link.loss.min(1.0)) {
+            return None;
+        }
+
+        let jitter = link.jitter.mul_f64(self.rng.random());
+        Some(now + link.latency + jitter)
+    }
+}
+
+/// Whatever is bound to an address, or to the unspecified address of its port.
+fn route<T>(bound: &mut HashMap<SocketAddr, T>, addr: SocketAddr) -> Option<&mut T> {
+    if !bound.contains_key(&addr) {
+        let any = SocketAddr::new(unspecified(addr.ip()), addr.port());
+        return bound.get_mut(&any);
+    }
+    bound.get_mut(&addr)
+}
+
+const fn unspecified(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    }
+}
+
+const fn loopback(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+    }
+}
+
+/// An end of a stream (closed once every clone has been dropped).
+struct End {
+    net: MemNet,
+    pipe: usize,
+    end: usize,
+    addrs: [SocketAddr; 2], // local, peer
+}
+
+impl Drop for End {
+    fn drop(&mut self) {
+        let mut state = self.net.lock();
+        let now = self.net.now(&state);
+        let latency = state.link.latency;
+        let (end, other) = (self.end, 1 - self.end);
+        let Some(pipe) = state.pipes.get_mut(&self.pipe) else {
+            return;
+        };
+
+        pipe.is_dropped[end] = true;
+        pipe.is_read_shut[end] = true;
+        pipe.inbox[end].clear();
+        if pipe.is_dropped[other] {
+            state.pipes.remove(&self.pipe);
+        } else if !pipe.is_write_shut[end] {
+            pipe.is_write_shut[end] = true;
+            let at = (now + latency).max(pipe.last[other]);
+            let (pipe, to) = (self.pipe, other);
+            state.schedule(at, Delivery::Eof { pipe, to });
+        }
+        self.net.deliver(state);
+    }
+}
+
+/// A stream within an in-memory network.
+#[derive(Clone)]
+pub struct MemStream {
+    inner: Arc<End>,
+}
+
+impl MemStream {
+    /// Connect to a listener (refused if there's none).
+    pub fn connect(net: &MemNet, addr: SocketAddr) -> io::Result<Self> {
+        let mut state = net.lock();
+        if route(&mut state.listeners, addr).is_none() {
+            return Err(ErrorKind::ConnectionRefused.into());
+        }
+        let now = net.now(&state);
+        let local = SocketAddr::new(loopback(addr.ip()), state.ephemeral_port());
+
+        let id = state.next_pipe;
+        state.next_pipe += 1;
+        let at = state.arrival(now, false).unwrap();
+        let pipe = Pipe {
+            addrs: [local, addr],
+            inbox: Default::default(),
+            last: [at; 2],
+            is_eof: [false; 2],
+            is_read_shut: [false; 2],
+            is_write_shut: [false; 2],
+            is_dropped: [false, true], // until accepted
+            opts: Default::default(),
+        };
+        state.pipes.insert(id, pipe);
+        state.schedule(
+            at,
+            Delivery::Connect {
+                listener: addr,
+                pipe: id,
+            },
+        );
+        net.deliver(state);
+
+        Ok(Self::new(net.clone(), id, 0, [local, addr]))
+    }
+
+    fn new(net: MemNet, pipe: usize, end: usize, addrs: [SocketAddr; 2]) -> Self {
+        let inner = Arc::new(End {
+            net,
+            pipe,
+            end,
+            addrs,
+        });
+        Self { inner }
+    }
+
+    fn handle(&self) -> Handle {
+        Handle::End(self.inner.pipe, self.inner.end)
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.inner.addrs[0]
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.inner.addrs[1]
+    }
+
+    pub fn set_nonblocking(&self, is_nonblocking: bool) -> io::Result<()> {
+        self.inner
+            .net
+            .set_nonblocking(self.handle(), is_nonblocking)
+    }
+
+    /// Call a hook whenever something arrives (e.g., bytes or the end of the stream).
+    pub fn set_ready_hook(&self, hook: Option<ReadyHook>) -> io::Result<()> {
+        self.inner.net.set_ready_hook(self.handle(), hook)
+    }
+}
+
+impl TcpConn for MemStream {
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let End { net, pipe, end, .. } = &*self.inner;
+        let mut state = net.readable(self.handle())?;
+        let Some(pipe) = state.pipes.get_mut(pipe) else {
+            return Ok(0);
+        };
+        if pipe.is_read_shut[*end] {
+            return Ok(0);
+        }
+
+        let n = buf.len().min(pipe.inbox[*end].len());
+        for (dst, src) in buf.iter_mut().zip(pipe.inbox[*end].drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+
+    fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        let End {
+            net, pipe: id, end, ..
+        } = &*self.inner;
+        let other = 1 - end;
+        let mut state = net.lock();
+        let now = net.now(&state);
+        let at = state.arrival(now, false).unwrap();
+
+        let pipe = state.pipes.get_mut(id).ok_or(ErrorKind::NotConnected)?;
+        if pipe.is_write_shut[*end] {
+            return Err(ErrorKind::BrokenPipe.into());
+        }
+        if pipe.is_eof[*end] && pipe.is_dropped[other] {
+            return Err(ErrorKind::ConnectionReset.into());
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let at = at.max(pipe.last[other]);
+        pipe.last[other] = at;
+        let bytes = buf.to_vec();
+        state.schedule(
+            at,
+            Delivery::Bytes {
+                pipe: *id,
+                to: other,
+                bytes,
+            },
+        );
+        net.deliver(state);
+        Ok(buf.len())
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        let End {
+            net, pipe: id, end, ..
+        } = &*self.inner;
+        let other = 1 - end;
+        let mut state = net.lock();
+        let now = net.now(&state);
+        let at = state.arrival(now, false).unwrap();
+
+        let pipe = state.pipes.get_mut(id).ok_or(ErrorKind::NotConnected)?;
+        if matches!(how, Shutdown::Read | Shutdown::Both) {
+            pipe.is_read_shut[*end] = true;
+            pipe.inbox[*end].clear();
+        }
+        if matches!(how, Shutdown::Write | Shutdown::Both) && !pipe.is_write_shut[*end] {
+            pipe.is_write_shut[*end] = true;
+            let at = at.max(pipe.last[other]);
+            state.schedule(
+                at,
+                Delivery::Eof {
+                    pipe: *id,
+                    to: other,
+                },
+            );
+        }
+        net.deliver(state);
+        Ok(())
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.net.set_read_timeout(self.handle(), timeout)
+    }
+}
+
+impl fmt::Debug for MemStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemStream")
+            .field("local", &self.local_addr())
+            .field("peer", &self.peer_addr())
+            .finish()
+    }
+}
+
+/// Accepts streams within an in-memory network.
+pub struct MemListener {
+    net: MemNet,
+    addr: SocketAddr,
+}
+
+impl MemListener {
+    pub fn bind(net: &MemNet, addr: SocketAddr) -> io::Result<Self> {
+        let mut state = net.lock();
+        let addr = state.bind_addr(addr, |state, addr| state.listeners.contains_key(&addr))?;
+        let acceptor = Acceptor {
+            backlog: VecDeque::new(),
+            opts: Opts::default(),
+        };
+        state.listeners.insert(addr, acceptor);
+
+        Ok(Self {
+            net: net.clone(),
+            addr,
+        })
+    }
+
+    const fn handle(&self) -> Handle {
+        Handle::Listener(self.addr)
+    }
+
+    pub fn accept(&self) -> io::Result<(MemStream, SocketAddr)> {
+        let mut state = self.net.readable(self.handle())?;
+        let acceptor = state.listeners.get_mut(&self.addr).unwrap();
+
+        // skipping whoever has given up meanwhile
+        let mut backlog = std::mem::take(&mut acceptor.backlog);
+        let accepted = loop {
+            let id = backlog.pop_front().ok_or(ErrorKind::WouldBlock)?;
+            if let Some(pipe) = state.pipes.get_mut(&id) {
+                pipe.is_dropped[1] = false;
+                break (id, pipe.addrs);
+            }
+        };
+        state.listeners.get_mut(&self.addr).unwrap().backlog = backlog;
+        drop(state);
+
+        let (id, [peer, local]) = accepted;
+        let stream = MemStream::new(self.net.clone(), id, 1, [local, peer]);
+        Ok((stream, peer))
+    }
+
+    pub const fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn set_nonblocking(&self, is_nonblocking: bool) -> io::Result<()> {
+        self.net.set_nonblocking(self.handle(), is_nonblocking)
+    }
+
+    /// Call a hook whenever a stream is waiting to be accepted.
+    pub fn set_ready_hook(&self, hook: Option<ReadyHook>) -> io::Result<()> {
+        self.net.set_ready_hook(self.handle(), hook)
+    }
+}
+
+impl Drop for MemListener {
+    fn drop(&mut self) {
+        let mut state = self.net.lock();
+        let Some(acceptor) = state.listeners.remove(&self.addr) else {
+            return;
+        };
+
+        // never to be accepted
+        for id in acceptor.backlog {
+            if let Some(pipe) = state.pipes.get_mut(&id) {
+                pipe.is_eof[0] = true;
+            }
+        }
+        self.net.deliver(state);
+    }
+}
+
+impl fmt::Debug for MemListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemListener")
+            .field("addr", &self.addr)
+            .finish()
+    }
+}
+
+/// A bound datagram socket (unbound once every clone has been dropped).
+struct Bound {
+    net: MemNet,
+    addr: SocketAddr,
+}
+
+impl Drop for Bound {
+    fn drop(&mut self) {
+        self.net.lock().sockets.remove(&self.addr);
+    }
+}
+
+/// A datagram socket within an in-memory network.
+#[derive(Clone)]
+pub struct MemUdp {
+    inner: Arc<Bound>,
+}
+
+impl MemUdp {
+    pub fn bind(net: &MemNet, addr: SocketAddr) -> io::Result<Self> {
+        let mut state = net.lock();
+        let addr = state.bind_addr(addr, |state, addr| state.sockets.contains_key(&addr))?;
+        let socket = Socket {
+            inbox: VecDeque::new(),
+            opts: Opts::default(),
+        };
+        state.sockets.insert(addr, socket);
+
+        let net = net.clone();
+        Ok(Self {
+            inner: Arc::new(Bound { net, addr }),
+        })
+    }
+
+    fn handle(&self) -> Handle {
+        Handle::Socket(self.inner.addr)
+    }
+
+    pub fn set_nonblocking(&self, is_nonblocking: bool) -> io::Result<()> {
+        self.inner
+            .net
+            .set_nonblocking(self.handle(), is_nonblocking)
+    }
+
+    /// Call a hook whenever a datagram arrives.
+    pub fn set_ready_hook(&self, hook: Option<ReadyHook>) -> io::Result<()> {
+        self.inner.net.set_ready_hook(self.handle(), hook)
+    }
+}
+
+impl UdpConn for MemUdp {
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        let Bound { net, addr: from } = &*self.inner;
+        let mut state = net.lock();
+        let now = net.now(&state);
+
+        if let Some(at) = state.arrival(now, true) {
+            let delivery = Delivery::Datagram {
+                from: *from,
+                to: addr,
+                bytes: buf.to_vec(),
+            };
+            state.schedule(at, delivery);
+        }
+        net.deliver(state);
+        Ok(buf.len())
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let Bound { net, addr } = &*self.inner;
+        let mut state = net.readable(self.handle())?;
+        let socket = state.sockets.get_mut(addr).ok_or(ErrorKind::NotConnected)?;
+        let (from, bytes) = socket.inbox.pop_front().unwrap();
+
+        // the rest of a datagram that doesn't fit is lost
+        let n = buf.len().min(bytes.len());
+        buf[..n].copy_from_slice(&bytes[..n]);
+        Ok((n, from))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.inner.addr)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.net.set_read_timeout(self.handle(), timeout)
+    }
+}
+
+impl fmt::Debug for MemUdp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemUdp")
+            .field("addr", &self.inner.addr)
+            .finish()
+    }
+}
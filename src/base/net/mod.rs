@@ -1,10 +1,16 @@
 mod conn;
+mod connection;
+mod control;
+mod mem;
 mod packet;
 mod tcp;
 mod udp;
 mod util;
 
 pub use conn::*;
+pub use connection::*;
+pub use control::*;
+pub use mem::*;
 pub use packet::*;
 pub use tcp::*;
 pub use udp::*;
@@ -2,15 +2,49 @@ use crate::*;
 use std::fmt::Debug;
 use wopt::*;
 
+/// NTP-style time exchange (timestamps in µs of each side's own clock).
 #[derive(Clone, Copy, Debug, WithOpt)]
-pub struct Ping;
+#[wopt(derive(Clone, Copy, Debug, Default))]
+pub struct TimeSync {
+    pub client_send: u64,
+    pub server_recv: u64,
+    pub server_send: u64,
+
+    // (requests) the latest reply, echoed back so the server can time the exchange too (0 without one)
+    pub echo_send: u64, // its `server_send`
+    pub echo_recv: u64, // when the client received it
+}
+
+impl TimeSync {
+    pub const fn new(client_send: u64) -> Self {
+        Self {
+            client_send,
+            server_recv: 0,
+            server_send: 0,
+            echo_send: 0,
+            echo_recv: 0,
+        }
+    }
+
+    /// Echo a reply the client received at `client_recv`.
+    pub const fn echoing(self, reply: &Self, client_recv: u64) -> Self {
+        Self {
+            echo_send: reply.server_send,
+            echo_recv: client_recv,
+            ..self
+        }
+    }
+}
 
 #[derive(Clone, Copy, Debug, WithOpt)]
 pub struct Flush;
 
 #[derive(Clone, Copy, Debug, WithOpt)]
 #[wopt(derive(Clone, Copy, Debug, Default))]
-pub struct ClientHandshake;
+pub struct ClientHandshake {
+    pub name: Name,  // requested name (the server may change it)
+    pub color: Vec3, // preferred color
+}
 
 #[derive(Clone, Copy, Debug, WithOpt)]
 #[wopt(derive(Clone, Copy, Debug, Default))]
@@ -26,11 +60,26 @@ impl ServerHandshake {
     }
 }
 
+/// The server refused to let the client join (instead of a [`ServerHandshake`]) or stopped serving it.
+#[derive(Clone, Copy, Debug, WithOpt)]
+#[wopt(derive(Clone, Copy, Debug, Default))]
+pub struct Rejected {
+    pub reason: RejectReason,
+}
+
+/// A message from the server to every player.
+#[derive(Clone, Copy, Debug, WithOpt)]
+#[wopt(derive(Clone, Copy, Debug, Default))]
+pub struct Notice {
+    pub text: Text,
+}
+
 #[derive(Clone, Copy, Debug, WithOpt)]
 #[wopt(derive(Clone, Copy, Debug, Default))]
 pub struct Keyboard {
     pub bits: u16,
     pub is_pressed: u8,
+    pub seq: u32, // of the player's inputs (numbered once sent, see `Input::numbered`)
 }
 
 impl From<Keyboard> for Keys {
@@ -43,6 +92,7 @@ impl From<Keyboard> for Keys {
 #[wopt(derive(Clone, Copy, Debug, Default))]
 pub struct Wheel {
     pub precise_y: f32,
+    pub seq: u32,
 }
 
 #[derive(Clone, Copy, Debug, WithOpt)]
@@ -50,6 +100,8 @@ pub struct Wheel {
 pub struct Motion {
     pub xrel: i32,
     pub yrel: i32,
+    #[wopt(required)]
+    pub seq: u32,
 }
 
 #[derive(Clone, Copy, Debug, WithOpt)]
@@ -58,12 +110,21 @@ pub struct RemObj {
     pub id: Id,
 }
 
+/// A player has been (re)placed at a spawn point.
+#[derive(Clone, Copy, Debug, WithOpt)]
+#[wopt(derive(Clone, Copy, Debug, Default))]
+pub struct Respawn {
+    pub id: Id,
+    pub pos: Vec3,
+}
+
 #[derive(Clone, Copy, Debug, Default, WithOpt)]
 #[wopt(derive(Clone, Copy, Debug, Default))]
 pub struct UptObj {
     #[wopt(required)]
     pub id: Id,
     pub kind: ObjType,
+    pub name: Name,
     pub dim: Vec3,
     pub color: Color,
     #[wopt(optional, serde)]
@@ -71,3 +132,100 @@ pub struct UptObj {
     #[wopt(required)]
     pub keys: Keys, // TODO - remove this
 }
+
+/// The server's simulation of the receiving player after a tick (to check predictions against).
+#[derive(Clone, Copy, Debug, WithOpt)]
+#[wopt(derive(Clone, Copy, Debug, Default))]
+pub struct Snapshot {
+    pub tick: u64,
+    pub hash: u64,       // of the player's state (see `UptObj::state_hash`)
+    pub input_tick: u64, // when the player's latest input (or respawn) was applied
+    pub input_seq: u32,  // of the latest input (0 if the player has respawned or joined since)
+    pub keys: Keys,
+    #[wopt(optional, serde)]
+    pub cam: CameraAttr,
+}
+
+/// The payload of a fixed-size message, if it's exactly `len` (its `UNPADDED_SIZE`) bytes long
+/// (deserializing a shorter one panics).
+pub fn payload(bytes: &[u8], len: usize) -> Option<&[u8]> {
+    (bytes.len() == len).then_some(bytes)
+}
+
+impl MotionOpt {
+    /// Deserialize a payload, if it's well-formed.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        parse_opt(bytes, Self::deserialize, Self::serialize)
+    }
+}
+
+impl UptObjOpt {
+    /// Deserialize a payload, if it's well-formed.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        parse_opt(bytes, Self::deserialize, Self::serialize)
+    }
+}
+
+/// Deserialize a variable-size message (whose length depends on which of its fields are set),
+/// if it's exactly as long as it says.
+fn parse_opt<T>(
+    bytes: &[u8],
+    deserialize: impl Fn(&[u8]) -> T,
+    serialize: impl Fn(&T) -> Vec<u8>,
+) -> Option<T> {
+    if bytes.len() >= PACKET_SIZE {
+        return None;
+    }
+
+    // zeroes past the end keep a truncated payload from being read out of bounds
+    let mut padded = [0; PACKET_SIZE];
+    padded[..bytes.len()].copy_from_slice(bytes);
+    let message = deserialize(&padded);
+
+    // (without the identity byte)
+    (serialize(&message).len() == 1 + bytes.len()).then_some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payloads_must_be_exactly_sized() {
+        let kb = Keyboard {
+            bits: 1,
+            is_pressed: 1,
+            seq: 1,
+        }
+        .serialize();
+
+        assert!(payload(&kb[1..], Keyboard::UNPADDED_SIZE).is_some());
+        assert!(payload(&kb[1..2], Keyboard::UNPADDED_SIZE).is_none());
+        assert!(payload(&[], Keyboard::UNPADDED_SIZE).is_none());
+        assert!(payload(&[0; 64], Keyboard::UNPADDED_SIZE).is_none());
+    }
+
+    #[test]
+    fn opt_payloads_must_match_their_fields() {
+        let motion = MotionOpt {
+            xrel: Some(3),
+            yrel: None,
+            seq: 1,
+        }
+        .serialize();
+
+        let parsed = MotionOpt::parse(&motion[1..]).unwrap();
+        assert_eq!((parsed.xrel, parsed.yrel), (Some(3), None));
+
+        // truncated, padded or empty
+        assert!(MotionOpt::parse(&motion[1..motion.len() - 1]).is_none());
+        assert!(MotionOpt::parse(&[&motion[1..], &[0]].concat()).is_none());
+        assert!(MotionOpt::parse(&[]).is_none());
+        assert!(UptObjOpt::parse(&[0xff]).is_none());
+
+        // the largest update still fits in a packet
+        let full = UptObj::default().into_opt().serialize();
+        assert!(full.len() < PACKET_SIZE);
+        assert!(UptObjOpt::parse(&full[1..]).is_some());
+    }
+}
@@ -1,8 +1,10 @@
 use crate::*;
 use std::{
-    net::{SocketAddr, TcpListener, TcpStream},
+    io,
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
     ops::Deref,
     sync::Arc,
+    time::Duration,
 };
 
 #[derive(Clone, Debug)]
@@ -17,6 +19,24 @@ impl TcpClient {
     }
 }
 
+impl TcpConn for TcpClient {
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        TcpConn::read(&*self.inner, buf)
+    }
+
+    fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        TcpConn::write(&*self.inner, buf)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+}
+
 impl Deref for TcpClient {
     type Target = TcpStream;
 
@@ -36,6 +56,10 @@ impl TcpServer {
         Ok(Self { inner })
     }
 
+    pub fn local_addr(&self) -> BlazedResult<SocketAddr> {
+        self.inner.local_addr().map_err(Into::into)
+    }
+
     pub fn incoming(&self) -> impl Iterator<Item = TcpClient> + '_ {
         self.inner.incoming().filter_map(|s| {
             if let Ok(stream) = s {
@@ -1,8 +1,10 @@
 use crate::*;
 use std::{
+    io,
     net::{SocketAddr, UdpSocket},
     ops::Deref,
     sync::Arc,
+    time::Duration,
 };
 
 #[derive(Clone, Debug)]
@@ -27,6 +29,24 @@ impl UdpClient {
     }
 }
 
+impl UdpConn for UdpClient {
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        self.inner.send_to(buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.inner.recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+}
+
 impl Deref for UdpClient {
     type Target = UdpSocket;
 
@@ -47,6 +67,24 @@ impl UdpServer {
     }
 }
 
+impl UdpConn for UdpServer {
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        self.inner.send_to(buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.inner.recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+}
+
 impl Deref for UdpServer {
     type Target = UdpSocket;
 
@@ -90,6 +90,17 @@ impl Transformations {
         self.model
     }
 
+    /// The axis-aligned box of this (unrotated) object.
+    pub fn bounds(&self) -> Aabb {
+        let pos = self.translation.extract_translation();
+        let dim = Vec3::new(
+            self.scale.cols[0].x,
+            self.scale.cols[1].y,
+            self.scale.cols[2].z,
+        );
+        Aabb::from_center(pos, dim)
+    }
+
     pub fn model_upt(&mut self) {
         let t = self.translation();
         let r = self.rotation();
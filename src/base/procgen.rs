@@ -0,0 +1,179 @@
+use crate::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use wopt::*;
+
+/// A fresh seed for worlds that weren't given one.
+pub fn random_seed() -> u64 {
+    rand::random()
+}
+
+/// Seeded procedural generation of random cubes.
+///
+/// Only this (not the generated objects) is sent to clients, so every
+/// participant must produce the identical world from the same parameters.
+#[derive(Clone, Copy, Debug, PartialEq, WithOpt)]
+#[wopt(derive(Clone, Copy, Debug, Default))]
+pub struct Generator {
+    pub seed: u64,
+    pub count: u16,     // number of cubes
+    pub extent: f32,    // cubes are centered within [-extent, extent] on each axis
+    pub size_min: f32,  // minimum half extent of a cube (per axis)
+    pub size_max: f32,  // maximum half extent of a cube (per axis)
+    pub alpha_min: f32, // minimum alpha of a cube
+    pub alpha_max: f32, // maximum alpha of a cube
+    pub exclusion: f32, // no cube comes within this distance of a spawn point
+}
+
+impl Generator {
+    /// maximum placement attempts per cube (before giving up on it)
+    const MAX_ATTEMPTS: usize = 32;
+
+    /// The stress-test cubes of the demo world.
+    pub const fn demo(seed: u64) -> Self {
+        Self {
+            seed,
+            count: 4091,
+            extent: 1000.0,
+            size_min: 1.0,
+            size_max: 20.0,
+            alpha_min: 0.1,
+            alpha_max: 1.0,
+            exclusion: 8.0,
+        }
+    }
+
+    pub fn validate(&self) -> BlazedResult {
+        let values = [
+            self.extent,
+            self.size_min,
+            self.size_max,
+            self.alpha_min,
+            self.alpha_max,
+            self.exclusion,
+        ];
+        if values.iter().any(|x| !x.is_finite()) {
+            return Err("Generator parameters must be finite.".into());
+        }
+        if self.extent <= 0.0 {
+            return Err("Generator extent must be positive.".into());
+        }
+        if !(self.size_min > 0.0 && self.size_min <= self.size_max) {
+            return Err("Generator size range must be positive (min <= max).".into());
+        }
+        if !(self.alpha_min > 0.0 && self.alpha_min <= self.alpha_max && self.alpha_max <= 1.0) {
+            return Err("Generator alpha range must be within (0.0, 1.0] (min <= max).".into());
+        }
+        if self.exclusion < 0.0 {
+            return Err("Generator exclusion must not be negative.".into());
+        }
+        Ok(())
+    }
+
+    /// Generate every cube, numbered from `first_id`, keeping clear of the spawn points.
+    pub fn generate(&self, first_id: Id, spawns: &[Vec3]) -> Vec<StaticObj> {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+
+        // without spawn points, players spawn at the origin
+        let origin = [Vec3::zero()];
+        let spawns = if spawns.is_empty() { &origin } else { spawns };
+
+        let zones = spawns
+            .iter()
+            .map(|&pos| Aabb::from_center(pos, Vec3::broadcast(self.exclusion)))
+            .collect::<Vec<_>>();
+
+        let count = (self.count as usize).min(WORLD_IDS.end.saturating_sub(first_id) as usize);
+        let mut objects = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            // draw until the cube is clear of every zone (deterministically)
+            let cube = (0..Self::MAX_ATTEMPTS).find_map(|_| {
+                let pos = Vec3::new(
+                    rng.random_range(-self.extent..=self.extent),
+                    rng.random_range(-self.extent..=self.extent),
+                    rng.random_range(-self.extent..=self.extent),
+                );
+                let dim = Vec3::new(
+                    rng.random_range(self.size_min..=self.size_max),
+                    rng.random_range(self.size_min..=self.size_max),
+                    rng.random_range(self.size_min..=self.size_max),
+                );
+                let color = [
+                    rng.random_range(0.0..=1.0),
+                    rng.random_range(0.0..=1.0),
+                    rng.random_range(0.0..=1.0),
+                    rng.random_range(self.alpha_min..=self.alpha_max),
+                ];
+
+                let bounds = Aabb::from_center(pos, dim);
+                zones
+                    .iter()
+                    .all(|zone| !zone.intersects(&bounds))
+                    .then_some((pos, dim, color))
+            });
+
+            if let Some((pos, dim, color)) = cube {
+                objects.push(StaticObj::new(
+                    first_id + objects.len() as Id,
+                    MeshKind::SimpleCube,
+                    pos,
+                    dim,
+                    Color::new(color, false),
+                ));
+            }
+        }
+        objects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator(seed: u64) -> Generator {
+        Generator {
+            count: 200,
+            extent: 100.0,
+            ..Generator::demo(seed)
+        }
+    }
+
+    /// Everything a player is sent about each cube.
+    fn cubes(objects: &[StaticObj]) -> Vec<(Id, Aabb, [f32; 4])> {
+        objects
+            .iter()
+            .map(|o| (o.id, o.bounds(), o.color.data()))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_cubes() {
+        let spawns = [Vec3::zero(), Vec3::new(50.0, 0.0, 50.0)];
+        let first = generator(42).generate(5, &spawns);
+        let second = generator(42).generate(5, &spawns);
+
+        assert!(!first.is_empty());
+        assert_eq!(cubes(&first), cubes(&second));
+        assert_eq!(first[0].id, 5);
+    }
+
+    #[test]
+    fn another_seed_generates_other_cubes() {
+        let first = cubes(&generator(42).generate(0, &[]));
+        let other = cubes(&generator(43).generate(0, &[]));
+        assert_ne!(first, other);
+        assert!(first.iter().zip(&other).all(|(a, b)| a.1 != b.1));
+    }
+
+    #[test]
+    fn cubes_keep_clear_of_spawns() {
+        let spawn = Vec3::new(10.0, -20.0, 30.0);
+        let generator = generator(7);
+        let zone = Aabb::from_center(spawn, Vec3::broadcast(generator.exclusion));
+
+        let objects = generator.generate(0, &[spawn]);
+        assert!(objects.len() <= generator.count as usize);
+        assert!(objects.iter().all(|o| !o.bounds().intersects(&zone)));
+    }
+}
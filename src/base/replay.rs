@@ -0,0 +1,467 @@
+use crate::*;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufReader, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use ultraviolet::Vec3;
+
+/// first bytes of every replay file
+pub const REPLAY_MAGIC: [u8; 4] = *b"BLZR";
+
+/// format of the replay files written (and read) by this build
+pub const REPLAY_VERSION: u16 = 3;
+
+/// extension of replay files
+pub const REPLAY_EXT: &str = "replay";
+
+// record tags (packet identities may differ between builds, so files have their own)
+mod tag {
+    pub const AMBIENT: u8 = 1;
+    pub const SPAWN: u8 = 2;
+    pub const STATIC: u8 = 3;
+    pub const GENERATOR: u8 = 4;
+    pub const PLAYER: u8 = 5;
+    pub const BEGIN: u8 = 6;
+    pub const TICK: u8 = 7;
+    pub const JOINED: u8 = 8;
+    pub const LEFT: u8 = 9;
+    pub const KEYBOARD: u8 = 10;
+    pub const WHEEL: u8 = 11;
+    pub const MOTION: u8 = 12;
+    pub const RESPAWNED: u8 = 13;
+    pub const MOVEMENT: u8 = 14;
+    pub const STATE: u8 = 15;
+}
+
+/// The game as it was at some point of a recording (e.g., the start of a file).
+#[derive(Clone, Debug)]
+pub struct ReplayState {
+    pub world: World,
+    pub movement: Movement,
+    pub players: BTreeMap<Id, UptObj>,
+}
+
+impl ReplayState {
+    pub fn new(world: World, movement: Movement) -> Self {
+        Self {
+            world,
+            movement,
+            players: Default::default(),
+        }
+    }
+
+    /// Advance the game by a recorded tick (as the server did).
+    pub fn apply(&mut self, frame: &ReplayFrame) {
+        for event in &frame.events {
+            match *event {
+                ReplayEvent::Joined(obj) => {
+                    self.players.insert(obj.id, obj);
+                }
+                ReplayEvent::Left(id) => {
+                    self.players.remove(&id);
+                }
+                ReplayEvent::Input { id, input } => {
+                    if let Some(player) = self.players.get_mut(&id) {
+                        player.apply(input, &self.movement);
+                    }
+                }
+                ReplayEvent::Respawned { id, pos } => {
+                    if let Some(player) = self.players.get_mut(&id) {
+                        player.cam.respawn(pos);
+                    }
+                }
+                ReplayEvent::Movement(movement) => self.movement = movement,
+                ReplayEvent::State(mut upt) => {
+                    if let Some(player) = self.players.get_mut(&upt.id) {
+                        player.patch(&mut upt);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Something the server saw (or did) within a tick.
+#[derive(Clone, Copy, Debug)]
+pub enum ReplayEvent {
+    Joined(UptObj), // in full
+    Left(Id),
+    Input { id: Id, input: Input }, // accepted (i.e., within the rate limit)
+    Respawned { id: Id, pos: Vec3 },
+    Movement(Movement), // the rules have changed
+    State(UptObjOpt),   // whatever has changed about a player since its last state
+}
+
+/// Everything the server saw within a single tick, in order.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayFrame {
+    pub tick: u64,      // simulation steps so far
+    pub time: Duration, // since the recording began
+    pub hash: u64,      // of the game after the tick (see `tick_hash`)
+    pub events: Vec<ReplayEvent>,
+}
+
+/// Writes a replay file: its header and starting state, then every frame.
+///
+/// Every record is length-prefixed (as packets are over TCP), tagged and followed by the
+/// packet's own encoding, so each file can be played on its own.
+#[derive(Debug)]
+pub struct ReplayWriter<W: Write> {
+    inner: W,
+    size: u64, // bytes written
+}
+
+impl<W: Write> ReplayWriter<W> {
+    /// Begin a replay at the given state.
+    pub fn new(inner: W, state: &ReplayState) -> BlazedResult<Self> {
+        let mut writer = Self { inner, size: 0 };
+        writer.write_all(&REPLAY_MAGIC)?;
+        writer.write_all(&REPLAY_VERSION.to_le_bytes())?;
+
+        let world = &state.world;
+        writer.record(tag::AMBIENT, &world.ambient().serialize())?;
+        for &pos in world.spawns() {
+            writer.record(tag::SPAWN, &SpawnPoint { pos }.serialize())?;
+        }
+        for obj in world.authored() {
+            writer.record(tag::STATIC, &obj.serialize())?;
+        }
+
+        // generated objects are rebuilt by the reader
+        if let Some(generator) = world.generator() {
+            writer.record(tag::GENERATOR, &generator.serialize())?;
+        }
+
+        writer.record(tag::MOVEMENT, &state.movement.serialize())?;
+        for player in state.players.values() {
+            writer.record(tag::PLAYER, &player.serialize())?;
+        }
+        writer.record(tag::BEGIN, &Flush::serialize())?;
+        Ok(writer)
+    }
+
+    pub fn write(&mut self, frame: &ReplayFrame) -> BlazedResult {
+        let mut tick = Vec::with_capacity(1 + 3 * size_of::<u64>());
+        tick.push(0);
+        tick.extend_from_slice(&frame.tick.to_le_bytes());
+        tick.extend_from_slice(&(frame.time.as_micros() as u64).to_le_bytes());
+        tick.extend_from_slice(&frame.hash.to_le_bytes());
+        self.record(tag::TICK, &tick)?;
+
+        for event in &frame.events {
+            match *event {
+                ReplayEvent::Joined(obj) => self.record(tag::JOINED, &obj.serialize()),
+                ReplayEvent::Left(id) => self.record(tag::LEFT, &RemObj { id }.serialize()),
+                ReplayEvent::Input { id, input } => {
+                    let tag = match input {
+                        Input::Keyboard(_) => tag::KEYBOARD,
+                        Input::Wheel(_) => tag::WHEEL,
+                        Input::Motion(_) => tag::MOTION,
+                    };
+                    let mut bytes = input.serialize();
+                    bytes.splice(1..1, id.to_le_bytes());
+                    self.record(tag, &bytes)
+                }
+                ReplayEvent::Respawned { id, pos } => {
+                    self.record(tag::RESPAWNED, &Respawn { id, pos }.serialize())
+                }
+                ReplayEvent::Movement(movement) => {
+                    self.record(tag::MOVEMENT, &movement.serialize())
+                }
+                ReplayEvent::State(upt) => self.record(tag::STATE, &upt.serialize()),
+            }?;
+        }
+        Ok(())
+    }
+
+    /// Bytes written so far.
+    pub const fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn flush(&mut self) -> BlazedResult {
+        self.inner.flush().map_err(Into::into)
+    }
+
+    /// Write a serialized packet, its identity replaced by the tag.
+    fn record(&mut self, tag: u8, packet: &[u8]) -> BlazedResult {
+        let mut bytes = packet.to_vec();
+        bytes[0] = tag;
+        self.write_all(&frame(&bytes)?)
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> BlazedResult {
+        self.inner.write_all(bytes)?;
+        self.size += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+/// A record of a file, past its starting state.
+enum Record {
+    Tick(ReplayFrame), // without its events
+    Event(ReplayEvent),
+}
+
+/// Reads a replay file (as written by [`ReplayWriter`]), frame by frame.
+///
+/// A file that ends partway through a record (e.g., the server was killed) ends at the last whole one.
+#[derive(Debug)]
+pub struct ReplayReader<R: Read> {
+    inner: R,
+    start: ReplayState,
+    next: Option<ReplayFrame>, // the next frame (its tick already read)
+    buf: Vec<u8>,
+}
+
+impl ReplayReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> BlazedResult<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| format!("Couldn't open replay '{}': {e}", path.display()))?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> ReplayReader<R> {
+    /// Read the header and starting state.
+    pub fn new(mut inner: R) -> BlazedResult<Self> {
+        let mut header = [0; REPLAY_MAGIC.len() + size_of::<u16>()];
+        match inner.read_exact(&mut header) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Err(ReplayError::NotReplay)?,
+            result => result?,
+        }
+        if header[..REPLAY_MAGIC.len()] != REPLAY_MAGIC {
+            return Err(ReplayError::NotReplay.into());
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != REPLAY_VERSION {
+            return Err(ReplayError::Version(version).into());
+        }
+
+        let mut buf = vec![0; u16::MAX as usize];
+        let (mut authored, mut spawns) = (Vec::new(), Vec::new());
+        let (mut generator, mut ambient) = (None, Ambient::default());
+        let (mut movement, mut players) = (None, BTreeMap::new());
+
+        loop {
+            let (tag, bytes) = read_record(&mut inner, &mut buf)?.ok_or(ReplayError::Truncated)?;
+            match tag {
+                tag::AMBIENT => {
+                    ambient = Ambient::deserialize(sized(tag, bytes, Ambient::UNPADDED_SIZE)?)
+                }
+                tag::SPAWN => spawns.push(
+                    SpawnPoint::deserialize(sized(tag, bytes, SpawnPoint::UNPADDED_SIZE)?).pos,
+                ),
+                tag::STATIC => authored.push(StaticObj::deserialize(sized(
+                    tag,
+                    bytes,
+                    StaticObj::UNPADDED_SIZE,
+                )?)),
+                tag::GENERATOR => {
+                    generator = Some(Generator::deserialize(sized(
+                        tag,
+                        bytes,
+                        Generator::UNPADDED_SIZE,
+                    )?))
+                }
+                tag::MOVEMENT => {
+                    movement = Some(Movement::deserialize(sized(
+                        tag,
+                        bytes,
+                        Movement::UNPADDED_SIZE,
+                    )?))
+                }
+                tag::PLAYER => {
+                    let player = UptObj::deserialize(sized(tag, bytes, UptObj::UNPADDED_SIZE)?);
+                    players.insert(player.id, player);
+                }
+                tag::BEGIN => break,
+                other => return Err(ReplayError::Unexpected(other).into()),
+            }
+        }
+
+        let start = ReplayState {
+            world: World::new(authored, generator, spawns, ambient),
+            movement: movement.ok_or(ReplayError::Missing("movement rules"))?,
+            players,
+        };
+        Ok(Self {
+            inner,
+            start,
+            next: None,
+            buf,
+        })
+    }
+
+    /// The game as it was before the first frame.
+    pub const fn start(&self) -> &ReplayState {
+        &self.start
+    }
+
+    /// The next tick (if any).
+    pub fn next_frame(&mut self) -> BlazedResult<Option<ReplayFrame>> {
+        let mut frame = match self.next.take() {
+            Some(next) => next,
+            None => match self.read()? {
+                Some(Record::Tick(frame)) => frame,
+                Some(Record::Event(_)) => return Err(ReplayError::Unexpected(tag::TICK).into()),
+                None => return Ok(None),
+            },
+        };
+
+        loop {
+            match self.read()? {
+                Some(Record::Event(event)) => frame.events.push(event),
+                Some(Record::Tick(next)) => {
+                    self.next = Some(next);
+                    break;
+                }
+                None => break,
+            }
+        }
+        Ok(Some(frame))
+    }
+
+    fn read(&mut self) -> BlazedResult<Option<Record>> {
+        let (tag, bytes) = match read_record(&mut self.inner, &mut self.buf) {
+            Ok(Some(record)) => record,
+            Ok(None) => return Ok(None),
+            Err(BlazedError::Replay(ReplayError::Truncated)) => {
+                warn!("[Replay] Ignoring a truncated record");
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+
+        let event = match tag {
+            tag::TICK => {
+                let bytes = sized(tag, bytes, 3 * size_of::<u64>())?;
+                let mut values = bytes
+                    .chunks_exact(size_of::<u64>())
+                    .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()));
+
+                return Ok(Some(Record::Tick(ReplayFrame {
+                    tick: values.next().unwrap_or_default(),
+                    time: Duration::from_micros(values.next().unwrap_or_default()),
+                    hash: values.next().unwrap_or_default(),
+                    events: Vec::new(),
+                })));
+            }
+            tag::JOINED => ReplayEvent::Joined(UptObj::deserialize(sized(
+                tag,
+                bytes,
+                UptObj::UNPADDED_SIZE,
+            )?)),
+            tag::LEFT => {
+                ReplayEvent::Left(RemObj::deserialize(sized(tag, bytes, RemObj::UNPADDED_SIZE)?).id)
+            }
+            tag::KEYBOARD | tag::WHEEL | tag::MOTION => {
+                let Some((id, bytes)) = bytes.split_first_chunk() else {
+                    return Err(ReplayError::Malformed {
+                        tag,
+                        len: bytes.len(),
+                    }
+                    .into());
+                };
+                let input = match tag {
+                    tag::KEYBOARD => Input::Keyboard(Keyboard::deserialize(sized(
+                        tag,
+                        bytes,
+                        Keyboard::UNPADDED_SIZE,
+                    )?)),
+                    tag::WHEEL => {
+                        Input::Wheel(Wheel::deserialize(sized(tag, bytes, Wheel::UNPADDED_SIZE)?))
+                    }
+                    _ => Input::Motion(MotionOpt::parse(bytes).ok_or(ReplayError::Malformed {
+                        tag,
+                        len: bytes.len(),
+                    })?),
+                };
+                ReplayEvent::Input {
+                    id: Id::from_le_bytes(*id),
+                    input,
+                }
+            }
+            tag::RESPAWNED => {
+                let Respawn { id, pos } =
+                    Respawn::deserialize(sized(tag, bytes, Respawn::UNPADDED_SIZE)?);
+                ReplayEvent::Respawned { id, pos }
+            }
+            tag::MOVEMENT => ReplayEvent::Movement(Movement::deserialize(sized(
+                tag,
+                bytes,
+                Movement::UNPADDED_SIZE,
+            )?)),
+            tag::STATE => {
+                ReplayEvent::State(UptObjOpt::parse(bytes).ok_or(ReplayError::Malformed {
+                    tag,
+                    len: bytes.len(),
+                })?)
+            }
+            other => return Err(ReplayError::Unexpected(other).into()),
+        };
+        Ok(Some(Record::Event(event)))
+    }
+}
+
+impl<R: Read> Iterator for ReplayReader<R> {
+    type Item = BlazedResult<ReplayFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+/// Every replay file of a directory, oldest first (as named by the server's recorder).
+pub fn replay_files(dir: impl AsRef<Path>) -> BlazedResult<Vec<PathBuf>> {
+    let mut files = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == REPLAY_EXT))
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+/// Read a single record (none at the end of the file), returning its tag and contents.
+fn read_record<'a>(
+    inner: &mut impl Read,
+    buf: &'a mut [u8],
+) -> BlazedResult<Option<(u8, &'a [u8])>> {
+    let mut len = [0; size_of::<u16>()];
+    match inner.read(&mut len[..1]) {
+        Ok(0) => return Ok(None),
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+    let len = match inner.read_exact(&mut len[1..]) {
+        Ok(()) => u16::from_le_bytes(len) as usize,
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Err(ReplayError::Truncated)?,
+        Err(e) => Err(e)?,
+    };
+
+    let record = &mut buf[..len];
+    match inner.read_exact(record) {
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Err(ReplayError::Truncated)?,
+        result => result?,
+    }
+    let (&tag, bytes) = record
+        .split_first()
+        .ok_or(ReplayError::Malformed { tag: 0, len })?;
+    Ok(Some((tag, bytes)))
+}
+
+/// The contents of a record, if they're as long as they should be.
+fn sized(tag: u8, bytes: &[u8], len: usize) -> BlazedResult<&[u8]> {
+    if bytes.len() != len {
+        return Err(ReplayError::Malformed {
+            tag,
+            len: bytes.len(),
+        }
+        .into());
+    }
+    Ok(bytes)
+}
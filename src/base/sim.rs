@@ -0,0 +1,420 @@
+use crate::*;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+};
+use ultraviolet::Vec3;
+
+/// FNV-1a, which (unlike the standard hasher) is the same for every build and platform.
+#[derive(Clone, Copy, Debug)]
+pub struct StateHasher(u64);
+
+impl StateHasher {
+    const BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    pub const fn new() -> Self {
+        Self(Self::BASIS)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Bit for bit (so any divergence shows, however small).
+    pub fn write_f32(&mut self, value: f32) {
+        self.write(&value.to_bits().to_le_bytes());
+    }
+
+    pub fn write_vec3(&mut self, value: Vec3) {
+        for v in [value.x, value.y, value.z] {
+            self.write_f32(v);
+        }
+    }
+
+    pub const fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UptObj {
+    /// Apply a player's input (as the server does once it arrives).
+    pub fn apply(&mut self, input: Input, movement: &Movement) {
+        match input {
+            Input::Keyboard(kb) => {
+                if kb.is_pressed == 1 {
+                    self.keys |= Keys::from(kb);
+                } else {
+                    self.keys -= Keys::from(kb);
+                }
+            }
+            Input::Wheel(wheel) => self.cam.upt_fov(wheel.precise_y, movement),
+            Input::Motion(motion) => self.cam.look_at(
+                motion.xrel.unwrap_or_default(),
+                motion.yrel.unwrap_or_default(),
+            ),
+        }
+    }
+
+    /// Advance the player by a single step (unless at rest), returning whether it has.
+    pub fn step(&mut self, world: &Collider, movement: &Movement) -> bool {
+        let is_moving = !self.keys.is_empty() || !self.cam.is_resting(movement);
+        if is_moving {
+            self.cam.input(self.keys, world, movement);
+        }
+        is_moving
+    }
+
+    /// Hash of whatever the simulation of the player depends on.
+    pub fn state_hash(&self) -> u64 {
+        let cam = &self.cam;
+        let mut hasher = StateHasher::new();
+        hasher.write(&self.keys.bits().to_le_bytes());
+        hasher.write(&[cam.state.bits()]);
+        hasher.write_f32(cam.yaw.radians());
+        hasher.write_f32(cam.pitch.radians());
+        for v in [cam.eye, cam.velocity, cam.target, cam.up] {
+            hasher.write_vec3(v);
+        }
+        hasher.finish()
+    }
+}
+
+/// Hash of every player's state (in order of identity).
+pub fn tick_hash<'a>(players: impl IntoIterator<Item = &'a UptObj>) -> u64 {
+    let mut players = players.into_iter().collect::<Vec<_>>();
+    players.sort_unstable_by_key(|p| p.id);
+
+    let mut hasher = StateHasher::new();
+    for player in players {
+        hasher.write(&player.id.to_le_bytes());
+        hasher.write_u64(player.state_hash());
+    }
+    hasher.finish()
+}
+
+/// The game simulated from inputs alone, step by step as the server does
+/// (e.g., to check that a recording reproduces the server's hashes).
+#[derive(Debug)]
+pub struct Simulation {
+    collider: Collider,
+    movement: Movement,
+    players: BTreeMap<Id, UptObj>,
+    tick: u64,
+}
+
+impl Simulation {
+    pub fn new(world: &World, movement: Movement) -> Self {
+        Self {
+            collider: world.collider(),
+            movement,
+            players: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Simulation steps so far.
+    pub const fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub const fn players(&self) -> &BTreeMap<Id, UptObj> {
+        &self.players
+    }
+
+    pub fn join(&mut self, player: UptObj) {
+        self.players.insert(player.id, player);
+    }
+
+    pub fn leave(&mut self, id: Id) {
+        self.players.remove(&id);
+    }
+
+    pub fn input(&mut self, id: Id, input: Input) {
+        if let Some(player) = self.players.get_mut(&id) {
+            player.apply(input, &self.movement);
+        }
+    }
+
+    pub fn respawn(&mut self, id: Id, pos: Vec3) {
+        if let Some(player) = self.players.get_mut(&id) {
+            player.cam.respawn(pos);
+        }
+    }
+
+    pub fn set_movement(&mut self, movement: Movement) {
+        self.movement = movement;
+    }
+
+    /// Advance every player by a single step, returning the hash of the game after it.
+    pub fn step(&mut self) -> u64 {
+        for player in self.players.values_mut() {
+            player.step(&self.collider, &self.movement);
+        }
+        self.tick += 1;
+        self.hash()
+    }
+
+    pub fn hash(&self) -> u64 {
+        tick_hash(self.players.values())
+    }
+}
+
+/// The server's simulation of a player has diverged from the prediction.
+#[derive(Clone, Copy, Debug)]
+pub struct Desync {
+    pub tick: u64,
+    pub predicted: CameraAttr,
+    pub actual: CameraAttr, // as the server has it
+}
+
+impl fmt::Display for Desync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (predicted, actual) = (self.predicted.eye, self.actual.eye);
+        write!(
+            f,
+            "Tick {}: predicted {predicted:?}, but the server has {actual:?} ({} apart)",
+            self.tick,
+            (predicted - actual).mag()
+        )
+    }
+}
+
+/// inputs kept to check snapshots against (far more than are ever in flight)
+const SENT: usize = 64;
+
+/// Checks the server's snapshots of the player against the client's own prediction of them.
+///
+/// Each snapshot is predicted from the one before: stepped as often as the server has stepped since,
+/// with the player's own input applied at the tick the server says it applied it. Snapshots that follow
+/// several new inputs (or a respawn) aren't checked, as when each of them was applied isn't known.
+#[derive(Debug, Default)]
+pub struct DesyncCheck {
+    sent: VecDeque<Input>, // oldest first
+    latest: Option<Snapshot>,
+    checked: u64, // snapshots compared
+    desyncs: u64, // of which diverged
+}
+
+impl DesyncCheck {
+    pub const fn checked(&self) -> u64 {
+        self.checked
+    }
+
+    pub const fn desyncs(&self) -> u64 {
+        self.desyncs
+    }
+
+    /// Remember an input, as numbered once sent.
+    pub fn sent(&mut self, input: Input) {
+        if self.sent.len() == SENT {
+            self.sent.pop_front();
+        }
+        self.sent.push_back(input);
+    }
+
+    /// Forget the latest snapshot (e.g., the player has been placed elsewhere), as it can't be predicted from.
+    pub fn reset(&mut self) {
+        self.latest = None;
+    }
+
+    /// Compare a snapshot against the prediction from the one before (if there's any).
+    pub fn check(
+        &mut self,
+        snapshot: Snapshot,
+        world: &Collider,
+        movement: &Movement,
+    ) -> Option<Desync> {
+        // (datagrams may arrive out of order)
+        if self.latest.is_some_and(|prev| prev.tick >= snapshot.tick) {
+            return None;
+        }
+        let prev = self.latest.replace(snapshot)?;
+
+        let mut player = UptObj {
+            keys: prev.keys,
+            cam: prev.cam,
+            ..Default::default()
+        };
+        let advance = |player: &mut UptObj, steps: u64| {
+            for _ in 0..steps {
+                player.step(world, movement);
+            }
+        };
+
+        if (snapshot.input_seq, snapshot.input_tick) == (prev.input_seq, prev.input_tick) {
+            advance(&mut player, snapshot.tick - prev.tick);
+        } else {
+            // a single input since (applied before the step of its tick)
+            let is_next = prev.input_seq.checked_add(1) == Some(snapshot.input_seq);
+            let is_between = (prev.tick..snapshot.tick).contains(&snapshot.input_tick);
+            let input = self
+                .sent
+                .iter()
+                .rev()
+                .find(|input| input.seq() == snapshot.input_seq)
+                .filter(|_| is_next && is_between)?;
+
+            advance(&mut player, snapshot.input_tick - prev.tick);
+            player.apply(*input, movement);
+            advance(&mut player, snapshot.tick - snapshot.input_tick);
+        }
+        self.checked += 1;
+
+        if player.state_hash() == snapshot.hash {
+            return None;
+        }
+        self.desyncs += 1;
+        Some(Desync {
+            tick: snapshot.tick,
+            predicted: player.cam,
+            actual: snapshot.cam,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The server's simulation of a player, snapshotting it every few steps.
+    struct Server {
+        world: Collider,
+        player: UptObj,
+        tick: u64,
+        input: (u64, u32), // tick and number of the latest
+    }
+
+    impl Server {
+        fn new() -> Self {
+            Self {
+                world: Collider::default(),
+                player: UptObj {
+                    cam: CameraAttr::new(Vec3::zero()),
+                    ..Default::default()
+                },
+                tick: 0,
+                input: (0, 0),
+            }
+        }
+
+        fn apply(&mut self, input: Input) {
+            self.player.apply(input, &Movement::default());
+            self.input = (self.tick, input.seq());
+        }
+
+        fn step(&mut self, steps: u64) -> Snapshot {
+            for _ in 0..steps {
+                self.player.step(&self.world, &Movement::default());
+                self.tick += 1;
+            }
+            Snapshot {
+                tick: self.tick,
+                hash: self.player.state_hash(),
+                input_tick: self.input.0,
+                input_seq: self.input.1,
+                keys: self.player.keys,
+                cam: self.player.cam,
+            }
+        }
+    }
+
+    fn check(check: &mut DesyncCheck, server: &Server, snapshot: Snapshot) -> Option<Desync> {
+        check.check(snapshot, &server.world, &Movement::default())
+    }
+
+    #[test]
+    fn snapshots_are_predicted_from_the_one_before() {
+        let (mut server, mut client) = (Server::new(), DesyncCheck::default());
+
+        // nothing to predict the first one from
+        let first = server.step(5);
+        assert!(check(&mut client, &server, first).is_none());
+        assert_eq!(client.checked(), 0);
+
+        // walking after pressing W within the next snapshot's steps
+        let w = Input::key(Keys::W, true).numbered(1);
+        client.sent(w);
+        server.step(3);
+        server.apply(w);
+        for _ in 0..3 {
+            let snapshot = server.step(SNAPSHOT_RATE);
+            assert!(check(&mut client, &server, snapshot).is_none());
+        }
+        assert!(server.player.cam.eye.mag() > 0.0);
+        assert_eq!((client.checked(), client.desyncs()), (3, 0));
+    }
+
+    #[test]
+    fn unknown_inputs_are_not_checked() {
+        let (mut server, mut client) = (Server::new(), DesyncCheck::default());
+        let first = server.step(1);
+        check(&mut client, &server, first);
+
+        // several inputs since the latest snapshot
+        for seq in 1..=2 {
+            let input = Input::key(Keys::W, seq == 1).numbered(seq);
+            client.sent(input);
+            server.step(1);
+            server.apply(input);
+        }
+        let snapshot = server.step(SNAPSHOT_RATE);
+        assert!(check(&mut client, &server, snapshot).is_none());
+
+        // or one the client doesn't know of
+        server.apply(Input::key(Keys::A, true).numbered(3));
+        let snapshot = server.step(SNAPSHOT_RATE);
+        assert!(check(&mut client, &server, snapshot).is_none());
+        assert_eq!(client.checked(), 0);
+
+        // which the next one is predicted from again
+        let snapshot = server.step(SNAPSHOT_RATE);
+        assert!(check(&mut client, &server, snapshot).is_none());
+        assert_eq!(client.checked(), 1);
+    }
+
+    #[test]
+    fn diverged_predictions_are_desyncs() {
+        let (mut server, mut client) = (Server::new(), DesyncCheck::default());
+        let first = server.step(1);
+        check(&mut client, &server, first);
+
+        // e.g., the server has had other keys pressed than the client has sent
+        client.sent(Input::key(Keys::W, true).numbered(1));
+        server.apply(Input::key(Keys::W | Keys::D, true).numbered(1));
+        let snapshot = server.step(SNAPSHOT_RATE);
+        let desync = check(&mut client, &server, snapshot).unwrap();
+        assert_eq!(desync.tick, 1 + SNAPSHOT_RATE);
+        assert_eq!((client.checked(), client.desyncs()), (1, 1));
+
+        // and a state the player has been in is no match for a later tick
+        let (mut server, mut client) = (Server::new(), DesyncCheck::default());
+        let w = Input::key(Keys::W, true).numbered(1);
+        client.sent(w);
+        server.apply(w);
+        let earlier = server.step(SNAPSHOT_RATE);
+        check(&mut client, &server, earlier);
+        let later = Snapshot {
+            tick: earlier.tick + SNAPSHOT_RATE,
+            ..earlier
+        };
+        assert!(check(&mut client, &server, later).is_some());
+
+        // nor is anything before a respawn
+        client.reset();
+        let snapshot = server.step(1);
+        assert!(check(&mut client, &server, snapshot).is_none());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::sync::Arc;
+
+/// How spawn points are chosen.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SpawnPolicy {
+    #[default]
+    Random,
+    RoundRobin,
+    Farthest, // farthest from every other player
+}
+
+impl SpawnPolicy {
+    /// Spawn policy by name (`random`, `round-robin` or `farthest`).
+    pub fn parse(s: &str) -> BlazedResult<Self> {
+        match s {
+            "random" => Ok(Self::Random),
+            "round-robin" => Ok(Self::RoundRobin),
+            "farthest" => Ok(Self::Farthest),
+            _ => Err(format!(
+                "Unknown spawn policy '{s}' (expected random, round-robin or farthest)."
+            )
+            .into()),
+        }
+    }
+}
+
+/// Collision-free placement of (re)spawning players.
+#[derive(Debug)]
+pub struct Spawner {
+    points: Vec<Vec3>,
+    policy: SpawnPolicy,
+    collider: Arc<Collider>,
+    kill_depth: f32,
+    next: usize,
+    rng: ChaCha8Rng, // seeded by the world, so spawns only depend on what has happened
+}
+
+impl Spawner {
+    /// distance below the lowest geometry at which players are respawned
+    const KILL_MARGIN: f32 = 128.0;
+
+    /// vertical distance between placement attempts above an obstructed spawn point
+    const NUDGE: f32 = 1.0;
+
+    /// maximum placement attempts above an obstructed spawn point
+    const MAX_NUDGES: usize = 64;
+
+    pub fn new(world: &World, collider: Arc<Collider>, policy: SpawnPolicy) -> Self {
+        // without spawn points, players spawn at the origin
+        let points = if world.spawns().is_empty() {
+            vec![Vec3::zero()]
+        } else {
+            world.spawns().to_vec()
+        };
+
+        let lowest = world
+            .objects()
+            .map(|obj| obj.bounds().min.y)
+            .chain(points.iter().map(|p| p.y))
+            .fold(f32::INFINITY, f32::min);
+
+        Self {
+            points,
+            policy,
+            collider,
+            kill_depth: lowest - Self::KILL_MARGIN,
+            next: 0,
+            rng: ChaCha8Rng::seed_from_u64(world.generator().map_or(0, |g| g.seed)),
+        }
+    }
+
+    /// Players below this height have fallen out of the world.
+    pub const fn kill_depth(&self) -> f32 {
+        self.kill_depth
+    }
+
+    /// Choose where a player spawns, given the positions of every other player.
+    pub fn pick(&mut self, others: &[Vec3]) -> Vec3 {
+        let preferred = match self.policy {
+            SpawnPolicy::Random => self.rng.random_range(0..self.points.len()),
+            SpawnPolicy::RoundRobin => {
+                let idx = self.next % self.points.len();
+                self.next = idx + 1;
+                idx
+            }
+            SpawnPolicy::Farthest => (0..self.points.len())
+                .max_by(|&a, &b| {
+                    let a = Self::clearance(self.points[a], others);
+                    let b = Self::clearance(self.points[b], others);
+                    a.total_cmp(&b)
+                })
+                .unwrap_or_default(),
+        };
+
+        // the preferred point first, then every other point in order
+        let candidates = (0..self.points.len())
+            .map(|i| self.points[(preferred + i) % self.points.len()])
+            .collect::<Vec<_>>();
+
+        if let Some(&pos) = candidates.iter().find(|&&pos| self.is_free(pos, others)) {
+            return pos;
+        }
+
+        // every point is obstructed, so look above the preferred one
+        let base = candidates[0];
+        (1..=Self::MAX_NUDGES)
+            .map(|i| base + Vec3::unit_y() * (i as f32 * Self::NUDGE))
+            .find(|&pos| self.is_free(pos, others))
+            .unwrap_or(base)
+    }
+
+    /// A player could stand here without intersecting geometry or other players.
+    fn is_free(&self, pos: Vec3, others: &[Vec3]) -> bool {
+        let body = CameraAttr::new(pos).bounds();
+        self.collider.is_clear(&body)
+            && others
+                .iter()
+                .all(|&other| !CameraAttr::new(other).bounds().intersects(&body))
+    }
+
+    /// Distance to the nearest other player.
+    fn clearance(pos: Vec3, others: &[Vec3]) -> f32 {
+        others
+            .iter()
+            .map(|&other| (other - pos).mag())
+            .fold(f32::INFINITY, f32::min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Spawn points along the x axis, far enough apart for players not to touch.
+    const POINTS: [Vec3; 3] = [
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(10.0, 0.0, 0.0),
+        Vec3::new(20.0, 0.0, 0.0),
+    ];
+
+    fn spawner_at(points: &[Vec3], objects: Vec<StaticObj>, policy: SpawnPolicy) -> Spawner {
+        let world = World::new(objects, None, points.to_vec(), Ambient::default());
+        Spawner::new(&world, Arc::new(world.collider()), policy)
+    }
+
+    #[test]
+    fn round_robin_takes_turns() {
+        let mut spawner = spawner_at(&POINTS, Vec::new(), SpawnPolicy::RoundRobin);
+        let picks = (0..4).map(|_| spawner.pick(&[])).collect::<Vec<_>>();
+        assert_eq!(picks, [POINTS[0], POINTS[1], POINTS[2], POINTS[0]]);
+
+        // an occupied point is passed over for the next one
+        assert_eq!(spawner.pick(&[POINTS[1]]), POINTS[2]);
+    }
+
+    #[test]
+    fn farthest_keeps_clear_of_players() {
+        let mut spawner = spawner_at(&POINTS, Vec::new(), SpawnPolicy::Farthest);
+        assert_eq!(spawner.pick(&[Vec3::new(1.0, 0.0, 0.0)]), POINTS[2]);
+        assert_eq!(spawner.pick(&[Vec3::new(19.0, 0.0, 0.0)]), POINTS[0]);
+        assert_eq!(spawner.pick(&[POINTS[0], POINTS[2]]), POINTS[1]);
+    }
+
+    #[test]
+    fn obstructed_points_are_nudged_clear() {
+        // the only point is taken by another player
+        let pos = spawner_at(&POINTS[..1], Vec::new(), SpawnPolicy::RoundRobin).pick(&POINTS[..1]);
+        assert_eq!((pos.x, pos.z), (0.0, 0.0));
+        assert!(pos.y > 0.0);
+        assert!(
+            !CameraAttr::new(pos)
+                .bounds()
+                .intersects(&CameraAttr::new(POINTS[0]).bounds())
+        );
+
+        // or sunk into the geometry
+        let cube = StaticObj::new(
+            0,
+            MeshKind::NormalCube,
+            POINTS[0],
+            Vec3::one(),
+            Color::default(),
+        );
+        let pos = spawner_at(&POINTS[..1], vec![cube], SpawnPolicy::Farthest).pick(&[]);
+        assert!(pos.y > 0.0);
+        assert!(!cube.bounds().intersects(&CameraAttr::new(pos).bounds()));
+    }
+}
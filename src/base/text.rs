@@ -0,0 +1,62 @@
+use crate::*;
+use bytemuck::{Pod, Zeroable};
+use std::fmt;
+
+/// maximum encoded length of a text (bytes)
+const CAPACITY: usize = 255;
+
+/// A short line of text (e.g., a message to every player).
+#[repr(C)]
+#[derive(Clone, Copy, Eq, PartialEq, Pod, Zeroable)]
+pub struct Text {
+    len: u8,
+    bytes: [u8; CAPACITY],
+}
+
+impl Text {
+    /// Validate a text (surrounding whitespace is ignored).
+    pub fn new(s: &str) -> BlazedResult<Self> {
+        let s = s.trim();
+
+        if s.is_empty() {
+            return Err("Texts must not be empty.".into());
+        }
+        if s.len() > CAPACITY {
+            return Err(format!("Texts must not exceed {CAPACITY} bytes.").into());
+        }
+        if s.chars().any(char::is_control) {
+            return Err("Texts must not contain control characters.".into());
+        }
+
+        let mut bytes = [0; CAPACITY];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+
+        Ok(Self {
+            len: s.len() as u8,
+            bytes,
+        })
+    }
+
+    /// The text (empty if it isn't valid UTF-8).
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+impl fmt::Debug for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
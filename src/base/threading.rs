@@ -10,6 +10,7 @@ use std::sync::{
 pub struct Notifier {
     ready: Arc<AtomicBool>,
     cvar: Arc<Condvar>,
+    mutex: Arc<Mutex<()>>,
 }
 
 /// sets the ready flag without notifying
@@ -50,13 +51,16 @@ impl Spectator {
 
 impl Notifier {
     /// creates a new notifier from shared ready and condvar
-    const fn new(ready: Arc<AtomicBool>, cvar: Arc<Condvar>) -> Self {
-        Self { ready, cvar }
+    const fn new(ready: Arc<AtomicBool>, cvar: Arc<Condvar>, mutex: Arc<Mutex<()>>) -> Self {
+        Self { ready, cvar, mutex }
     }
 
     /// sets the ready flag and notifies one waiting thread
     pub fn notify(&self) {
         self.ready.store(true, Ordering::Release);
+
+        // not between the waiter's check and its wait
+        let _guard = self.mutex.lock();
         self.cvar.notify_one();
     }
 }
@@ -66,7 +70,7 @@ impl Notifier {
 pub struct Waiter {
     ready: Arc<AtomicBool>,
     cvar: Arc<Condvar>,
-    mutex: Mutex<()>,
+    mutex: Arc<Mutex<()>>,
 }
 
 impl Waiter {
@@ -75,14 +79,13 @@ impl Waiter {
         let backoff = Backoff::new();
 
         loop {
-            let is_ready = self.ready.load(Ordering::Acquire);
-            if is_ready {
+            if self.ready.load(Ordering::Acquire) {
                 break;
             }
 
             if backoff.is_completed() {
                 let mut guard = self.mutex.lock();
-                if !is_ready {
+                if !self.ready.load(Ordering::Acquire) {
                     self.cvar.wait(&mut guard);
                 }
             } else {
@@ -93,7 +96,7 @@ impl Waiter {
 
     /// returns a notifier handle for setting and notifying
     pub fn notifier(&self) -> Notifier {
-        Notifier::new(self.ready.clone(), self.cvar.clone())
+        Notifier::new(self.ready.clone(), self.cvar.clone(), self.mutex.clone())
     }
 
     /// returns a setter handle for setting only
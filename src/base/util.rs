@@ -1,4 +1,7 @@
-use std::time::Duration;
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
 
 #[inline(always)]
 pub fn clamp_unchecked(value: f32, min: f32, max: f32) -> f32 {
@@ -22,3 +25,9 @@ pub fn tick_dur(value: impl Into<f32>) -> Duration {
         Duration::ZERO
     }
 }
+
+/// monotonic microseconds elapsed since this process first asked.
+pub fn timestamp() -> u64 {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed().as_micros() as u64
+}
@@ -0,0 +1,171 @@
+use crate::*;
+use wopt::*;
+
+/// A single piece of static world geometry.
+#[derive(Clone, Copy, Debug, WithOpt)]
+#[wopt(derive(Clone, Copy, Debug, Default))]
+pub struct StaticObj {
+    pub id: Id,
+    pub mesh: MeshKind,
+    pub pos: Vec3,
+    pub dim: Vec3,
+    pub color: Color,
+}
+
+impl StaticObj {
+    pub fn new(id: Id, mesh: MeshKind, pos: Vec3, dim: Vec3, color: Color) -> Self {
+        Self {
+            id,
+            mesh,
+            pos,
+            dim,
+            color,
+        }
+    }
+
+    pub fn trans(&self) -> Transformations {
+        Transformations::new(self.pos, self.dim)
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_center(self.pos, self.dim)
+    }
+}
+
+/// A position players may (re)spawn at.
+#[derive(Clone, Copy, Debug, WithOpt)]
+#[wopt(derive(Clone, Copy, Debug, Default))]
+pub struct SpawnPoint {
+    pub pos: Vec3,
+}
+
+/// Global lighting of a world.
+#[derive(Clone, Copy, Debug, WithOpt)]
+#[wopt(derive(Clone, Copy, Debug, Default))]
+pub struct Ambient {
+    pub sky: Vec3,     // background (clear) color
+    pub strength: f32, // ambient light strength of lit objects
+}
+
+impl Default for Ambient {
+    fn default() -> Self {
+        Self {
+            sky: Vec3::new(0.4, 0.4, 0.4),
+            strength: 0.2,
+        }
+    }
+}
+
+/// The static geometry of a game (authored by the server).
+#[derive(Clone, Debug, Default)]
+pub struct World {
+    authored: Vec<StaticObj>,
+    generator: Option<Generator>,
+    generated: Vec<StaticObj>,
+    spawns: Vec<Vec3>,
+    ambient: Ambient,
+}
+
+impl World {
+    pub fn new(
+        authored: Vec<StaticObj>,
+        generator: Option<Generator>,
+        spawns: Vec<Vec3>,
+        ambient: Ambient,
+    ) -> Self {
+        // generated objects are numbered after the authored ones
+        let generated = generator
+            .map(|g| g.generate(authored.len() as Id, &spawns))
+            .unwrap_or_default();
+
+        Self {
+            authored,
+            generator,
+            generated,
+            spawns,
+            ambient,
+        }
+    }
+
+    /// The demo scene: a floor, four lights and a field of random cubes.
+    pub fn demo(seed: u64) -> Self {
+        let authored = vec![
+            StaticObj::new(
+                0,
+                MeshKind::NormalCube,
+                Vec3::new(0.0, -2.0, 0.0),
+                Vec3::new(7.5, 0.1, 7.5),
+                Color::new([1.0, 1.0, 1.0, 0.96], false),
+            ),
+            StaticObj::new(
+                1,
+                MeshKind::SimpleCube,
+                Vec3::new(3.0, 0.0, -4.0),
+                Vec3::new(0.5, 0.5, 0.5),
+                Color::new([0.0, 0.0, 1.0, 1.0], true),
+            ),
+            StaticObj::new(
+                2,
+                MeshKind::SimpleCube,
+                Vec3::new(0.0, 2.0, 8.5),
+                Vec3::new(0.5, 0.5, 0.5),
+                Color::new([0.0, 1.0, 0.0, 1.0], true),
+            ),
+            StaticObj::new(
+                3,
+                MeshKind::SimpleCube,
+                Vec3::new(-3.0, 1.0, -4.5),
+                Vec3::new(0.5, 0.5, 0.5),
+                Color::new([1.0, 0.0, 0.0, 1.0], true),
+            ),
+            StaticObj::new(
+                4,
+                MeshKind::SimpleCube,
+                Vec3::new(0.0, 20.0, 0.0),
+                Vec3::new(0.5, 0.5, 0.5),
+                Color::new([1.0, 1.0, 1.0, 1.0], true),
+            ),
+        ];
+        Self::new(
+            authored,
+            Some(Generator::demo(seed)),
+            Default::default(),
+            Default::default(),
+        )
+    }
+
+    /// The objects that were explicitly placed (i.e., not generated).
+    pub const fn authored(&self) -> &[StaticObj] {
+        self.authored.as_slice()
+    }
+
+    pub const fn generator(&self) -> Option<Generator> {
+        self.generator
+    }
+
+    /// Every object (authored, then generated).
+    pub fn objects(&self) -> impl Iterator<Item = &StaticObj> {
+        self.authored.iter().chain(self.generated.iter())
+    }
+
+    pub const fn spawns(&self) -> &[Vec3] {
+        self.spawns.as_slice()
+    }
+
+    pub const fn ambient(&self) -> Ambient {
+        self.ambient
+    }
+
+    pub const fn len(&self) -> usize {
+        self.authored.len() + self.generated.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Collision geometry of every object.
+    pub fn collider(&self) -> Collider {
+        Collider::new(self.objects().map(StaticObj::bounds))
+    }
+}
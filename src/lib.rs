@@ -3,26 +3,33 @@ mod base;
 pub use base::*;
 
 use atomint::*;
-use std::time::Duration;
+use std::{ops::Range, time::Duration};
 use ultraviolet::Vec3;
 
 pub type Id = u16;
 pub type AtomicId = <Id as AtomicInt>::Atomic;
 
+// disjoint identity ranges (world objects count up from zero, players take the rest)
+pub const WORLD_IDS: Range<Id> = 0..0xF000;
+pub const PLAYER_IDS: Range<Id> = WORLD_IDS.end..Id::MAX;
+
 // TODO - tune this
 pub const PACKET_SIZE: usize = 1024;
 
 // default dynamic ports (arbitrary, for now)
 pub const TCP_PORT: u16 = 54269;
 pub const UDP_PORT: u16 = 54277;
+pub const ADMIN_PORT: u16 = 54283;
 
 // common delays
 pub const SECOND: Duration = Duration::from_secs(1);
 pub const MILISECOND: Duration = Duration::from_millis(1);
 
 // platform rates
+pub const MAX_TPS: u16 = 1024;
 pub const GAME_SPEED: Duration = Duration::from_millis(3);
-pub const PING_MINIMUM: Duration = Duration::from_millis(10);
+pub const SYNC_RATE: Duration = Duration::from_millis(100);
+pub const SNAPSHOT_RATE: u64 = 32; // simulation steps between snapshots of each player
 
 // common mathematical values
 pub const RADIAN: f32 = std::f32::consts::PI / 180.0;